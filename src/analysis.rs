@@ -0,0 +1,111 @@
+use eframe::egui;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use syntect::parsing::{ParseState, ScopeStackOp, SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+use crate::folding::{self, FoldRegion};
+use crate::outline::{self, Symbol};
+
+/// Scope changes of each line, from one syntect pass over the text. Stops
+/// early if the parser gives up.
+pub type LineOps = Vec<Vec<(usize, ScopeStackOp)>>;
+
+pub fn parse_lines(text: &str, syntax: &SyntaxReference, syntax_set: &SyntaxSet) -> LineOps {
+    let mut state = ParseState::new(syntax);
+    let mut lines = Vec::new();
+    for (line_no, line) in LinesWithEndings::from(text).enumerate() {
+        match state.parse_line(line, syntax_set) {
+            Ok(ops) => lines.push(ops),
+            Err(err) => {
+                log::warn!("Failed to parse line {}: {}", line_no + 1, err);
+                break;
+            }
+        }
+    }
+    lines
+}
+
+/// What a document's analysis was worked out for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalysisKey {
+    pub revision: u64,
+    pub syntax: Option<String>,
+}
+
+/// Everything taken from one parse of a document, with the text it parsed
+/// for views that need more than these, like the Document Map.
+pub struct Analysis {
+    pub text: Arc<String>,
+    pub ops: Arc<LineOps>,
+    pub regions: Vec<FoldRegion>,
+    // definitions for the Function List, by the syntax's scopes
+    pub symbols: Vec<Symbol>,
+}
+
+// 在后台线程里运行，文本是提交时的副本
+pub fn analyze(text: String, syntax: Option<&SyntaxReference>, syntax_set: &SyntaxSet) -> Analysis {
+    let ops = syntax.map(|syntax| parse_lines(&text, syntax, syntax_set)).unwrap_or_default();
+    Analysis {
+        regions: folding::compute_regions(&text, syntax.map(|s| s.name.as_str()), &ops),
+        symbols: outline::extract_symbols(&text, &ops),
+        text: Arc::new(text),
+        ops: Arc::new(ops),
+    }
+}
+
+/// A result worked out on a worker thread, so a long parse never holds up a
+/// frame. One job runs at a time; when the text changes meanwhile the next
+/// one starts after it, and the old result is used until then.
+pub struct Background<K, T> {
+    running: Option<(K, Receiver<T>)>,
+    done: Option<(K, T)>,
+}
+
+impl<K: PartialEq, T: Send + 'static> Background<K, T> {
+    pub fn new() -> Self {
+        Self { running: None, done: None }
+    }
+
+    // The latest result, with what it was worked out for
+    pub fn get(&self) -> Option<&(K, T)> {
+        self.done.as_ref()
+    }
+
+    // Pick up a finished job; true if there is a new result
+    pub fn poll(&mut self) -> bool {
+        let Some((_, receiver)) = &self.running else {
+            return false;
+        };
+        match receiver.try_recv() {
+            Ok(result) => {
+                self.done = self.running.take().map(|(key, _)| (key, result));
+                true
+            }
+            Err(TryRecvError::Empty) => false,
+            Err(TryRecvError::Disconnected) => {
+                log::error!("Background job stopped without a result");
+                self.running = None;
+                false
+            }
+        }
+    }
+
+    // Whether a job for `key` should start now
+    pub fn wants(&self, key: &K) -> bool {
+        self.running.is_none() && self.done.as_ref().map(|(done, _)| done != key).unwrap_or(true)
+    }
+
+    pub fn start(&mut self, ctx: &egui::Context, key: K, job: impl FnOnce() -> T + Send + 'static) {
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            // 窗口可能已经关闭，结果没人要了
+            if sender.send(job()).is_ok() {
+                ctx.request_repaint();
+            }
+        });
+        self.running = Some((key, receiver));
+    }
+}
@@ -1,11 +1,30 @@
 use eframe::egui;
 use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
 use std::time::Instant;
 
-use crate::editor::{Document, DocumentCollection};
+use crate::editor::{self, Document, DocumentCollection};
+use crate::cursors::{Selection, Splice};
 use crate::theme::Theme;
 use crate::config::Config;
-use crate::ui::UiComponents;
+use crate::commands::Command;
+use crate::keymap::Keymap;
+use crate::picker::{FuzzyPicker, PickerRow};
+use crate::fuzzy::fuzzy_match;
+use crate::quick_open::QuickOpen;
+use crate::file_tree::{FileTree, TreeAction};
+use crate::minimap::Minimap;
+use crate::outline::FunctionList;
+use crate::panes::{self, EditorPanes, SplitDirection};
+use crate::tabs::{TabAction, TabBar, TabHistory};
+use crate::windows::{self, EditorWindow, WindowEvent};
+use crate::session::{Session, WindowSession};
+use crate::parser_rules::ParserRules;
+use crate::clipboard::{self, ClipboardHistory};
+use crate::column_editor::ColumnEditor;
+#[cfg(unix)]
+use crate::rpc::{self, RpcCall, RpcError};
 
 pub struct NotionApp {
     documents: DocumentCollection,
@@ -21,22 +40,107 @@ pub struct NotionApp {
     status_message: Option<(String, Instant)>,
     show_document_map: bool,
     show_function_list: bool,
+    function_list: FunctionList,
+    minimap: Minimap,
+    panes: EditorPanes,
+    tab_bar: TabBar,
+    tab_history: TabHistory,
+    windows: Vec<EditorWindow>,
+    next_window_serial: u64,
+    // main window's inner rect in screen coordinates, as of the last frame
+    screen_rect: Option<egui::Rect>,
+    // whether the restored window position was checked against the monitor
+    window_checked: bool,
+    instance_rx: Option<Receiver<Vec<PathBuf>>>,
+    keymap: Keymap,
+    command_palette: FuzzyPicker,
+    clipboard_history: ClipboardHistory,
+    clipboard_picker: FuzzyPicker,
+    column_editor: ColumnEditor,
+    quick_open: QuickOpen,
+    project_root: Option<PathBuf>,
+    file_tree: Option<FileTree>,
+    show_file_tree: bool,
+    #[cfg(unix)]
+    rpc_rx: Option<Receiver<RpcCall>>,
+}
+
+pub fn text_file_dialog() -> rfd::FileDialog {
+    rfd::FileDialog::new()
+        .add_filter("Text", &["txt", "md", "rs", "toml", "json", "py", "js", "html", "css"])
+        .add_filter("All Files", &["*"])
+}
+
+// Dim the window while files are dragged over it
+pub fn show_drop_overlay(ctx: &egui::Context, count: usize) {
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("file_drop_overlay"),
+    ));
+    let screen = ctx.screen_rect();
+    let accent = ctx.style().visuals.selection.stroke.color;
+    
+    painter.rect_filled(screen, 0.0, egui::Color32::from_black_alpha(140));
+    painter.rect_stroke(
+        screen.shrink(8.0),
+        8.0,
+        egui::Stroke::new(2.0, accent),
+    );
+    let text = if count == 1 {
+        "Drop to open".to_string()
+    } else {
+        format!("Drop to open {} items", count)
+    };
+    painter.text(
+        screen.center(),
+        egui::Align2::CENTER_CENTER,
+        text,
+        egui::FontId::proportional(24.0),
+        egui::Color32::WHITE,
+    );
+}
+
+// A command palette entry that matched the query
+struct CommandMatch {
+    cmd: Command,
+    text: String,
+    score: i32,
+    positions: Vec<usize>,
+    recent: Option<usize>,
 }
 
 impl NotionApp {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    pub fn new(cc: &eframe::CreationContext<'_>, config: Config) -> Self {
         // set up custom fonts if needed
         let fonts = egui::FontDefinitions::default();
         // TODO: Add custom fonts if needed
         
         cc.egui_ctx.set_fonts(fonts);
         
-        // load config
-        let config = Config::load().unwrap_or_default();
+        let mut app = Self::with_config(config);
+        app.keymap = Keymap::load();
+        app.function_list.set_rules(ParserRules::load());
         
         // apply theme
+        app.theme.apply_to_ctx(&cc.egui_ctx);
+        
+        // 脚本控制接口
+        #[cfg(unix)]
+        {
+            let socket_path = Config::runtime_dir().join(rpc::SOCKET_NAME);
+            match rpc::RpcServer::bind(&socket_path) {
+                Ok(server) => app.rpc_rx = Some(server.spawn(Some(cc.egui_ctx.clone()))),
+                Err(err) => log::error!("Failed to bind RPC socket {}: {}", socket_path.display(), err),
+            }
+        }
+        
+        app
+    }
+    
+    // Builds the app state without touching egui, so it can also run headless
+    pub fn with_config(config: Config) -> Self {
         let theme = Theme::new(&config.theme_name);
-        theme.apply_to_ctx(&cc.egui_ctx);
+        let layout = config.window.clone();
         
         Self {
             documents: DocumentCollection::new(),
@@ -50,16 +154,41 @@ impl NotionApp {
             find_text: String::new(),
             replace_text: String::new(),
             status_message: None,
-            show_document_map: false,
-            show_function_list: false,
+            show_document_map: layout.show_document_map,
+            show_function_list: layout.show_function_list,
+            function_list: FunctionList::new(),
+            minimap: Minimap::new(),
+            panes: EditorPanes::new(),
+            tab_bar: TabBar::new(),
+            tab_history: TabHistory::new(),
+            windows: Vec::new(),
+            next_window_serial: 0,
+            screen_rect: None,
+            window_checked: false,
+            instance_rx: None,
+            keymap: Keymap::defaults(),
+            command_palette: FuzzyPicker::new("command_palette", "Type a command..."),
+            clipboard_history: ClipboardHistory::new(),
+            clipboard_picker: FuzzyPicker::new("clipboard_history", "Paste from clipboard history..."),
+            column_editor: ColumnEditor::new(),
+            quick_open: QuickOpen::new(),
+            project_root: None,
+            file_tree: None,
+            show_file_tree: layout.show_file_tree,
+            #[cfg(unix)]
+            rpc_rx: None,
         }
     }
     
+    // Files handed over by later invocations of the binary arrive on this channel
+    pub fn set_instance_receiver(&mut self, rx: Receiver<Vec<PathBuf>>) {
+        self.instance_rx = Some(rx);
+    }
+    
     pub fn new_document(&mut self) {
         let mut doc = Document::new();
         // 使用配置中的设置
-        doc.line_numbers = self.config.line_numbers;
-        doc.word_wrap = self.config.word_wrap;
+        doc.apply_config(&self.config);
         
         self.documents.add(doc);
         self.active_document_index = Some(self.documents.len() - 1);
@@ -67,41 +196,57 @@ impl NotionApp {
     }
     
     pub fn open_document(&mut self) -> Result<()> {
-        if let Some(path) = rfd::FileDialog::new()
-            .add_filter("Text", &["txt", "md", "rs", "toml", "json", "py", "js", "html", "css"])
-            .add_filter("All Files", &["*"])
-            .pick_file() 
+        if let Some(path) = text_file_dialog().pick_file()
         {
-            match Document::from_file(&path) {
-                Ok(mut doc) => {
-                    // 使用配置中的设置
-                    doc.line_numbers = self.config.line_numbers;
-                    doc.word_wrap = self.config.word_wrap;
-                    
-                    self.documents.add(doc);
-                    self.active_document_index = Some(self.documents.len() - 1);
-                    self.set_status_message(format!("Opened {}", path.display()));
-                    Ok(())
-                },
-                Err(err) => {
-                    self.set_status_message(format!("Error opening file: {}", err));
-                    Err(err)
-                }
-            }
+            self.open_path(&path)
         } else {
             Ok(())
         }
     }
     
+    pub fn open_path(&mut self, path: &Path) -> Result<()> {
+        // 已经打开的文件直接切换到对应标签
+        if let Some(idx) = self.documents.position_by_path(path) {
+            self.active_document_index = Some(idx);
+            return Ok(());
+        }
+        
+        match Document::from_file(path) {
+            Ok(mut doc) => {
+                // 使用配置中的设置
+                doc.apply_config(&self.config);
+                
+                self.documents.add(doc);
+                self.active_document_index = Some(self.documents.len() - 1);
+                self.set_status_message(format!("Opened {}", path.display()));
+                Ok(())
+            },
+            Err(err) => {
+                self.set_status_message(format!("Error opening file: {}", err));
+                Err(err)
+            }
+        }
+    }
+    
+    pub fn open_folder_dialog(&mut self, ctx: &egui::Context) {
+        if let Some(path) = rfd::FileDialog::new().pick_folder() {
+            self.open_folder(ctx, &path);
+        }
+    }
+    
+    pub fn open_folder(&mut self, ctx: &egui::Context, path: &Path) {
+        self.project_root = Some(path.to_path_buf());
+        self.file_tree = Some(FileTree::new(path.to_path_buf(), ctx));
+        self.show_file_tree = true;
+        self.set_status_message(format!("Opened folder {}", path.display()));
+    }
+    
     pub fn save_document(&mut self) -> Result<()> {
         if let Some(idx) = self.active_document_index {
             let mut saved_path = None;
             if let Some(doc) = self.documents.get_mut(idx) {
                 if doc.path.is_none() {
-                    if let Some(path) = rfd::FileDialog::new()
-                        .add_filter("Text", &["txt", "md", "rs", "toml", "json", "py", "js", "html", "css"])
-                        .add_filter("All Files", &["*"])
-                        .save_file() 
+                    if let Some(path) = text_file_dialog().save_file()
                     {
                         doc.save_to_file(&path)?;
                         saved_path = Some(format!("Saved to {}", path.display()));
@@ -124,10 +269,7 @@ impl NotionApp {
     pub fn save_document_as(&mut self) -> Result<()> {
         if let Some(idx) = self.active_document_index {
             if let Some(doc) = self.documents.get_mut(idx) {
-                if let Some(path) = rfd::FileDialog::new()
-                    .add_filter("Text", &["txt", "md", "rs", "toml", "json", "py", "js", "html", "css"])
-                    .add_filter("All Files", &["*"])
-                    .save_file() 
+                if let Some(path) = text_file_dialog().save_file()
                 {
                     doc.save_to_file(&path)?;
                     self.set_status_message(format!("Saved to {}", path.display()));
@@ -140,30 +282,57 @@ impl NotionApp {
     pub fn close_document(&mut self) {
         if let Some(idx) = self.active_document_index {
             // TODO: Check for unsaved changes before closing
-            if self.documents.close(idx) {
+            if self.close_document_at(idx) {
                 self.set_status_message("Document closed");
-                if self.documents.len() == 0 {
-                    self.active_document_index = None;
-                } else {
-                    self.active_document_index = Some(idx.min(self.documents.len() - 1));
-                }
             }
         }
     }
     
-    pub fn find_text(&mut self) {
+    fn close_document_at(&mut self, idx: usize) -> bool {
+        if !self.documents.close(idx) {
+            return false;
+        }
+        self.document_removed(idx);
+        true
+    }
+    
+    // Take a document out of the main window, e.g. to move it to another one
+    fn take_document(&mut self, idx: usize) -> Option<Document> {
+        let doc = self.documents.take(idx)?;
+        self.document_removed(idx);
+        Some(doc)
+    }
+    
+    fn document_removed(&mut self, idx: usize) {
+        let remaining = self.documents.len();
+        self.active_document_index = panes::index_after_close(self.active_document_index, idx, remaining);
+        self.panes.document_closed(idx, remaining);
+        self.tab_history.document_closed(idx);
+    }
+    
+    pub fn find_text(&mut self) -> Option<usize> {
         if let Some(doc_idx) = self.active_document_index {
             if let Some(doc) = self.documents.get_mut(doc_idx) {
                 // 简单查找，仅查找第一个匹配项
                 if let Some(pos) = doc.content.find(&self.find_text) {
-                    doc.cursor_position = pos;
-                    doc.selection = Some((pos, pos + self.find_text.len()));
+                    doc.select(pos, pos + self.find_text.len());
                     self.set_status_message(format!("Found text at position {}", pos));
+                    return Some(pos);
                 } else {
                     self.set_status_message("Text not found");
                 }
             }
         }
+        None
+    }
+    
+    // Notepad++'s "Bookmark line" option of Mark All
+    fn bookmark_matching_lines(&mut self) {
+        let Some(doc) = self.active_document_index.and_then(|idx| self.documents.get_mut(idx)) else {
+            return;
+        };
+        let count = doc.bookmark_lines_matching(&self.find_text);
+        self.set_status_message(format!("Bookmarked {} lines", count));
     }
     
     pub fn replace_text(&mut self) {
@@ -173,11 +342,8 @@ impl NotionApp {
                     // 确保选中的文本与查找文本匹配
                     if doc.content[start..end] == self.find_text {
                         // 执行替换
-                        let before = doc.content[..start].to_string();
-                        let after = doc.content[end..].to_string();
-                        doc.content = format!("{}{}{}", before, self.replace_text, after);
-                        doc.selection = Some((start, start + self.replace_text.len()));
-                        doc.is_modified = true;
+                        let selection = Selection::new(start, start + self.replace_text.len());
+                        doc.apply_splice(Splice::new(start..end, self.replace_text.clone(), selection));
                         self.set_status_message("Text replaced");
                     } else {
                         self.set_status_message("Selected text doesn't match search text");
@@ -193,11 +359,466 @@ impl NotionApp {
         self.status_message = Some((message.into(), Instant::now()));
     }
     
+    // Saves every document that already has a path, in every window;
+    // untitled ones would need a dialog
+    pub fn save_all(&mut self) -> Result<usize> {
+        let mut saved = 0;
+        let collections = std::iter::once(&mut self.documents).chain(self.windows.iter_mut().map(|window| &mut window.documents));
+        for documents in collections {
+            for i in 0..documents.len() {
+                if let Some(doc) = documents.get_mut(i) {
+                    if doc.path.is_some() && doc.is_modified {
+                        doc.save()?;
+                        saved += 1;
+                    }
+                }
+            }
+        }
+        self.set_status_message(format!("Saved {} document(s)", saved));
+        Ok(saved)
+    }
+    
+    // Files open as tabs, folders in the sidebar
+    fn open_paths(&mut self, ctx: &egui::Context, paths: &[PathBuf]) {
+        for path in paths {
+            if path.is_dir() {
+                self.open_folder(ctx, path);
+            } else if let Err(err) = self.open_path(path) {
+                log::error!("Failed to open {}: {}", path.display(), err);
+            }
+        }
+    }
+    
+    // Files dropped onto an extra window open there; folders still become
+    // the project folder of the main window
+    fn open_paths_in_window(&mut self, ctx: &egui::Context, window: usize, paths: &[PathBuf]) {
+        for path in paths {
+            if path.is_dir() {
+                self.open_folder(ctx, path);
+                continue;
+            }
+            let window = &mut self.windows[window];
+            if let Some(idx) = window.documents.position_by_path(path) {
+                window.active_document_index = Some(idx);
+                continue;
+            }
+            match Document::from_file(path) {
+                Ok(mut doc) => {
+                    doc.apply_config(&self.config);
+                    window.add(doc);
+                }
+                Err(err) => log::error!("Failed to open {}: {}", path.display(), err),
+            }
+        }
+    }
+    
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let (hovered, dropped) = ctx.input(|i| {
+            let dropped: Vec<PathBuf> = i.raw.dropped_files.iter().filter_map(|file| file.path.clone()).collect();
+            (i.raw.hovered_files.len(), dropped)
+        });
+        
+        if hovered > 0 {
+            show_drop_overlay(ctx, hovered);
+        }
+        if !dropped.is_empty() {
+            self.open_paths(ctx, &dropped);
+        }
+    }
+    
+    fn handle_forwarded_files(&mut self, ctx: &egui::Context) {
+        let Some(rx) = &self.instance_rx else {
+            return;
+        };
+        let batches: Vec<Vec<PathBuf>> = rx.try_iter().collect();
+        if batches.is_empty() {
+            return;
+        }
+        
+        let paths: Vec<PathBuf> = batches.into_iter().flatten().collect();
+        self.open_paths(ctx, &paths);
+        
+        // 把窗口带到前台
+        ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+        ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+    }
+    
+    pub fn execute_command(&mut self, ctx: &egui::Context, cmd: Command) {
+        if !self.command_enabled(cmd) {
+            return;
+        }
+        if let Some(doc) = self.active_document_index.and_then(|idx| self.documents.get_mut(idx)) {
+            if editor::execute(ctx, doc, cmd) {
+                return;
+            }
+        }
+        
+        match cmd {
+            Command::NewFile => self.new_document(),
+            Command::OpenFile => {
+                if let Err(err) = self.open_document() {
+                    log::error!("Failed to open document: {}", err);
+                }
+            }
+            Command::OpenFolder => self.open_folder_dialog(ctx),
+            Command::QuickOpen => match self.project_root() {
+                Some(root) => self.quick_open.open(root, ctx),
+                None => self.set_status_message("No folder to search in"),
+            },
+            Command::Save => {
+                if let Err(err) = self.save_document() {
+                    log::error!("Failed to save document: {}", err);
+                }
+            }
+            Command::SaveAs => {
+                if let Err(err) = self.save_document_as() {
+                    log::error!("Failed to save document: {}", err);
+                }
+            }
+            Command::CloseTab => self.close_document(),
+            Command::ReopenClosedTab => self.reopen_closed_tab(),
+            Command::NewWindow => {
+                let window = self.new_window(None, None);
+                window.add(Document::new());
+            }
+            Command::CloseOtherTabs => {
+                if let Some(idx) = self.active_document_index {
+                    self.close_other_tabs(idx);
+                }
+            }
+            Command::CloseTabsToRight => {
+                if let Some(idx) = self.active_document_index {
+                    self.close_tabs_to_right(idx);
+                }
+            }
+            Command::Exit => {
+                // TODO: Check for unsaved changes
+                // 通过关闭主窗口退出，这样会话能在 on_exit 中保存
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+            Command::PasteFromHistory => self.clipboard_picker.open(),
+            Command::ColumnEditor => self.column_editor.open(),
+            Command::BookmarkMatchingLines => {
+                if self.find_text.is_empty() {
+                    self.show_find_dialog = true;
+                } else {
+                    self.bookmark_matching_lines();
+                }
+            }
+            Command::Find => self.show_find_dialog = true,
+            Command::Replace => self.show_replace_dialog = true,
+            Command::NextTab => self.cycle_tab(true),
+            Command::PreviousTab => self.cycle_tab(false),
+            Command::NextRecentTab | Command::PreviousRecentTab => {
+                let forward = cmd == Command::NextRecentTab;
+                if let Some(idx) = self.tab_history.cycle(forward, self.documents.len()) {
+                    self.active_document_index = Some(idx);
+                }
+            }
+            Command::TogglePinTab => {
+                if let Some(idx) = self.active_document_index {
+                    self.toggle_pin(idx);
+                }
+            }
+            Command::SplitRight => self.panes.split(SplitDirection::Horizontal, self.active_document_index, &self.documents),
+            Command::SplitDown => self.panes.split(SplitDirection::Vertical, self.active_document_index, &self.documents),
+            Command::ClosePane => self.panes.close_active(&mut self.active_document_index, &mut self.documents),
+            Command::FocusNextPane => self.panes.focus_next(&mut self.active_document_index, &mut self.documents),
+            Command::ToggleWordWrap => {
+                self.config.word_wrap = !self.config.word_wrap;
+                self.save_config();
+                self.apply_settings_to_documents();
+            }
+            Command::ToggleLineNumbers => {
+                self.config.line_numbers = !self.config.line_numbers;
+                self.save_config();
+                self.apply_settings_to_documents();
+            }
+            Command::ToggleSyntaxHighlighting => {
+                self.config.syntax_highlighting = !self.config.syntax_highlighting;
+                self.save_config();
+                // TODO: Apply syntax highlighting setting
+            }
+            Command::ToggleFileTree => self.show_file_tree = !self.show_file_tree,
+            Command::ToggleDocumentMap => self.show_document_map = !self.show_document_map,
+            Command::ToggleFunctionList => self.show_function_list = !self.show_function_list,
+            Command::Settings => self.show_settings = true,
+            Command::OpenCommandPalette => self.command_palette.open(),
+            Command::ThemeLight | Command::ThemeDark | Command::ThemeBlue
+            | Command::ThemeGreen | Command::ThemeSolarized => {
+                if let Some(name) = cmd.theme_name() {
+                    self.set_theme(ctx, name);
+                }
+            }
+            Command::About => self.show_about = true,
+            // 其余命令只作用于当前文档，上面的 editor::execute 已经处理
+            _ => {}
+        }
+        
+        ctx.request_repaint();
+    }
+    
+    // The opened folder, or else the repository (or folder) of the active document
+    pub fn project_root(&self) -> Option<PathBuf> {
+        if let Some(root) = &self.project_root {
+            return Some(root.clone());
+        }
+        
+        let doc_dir = self.active_document_index
+            .and_then(|idx| self.documents.get(idx))
+            .and_then(|doc| doc.path.as_ref())
+            .and_then(|path| path.parent())?;
+        let repo_root = doc_dir.ancestors().find(|dir| dir.join(".git").exists());
+        Some(repo_root.unwrap_or(doc_dir).to_path_buf())
+    }
+    
+    fn command_enabled(&self, cmd: Command) -> bool {
+        match cmd {
+            Command::Save | Command::SaveAs | Command::CloseTab
+            | Command::CloseOtherTabs | Command::CloseTabsToRight
+            | Command::TogglePinTab | Command::Cut | Command::Copy
+            | Command::Paste | Command::AddNextOccurrence | Command::SelectAllOccurrences
+            | Command::AddCursorAbove | Command::AddCursorBelow
+            | Command::ColumnEditor | Command::GotoMatchingBracket | Command::ToggleLineComment
+            | Command::ToggleBlockComment | Command::Fold | Command::Unfold
+            | Command::DuplicateLine | Command::MoveLineUp | Command::MoveLineDown
+            | Command::DeleteLine | Command::JoinLines | Command::SortLinesAscending
+            | Command::SortLinesDescending | Command::SortLinesIgnoreCaseAscending
+            | Command::SortLinesIgnoreCaseDescending | Command::SortLinesNumericAscending
+            | Command::SortLinesNumericDescending | Command::SortLinesNaturalAscending
+            | Command::SortLinesNaturalDescending | Command::RemoveDuplicateLines
+            | Command::RemoveEmptyLines | Command::ReverseLines
+            | Command::UpperCase | Command::LowerCase | Command::TitleCase | Command::SentenceCase
+            | Command::InvertCase | Command::SnakeCase | Command::CamelCase | Command::KebabCase
+            | Command::TrimTrailingWhitespace | Command::TrimLeadingWhitespace
+            | Command::TabsToSpaces | Command::SpacesToTabs | Command::HardWrap
+            | Command::ReflowParagraphs
+            | Command::FoldAll | Command::UnfoldAll | Command::FoldLevel1
+            | Command::FoldLevel2 | Command::FoldLevel3 | Command::FoldLevel4
+            | Command::FoldLevel5 | Command::ToggleBookmark
+            | Command::BookmarkMatchingLines => self.active_document_index.is_some(),
+            Command::NextBookmark | Command::PreviousBookmark | Command::ClearBookmarks
+            | Command::CopyBookmarkedLines | Command::CutBookmarkedLines
+            | Command::DeleteBookmarkedLines => self.active_document_index
+                .and_then(|idx| self.documents.get(idx))
+                .is_some_and(|doc| doc.has_bookmarks()),
+            Command::PasteFromHistory => self.active_document_index.is_some() && self.clipboard_history.len() > 0,
+            Command::ReopenClosedTab => self.documents.has_closed(),
+            Command::NextTab | Command::PreviousTab
+            | Command::NextRecentTab | Command::PreviousRecentTab => self.documents.len() > 1,
+            Command::ClosePane | Command::FocusNextPane => self.panes.len() > 1,
+            Command::ToggleFileTree => self.file_tree.is_some(),
+            _ => true,
+        }
+    }
+    
+    // None for commands that are not toggles
+    fn command_checked(&self, cmd: Command) -> Option<bool> {
+        match cmd {
+            Command::ToggleWordWrap => Some(self.config.word_wrap),
+            Command::ToggleLineNumbers => Some(self.config.line_numbers),
+            Command::ToggleSyntaxHighlighting => Some(self.config.syntax_highlighting),
+            Command::ToggleFileTree => Some(self.show_file_tree),
+            Command::ToggleDocumentMap => Some(self.show_document_map),
+            Command::ToggleFunctionList => Some(self.show_function_list),
+            Command::TogglePinTab => Some(
+                self.active_document_index
+                    .and_then(|idx| self.documents.get(idx))
+                    .map(|doc| doc.pinned)
+                    .unwrap_or(false),
+            ),
+            _ => cmd.theme_name().map(|name| name == self.theme.name),
+        }
+    }
+    
+    fn cycle_tab(&mut self, forward: bool) {
+        let count = self.documents.len();
+        if count == 0 {
+            return;
+        }
+        let current = self.active_document_index.unwrap_or(0);
+        let next = if forward {
+            (current + 1) % count
+        } else {
+            (current + count - 1) % count
+        };
+        self.active_document_index = Some(next);
+    }
+    
+    fn set_theme(&mut self, ctx: &egui::Context, name: &str) {
+        self.theme = Theme::new(name);
+        self.theme.apply_to_ctx(ctx);
+        self.config.theme_name = self.theme.name.clone();
+        self.save_config();
+    }
+    
+    fn save_config(&self) {
+        if let Err(err) = self.config.save() {
+            log::error!("Failed to save config: {}", err);
+        }
+    }
+    
     pub fn apply_settings_to_documents(&mut self) {
         for i in 0..self.documents.len() {
             if let Some(doc) = self.documents.get_mut(i) {
-                doc.line_numbers = self.config.line_numbers;
-                doc.word_wrap = self.config.word_wrap;
+                doc.apply_config(&self.config);
+            }
+        }
+        for window in &mut self.windows {
+            for i in 0..window.documents.len() {
+                if let Some(doc) = window.documents.get_mut(i) {
+                    doc.apply_config(&self.config);
+                }
+            }
+        }
+    }
+}
+
+impl NotionApp {
+    fn new_window(&mut self, position: Option<egui::Pos2>, size: Option<egui::Vec2>) -> &mut EditorWindow {
+        self.next_window_serial += 1;
+        self.windows.push(EditorWindow::new(self.next_window_serial, position, size));
+        self.windows.last_mut().unwrap()
+    }
+    
+    // Put a document that left a window into whichever window is under
+    // `screen_pos`, or into a new window there
+    fn place_document(&mut self, mut doc: Document, screen_pos: Option<egui::Pos2>, source: Option<usize>) {
+        doc.apply_config(&self.config);
+        
+        if let Some(pos) = screen_pos {
+            if self.screen_rect.map(|rect| rect.contains(pos)).unwrap_or(false) {
+                self.documents.add(doc);
+                self.active_document_index = Some(self.documents.len() - 1);
+                return;
+            }
+            let target = self.windows.iter_mut().enumerate().find(|(i, window)| {
+                Some(*i) != source && window.screen_rect.map(|rect| rect.contains(pos)).unwrap_or(false)
+            });
+            if let Some((_, window)) = target {
+                window.add(doc);
+                return;
+            }
+        }
+        
+        // 新窗口的标题栏放在鼠标位置附近
+        let position = screen_pos.map(|pos| pos - egui::vec2(100.0, 20.0));
+        self.new_window(position, None).add(doc);
+    }
+    
+    // Remember where the main window is, and pull it back if the saved
+    // position put its title bar off screen (e.g. on a monitor that is gone)
+    fn track_window_geometry(&mut self, ctx: &egui::Context) {
+        let info = ctx.input(|i| i.viewport().clone());
+        let (Some(outer), Some(inner)) = (info.outer_rect, info.inner_rect) else {
+            return;
+        };
+        
+        if !self.window_checked {
+            if let Some(monitor) = info.monitor_size {
+                self.window_checked = true;
+                if let Some(pos) = windows::position_on_screen(outer, monitor) {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(pos));
+                }
+            }
+        }
+        
+        let layout = &mut self.config.window;
+        layout.maximized = info.maximized.unwrap_or(false);
+        // 最大化时保留之前的普通尺寸
+        if !layout.maximized && info.minimized != Some(true) {
+            layout.position = Some([outer.min.x, outer.min.y]);
+            layout.size = [inner.width(), inner.height()];
+        }
+    }
+    
+    fn show_editor_windows(&mut self, ctx: &egui::Context) {
+        let mut events = Vec::new();
+        for (i, window) in self.windows.iter_mut().enumerate() {
+            events.extend(window.show(ctx, &mut self.keymap).into_iter().map(|event| (i, event)));
+        }
+        
+        let mut closed = Vec::new();
+        for (i, event) in events {
+            match event {
+                WindowEvent::TabDraggedOut { index, screen_pos } => {
+                    if let Some(doc) = self.windows[i].take(index) {
+                        self.place_document(doc, Some(screen_pos), Some(i));
+                    }
+                }
+                WindowEvent::MoveToNewWindow(index) => {
+                    if let Some(doc) = self.windows[i].take(index) {
+                        self.place_document(doc, None, Some(i));
+                    }
+                }
+                WindowEvent::FilesDropped(paths) => self.open_paths_in_window(ctx, i, &paths),
+                WindowEvent::Closed => closed.push(i),
+            }
+        }
+        
+        // 关闭的窗口中的文档移回主窗口，避免丢失未保存的内容
+        for i in closed.into_iter().rev() {
+            let mut window = self.windows.remove(i);
+            while let Some(doc) = window.take(0) {
+                self.documents.add(doc);
+            }
+        }
+    }
+    
+    fn session(&self) -> Session {
+        let mut windows = vec![WindowSession::from_documents(&self.documents, self.active_document_index)];
+        for window in &self.windows {
+            let mut session = WindowSession::from_documents(&window.documents, window.active_document_index);
+            session.position = window.position.map(|pos| [pos.x, pos.y]);
+            session.size = window.screen_rect.map(|rect| [rect.width(), rect.height()]);
+            windows.push(session);
+        }
+        Session { windows }
+    }
+    
+    // Reopen the documents and extra windows of the last session
+    pub fn restore_session(&mut self) {
+        let session = match Session::load() {
+            Ok(session) => session,
+            Err(err) => {
+                log::error!("Failed to load session: {}", err);
+                return;
+            }
+        };
+        
+        for (i, saved) in session.windows.into_iter().enumerate() {
+            let mut documents = DocumentCollection::new();
+            let mut active = None;
+            for (j, path) in saved.files.iter().enumerate() {
+                match Document::from_file(path) {
+                    Ok(mut doc) => {
+                        doc.apply_config(&self.config);
+                        doc.pinned = saved.pinned.contains(path);
+                        if let Some(lines) = saved.folds.get(path) {
+                            doc.set_folded_lines(lines);
+                        }
+                        if let Some(lines) = saved.bookmarks.get(path) {
+                            doc.set_bookmarked_lines(lines);
+                        }
+                        documents.add(doc);
+                        if saved.active == Some(j) {
+                            active = Some(documents.len() - 1);
+                        }
+                    }
+                    Err(err) => log::warn!("Skipping {} from the last session: {}", path.display(), err),
+                }
+            }
+            let active = active.or((documents.len() > 0).then_some(0));
+            
+            if i == 0 {
+                self.documents = documents;
+                self.active_document_index = active;
+            } else if documents.len() > 0 {
+                let position = saved.position.map(|[x, y]| egui::pos2(x, y));
+                let size = saved.size.map(|[w, h]| egui::vec2(w, h));
+                let window = self.new_window(position, size);
+                window.documents = documents;
+                window.active_document_index = active;
             }
         }
     }
@@ -205,8 +826,31 @@ impl NotionApp {
 
 impl eframe::App for NotionApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.handle_forwarded_files(ctx);
+        self.handle_dropped_files(ctx);
+        #[cfg(unix)]
+        if let Some(rx) = self.rpc_rx.take() {
+            self.handle_rpc_calls(&rx);
+            self.rpc_rx = Some(rx);
+        }
+        
+        for cmd in self.keymap.process_input(ctx) {
+            self.execute_command(ctx, cmd);
+        }
+        self.tab_history.update(ctx, self.active_document_index);
+        
         self.show_menu_bar(ctx);
         self.show_tabs_bar(ctx);
+        if self.show_file_tree {
+            self.show_file_tree_panel(ctx);
+        }
+        // 侧边栏必须在中央面板之前添加
+        if self.show_function_list {
+            self.show_function_list_panel(ctx);
+        }
+        if self.show_document_map {
+            self.show_document_map_panel(ctx);
+        }
         self.show_document_area(ctx);
         self.show_status_bar(ctx);
         
@@ -226,268 +870,512 @@ impl eframe::App for NotionApp {
             self.show_replace_window(ctx);
         }
         
-        if self.show_document_map {
-            self.show_document_map_panel(ctx);
+        if self.command_palette.is_open() {
+            self.show_command_palette(ctx);
         }
         
-        if self.show_function_list {
-            self.show_function_list_panel(ctx);
+        if self.column_editor.is_open() {
+            if let Some(insert) = self.column_editor.show(ctx) {
+                if let Some(doc) = self.active_document_index.and_then(|idx| self.documents.get_mut(idx)) {
+                    doc.insert_column(|count| insert.pieces(count));
+                    doc.request_focus();
+                }
+            }
+        }
+        
+        if self.clipboard_picker.is_open() {
+            self.show_clipboard_picker(ctx);
+        }
+        
+        if self.quick_open.is_open() {
+            if let Some(path) = self.quick_open.show(ctx) {
+                if let Err(err) = self.open_path(&path) {
+                    log::error!("Failed to open {}: {}", path.display(), err);
+                }
+            }
+        }
+        
+        // 记录本帧在编辑器里复制或剪切的内容
+        for text in clipboard::take_copies(ctx) {
+            self.clipboard_history.push(text);
+        }
+        
+        self.screen_rect = ctx.input(|i| i.viewport().inner_rect);
+        self.track_window_geometry(ctx);
+        self.show_editor_windows(ctx);
+    }
+    
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.config.window.show_file_tree = self.show_file_tree;
+        self.config.window.show_function_list = self.show_function_list;
+        self.config.window.show_document_map = self.show_document_map;
+        self.save_config();
+        
+        if let Err(err) = self.session().save() {
+            log::error!("Failed to save session: {}", err);
         }
     }
 }
 
 impl NotionApp {
     fn show_menu_bar(&mut self, ctx: &egui::Context) {
+        let mut clicked = None;
+        
         egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
-                    if ui.button("New").clicked() {
-                        self.new_document();
-                        ui.close_menu();
-                    }
-                    
-                    if ui.button("Open").clicked() {
-                        if let Err(err) = self.open_document() {
-                            log::error!("Failed to open document: {}", err);
-                        }
-                        ui.close_menu();
-                    }
+                    self.command_button(ui, Command::NewFile, &mut clicked);
+                    self.command_button(ui, Command::NewWindow, &mut clicked);
+                    self.command_button(ui, Command::OpenFile, &mut clicked);
+                    self.command_button(ui, Command::OpenFolder, &mut clicked);
+                    self.command_button(ui, Command::QuickOpen, &mut clicked);
                     
                     ui.separator();
                     
-                    let can_save = self.active_document_index.is_some();
-                    if ui.add_enabled(can_save, egui::Button::new("Save")).clicked() {
-                        if let Err(err) = self.save_document() {
-                            log::error!("Failed to save document: {}", err);
-                        }
-                        ui.close_menu();
-                    }
-                    
-                    if ui.add_enabled(can_save, egui::Button::new("Save As...")).clicked() {
-                        if let Err(err) = self.save_document_as() {
-                            log::error!("Failed to save document: {}", err);
-                        }
-                        ui.close_menu();
-                    }
+                    self.command_button(ui, Command::Save, &mut clicked);
+                    self.command_button(ui, Command::SaveAs, &mut clicked);
                     
                     ui.separator();
                     
-                    if ui.add_enabled(can_save, egui::Button::new("Close")).clicked() {
-                        self.close_document();
-                        ui.close_menu();
-                    }
+                    self.command_button(ui, Command::CloseTab, &mut clicked);
+                    self.command_button(ui, Command::CloseOtherTabs, &mut clicked);
+                    self.command_button(ui, Command::CloseTabsToRight, &mut clicked);
+                    self.command_button(ui, Command::ReopenClosedTab, &mut clicked);
                     
                     ui.separator();
                     
-                    if ui.button("Exit").clicked() {
-                        // TODO: Check for unsaved changes
-                        std::process::exit(0);
-                    }
+                    self.command_button(ui, Command::Exit, &mut clicked);
                 });
                 
                 ui.menu_button("Edit", |ui| {
-                    // TODO: Implement edit menu (copy, paste, undo, redo)
-                    if ui.button("Undo").clicked() {
-                        // TODO
-                        ui.close_menu();
-                    }
+                    self.command_button(ui, Command::Undo, &mut clicked);
+                    self.command_button(ui, Command::Redo, &mut clicked);
                     
-                    if ui.button("Redo").clicked() {
-                        // TODO
-                        ui.close_menu();
-                    }
+                    ui.separator();
+                    
+                    self.command_button(ui, Command::Cut, &mut clicked);
+                    self.command_button(ui, Command::Copy, &mut clicked);
+                    self.command_button(ui, Command::Paste, &mut clicked);
+                    self.command_button(ui, Command::PasteFromHistory, &mut clicked);
                     
                     ui.separator();
                     
-                    if ui.button("Cut").clicked() {
-                        // TODO
-                        ui.close_menu();
-                    }
+                    self.command_button(ui, Command::AddNextOccurrence, &mut clicked);
+                    self.command_button(ui, Command::SelectAllOccurrences, &mut clicked);
+                    self.command_button(ui, Command::AddCursorAbove, &mut clicked);
+                    self.command_button(ui, Command::AddCursorBelow, &mut clicked);
+                    self.command_button(ui, Command::ColumnEditor, &mut clicked);
+                    self.command_button(ui, Command::GotoMatchingBracket, &mut clicked);
+                    self.command_button(ui, Command::ToggleLineComment, &mut clicked);
+                    self.command_button(ui, Command::ToggleBlockComment, &mut clicked);
                     
-                    if ui.button("Copy").clicked() {
-                        // TODO
-                        ui.close_menu();
-                    }
+                    ui.menu_button("Line Operations", |ui| {
+                        self.command_button(ui, Command::DuplicateLine, &mut clicked);
+                        self.command_button(ui, Command::MoveLineUp, &mut clicked);
+                        self.command_button(ui, Command::MoveLineDown, &mut clicked);
+                        self.command_button(ui, Command::DeleteLine, &mut clicked);
+                        self.command_button(ui, Command::JoinLines, &mut clicked);
+                        
+                        ui.separator();
+                        
+                        self.command_button(ui, Command::SortLinesAscending, &mut clicked);
+                        self.command_button(ui, Command::SortLinesDescending, &mut clicked);
+                        self.command_button(ui, Command::SortLinesIgnoreCaseAscending, &mut clicked);
+                        self.command_button(ui, Command::SortLinesIgnoreCaseDescending, &mut clicked);
+                        self.command_button(ui, Command::SortLinesNumericAscending, &mut clicked);
+                        self.command_button(ui, Command::SortLinesNumericDescending, &mut clicked);
+                        self.command_button(ui, Command::SortLinesNaturalAscending, &mut clicked);
+                        self.command_button(ui, Command::SortLinesNaturalDescending, &mut clicked);
+                        
+                        ui.separator();
+                        
+                        self.command_button(ui, Command::RemoveDuplicateLines, &mut clicked);
+                        self.command_button(ui, Command::RemoveEmptyLines, &mut clicked);
+                        self.command_button(ui, Command::ReverseLines, &mut clicked);
+                    });
                     
-                    if ui.button("Paste").clicked() {
-                        // TODO
-                        ui.close_menu();
-                    }
+                    ui.menu_button("Convert", |ui| {
+                        self.command_button(ui, Command::UpperCase, &mut clicked);
+                        self.command_button(ui, Command::LowerCase, &mut clicked);
+                        self.command_button(ui, Command::TitleCase, &mut clicked);
+                        self.command_button(ui, Command::SentenceCase, &mut clicked);
+                        self.command_button(ui, Command::InvertCase, &mut clicked);
+                        
+                        ui.separator();
+                        
+                        self.command_button(ui, Command::SnakeCase, &mut clicked);
+                        self.command_button(ui, Command::CamelCase, &mut clicked);
+                        self.command_button(ui, Command::KebabCase, &mut clicked);
+                        
+                        ui.separator();
+                        
+                        self.command_button(ui, Command::TrimTrailingWhitespace, &mut clicked);
+                        self.command_button(ui, Command::TrimLeadingWhitespace, &mut clicked);
+                        self.command_button(ui, Command::TabsToSpaces, &mut clicked);
+                        self.command_button(ui, Command::SpacesToTabs, &mut clicked);
+                        
+                        ui.separator();
+                        
+                        self.command_button(ui, Command::HardWrap, &mut clicked);
+                        self.command_button(ui, Command::ReflowParagraphs, &mut clicked);
+                    });
                     
-                    ui.separator();
+                    ui.menu_button("Bookmarks", |ui| {
+                        self.command_button(ui, Command::ToggleBookmark, &mut clicked);
+                        self.command_button(ui, Command::NextBookmark, &mut clicked);
+                        self.command_button(ui, Command::PreviousBookmark, &mut clicked);
+                        self.command_button(ui, Command::ClearBookmarks, &mut clicked);
+                        
+                        ui.separator();
+                        
+                        self.command_button(ui, Command::BookmarkMatchingLines, &mut clicked);
+                        self.command_button(ui, Command::CopyBookmarkedLines, &mut clicked);
+                        self.command_button(ui, Command::CutBookmarkedLines, &mut clicked);
+                        self.command_button(ui, Command::DeleteBookmarkedLines, &mut clicked);
+                    });
                     
-                    if ui.button("Find...").clicked() {
-                        self.show_find_dialog = true;
-                        ui.close_menu();
-                    }
+                    ui.separator();
                     
-                    if ui.button("Replace...").clicked() {
-                        self.show_replace_dialog = true;
-                        ui.close_menu();
-                    }
+                    self.command_button(ui, Command::Find, &mut clicked);
+                    self.command_button(ui, Command::Replace, &mut clicked);
                 });
                 
                 ui.menu_button("View", |ui| {
-                    if ui.checkbox(&mut self.config.word_wrap, "Word Wrap").clicked() {
-                        if let Err(err) = self.config.save() {
-                            log::error!("Failed to save config: {}", err);
-                        }
-                        self.apply_settings_to_documents();
-                    }
+                    self.command_button(ui, Command::ToggleWordWrap, &mut clicked);
+                    self.command_button(ui, Command::ToggleLineNumbers, &mut clicked);
+                    self.command_button(ui, Command::ToggleSyntaxHighlighting, &mut clicked);
                     
-                    if ui.checkbox(&mut self.config.line_numbers, "Line Numbers").clicked() {
-                        if let Err(err) = self.config.save() {
-                            log::error!("Failed to save config: {}", err);
+                    ui.menu_button("Folding", |ui| {
+                        self.command_button(ui, Command::Fold, &mut clicked);
+                        self.command_button(ui, Command::Unfold, &mut clicked);
+                        self.command_button(ui, Command::FoldAll, &mut clicked);
+                        self.command_button(ui, Command::UnfoldAll, &mut clicked);
+                        
+                        ui.separator();
+                        
+                        for cmd in [Command::FoldLevel1, Command::FoldLevel2, Command::FoldLevel3, Command::FoldLevel4, Command::FoldLevel5] {
+                            self.command_button(ui, cmd, &mut clicked);
                         }
-                        self.apply_settings_to_documents();
-                    }
+                    });
+                    
+                    ui.separator();
                     
-                    if ui.checkbox(&mut self.config.syntax_highlighting, "Syntax Highlighting").clicked() {
-                        if let Err(err) = self.config.save() {
-                            log::error!("Failed to save config: {}", err);
+                    self.command_button(ui, Command::ToggleFileTree, &mut clicked);
+                    self.command_button(ui, Command::ToggleDocumentMap, &mut clicked);
+                    self.command_button(ui, Command::ToggleFunctionList, &mut clicked);
+                    
+                    ui.menu_button("Theme", |ui| {
+                        for &cmd in Command::ALL.iter().filter(|cmd| cmd.theme_name().is_some()) {
+                            self.command_button(ui, cmd, &mut clicked);
                         }
-                        // TODO: Apply syntax highlighting setting
-                    }
+                    });
                     
                     ui.separator();
                     
-                    if ui.checkbox(&mut self.show_document_map, "Document Map").clicked() {
-                        // 切换文档映射侧边栏
-                    }
+                    self.command_button(ui, Command::OpenCommandPalette, &mut clicked);
                     
-                    if ui.checkbox(&mut self.show_function_list, "Function List").clicked() {
-                        // 切换函数列表侧边栏
-                    }
+                    ui.separator();
+                    
+                    self.command_button(ui, Command::NextTab, &mut clicked);
+                    self.command_button(ui, Command::PreviousTab, &mut clicked);
+                    self.command_button(ui, Command::TogglePinTab, &mut clicked);
                     
                     ui.separator();
                     
-                    if ui.button("Settings").clicked() {
-                        self.show_settings = true;
-                        ui.close_menu();
-                    }
+                    self.command_button(ui, Command::SplitRight, &mut clicked);
+                    self.command_button(ui, Command::SplitDown, &mut clicked);
+                    self.command_button(ui, Command::ClosePane, &mut clicked);
+                    self.command_button(ui, Command::FocusNextPane, &mut clicked);
+                    
+                    ui.separator();
+                    
+                    self.command_button(ui, Command::Settings, &mut clicked);
                 });
                 
                 ui.menu_button("Help", |ui| {
-                    if ui.button("About").clicked() {
-                        self.show_about = true;
-                        ui.close_menu();
-                    }
+                    self.command_button(ui, Command::About, &mut clicked);
                 });
             });
         });
+        
+        if let Some(cmd) = clicked {
+            self.execute_command(ctx, cmd);
+        }
+    }
+    
+    // 菜单项：显示快捷键，切换类命令显示为选中状态
+    fn command_button(&self, ui: &mut egui::Ui, cmd: Command, clicked: &mut Option<Command>) {
+        let mut button = egui::Button::new(cmd.title());
+        if let Some(shortcut) = self.keymap.binding_text(cmd) {
+            button = button.shortcut_text(shortcut);
+        }
+        if let Some(checked) = self.command_checked(cmd) {
+            button = button.selected(checked);
+        }
+        
+        if ui.add_enabled(self.command_enabled(cmd), button).clicked() {
+            *clicked = Some(cmd);
+            ui.close_menu();
+        }
     }
     
     fn show_tabs_bar(&mut self, ctx: &egui::Context) {
-        if self.documents.len() > 0 {
-            egui::TopBottomPanel::top("tabs_bar")
-                .show_separator_line(true)
-                .show(ctx, |ui| {
-                    ui.horizontal_wrapped(|ui| {
-                        let mut clicked_idx = None;
-                        let mut close_idx = None;
-                        
-                        for i in 0..self.documents.len() {
-                            let doc = self.documents.get(i).unwrap();
-                            let is_active = Some(i) == self.active_document_index;
-                            
-                            ui.horizontal(|ui| {
-                                if UiComponents::file_tab(
-                                    ui,
-                                    &doc.filename,
-                                    is_active,
-                                    doc.is_modified
-                                ) {
-                                    clicked_idx = Some(i);
-                                }
-                                
-                                if ui.small_button("×").clicked() {
-                                    close_idx = Some(i);
-                                }
-                            });
-                            
-                            ui.separator();
+        if self.documents.len() == 0 {
+            return;
+        }
+        
+        let actions = egui::TopBottomPanel::top("tabs_bar")
+            .show_separator_line(true)
+            .show(ctx, |ui| self.tab_bar.show(ui, &self.documents, self.active_document_index))
+            .inner;
+        
+        for action in actions {
+            match action {
+                TabAction::Activate(idx) => self.active_document_index = Some(idx),
+                TabAction::Close(idx) => {
+                    self.close_document_at(idx);
+                }
+                TabAction::CloseOthers(idx) => self.close_other_tabs(idx),
+                TabAction::CloseToRight(idx) => self.close_tabs_to_right(idx),
+                TabAction::TogglePin(idx) => self.toggle_pin(idx),
+                TabAction::Move { from, to } => self.move_tab(from, to),
+                TabAction::DragOut { index, pos } => {
+                    let screen_pos = self.screen_rect.map(|rect| rect.min + pos.to_vec2());
+                    let outside = screen_pos
+                        .zip(self.screen_rect)
+                        .map(|(pos, rect)| !rect.contains(pos))
+                        .unwrap_or(false);
+                    if outside {
+                        if let Some(doc) = self.take_document(index) {
+                            self.place_document(doc, screen_pos, None);
                         }
-                        
-                        if let Some(idx) = clicked_idx {
-                            self.active_document_index = Some(idx);
+                    }
+                }
+                TabAction::MoveToNewWindow(idx) => {
+                    if let Some(doc) = self.take_document(idx) {
+                        self.place_document(doc, None, None);
+                    }
+                }
+            }
+        }
+    }
+    
+    pub fn reopen_closed_tab(&mut self) {
+        match self.documents.reopen_closed() {
+            Ok(Some(idx)) => {
+                self.panes.document_inserted(idx);
+                self.tab_history.document_inserted(idx);
+                
+                if let Some(doc) = self.documents.get_mut(idx) {
+                    // 使用配置中的设置
+                    doc.apply_config(&self.config);
+                    let message = format!("Reopened {}", doc.filename);
+                    self.set_status_message(message);
+                }
+                self.active_document_index = Some(idx);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                log::error!("Failed to reopen document: {}", err);
+                self.set_status_message(format!("Error reopening document: {}", err));
+            }
+        }
+    }
+    
+    // 固定的标签不会被批量关闭
+    fn close_other_tabs(&mut self, keep: usize) {
+        for idx in self.documents.close_other_tabs(keep) {
+            self.document_removed(idx);
+        }
+    }
+    
+    fn close_tabs_to_right(&mut self, idx: usize) {
+        for i in self.documents.close_tabs_to_right(idx) {
+            self.document_removed(i);
+        }
+    }
+    
+    fn toggle_pin(&mut self, idx: usize) {
+        if let Some(to) = self.documents.toggle_pin(idx) {
+            self.move_tab(idx, to);
+        }
+    }
+    
+    fn move_tab(&mut self, from: usize, to: usize) {
+        if !self.documents.move_tab(from, to) {
+            return;
+        }
+        self.active_document_index = self.active_document_index.map(|idx| panes::index_after_move(idx, from, to));
+        self.panes.document_moved(from, to);
+        self.tab_history.document_moved(from, to);
+    }
+    
+    fn show_file_tree_panel(&mut self, ctx: &egui::Context) {
+        let Some(tree) = &mut self.file_tree else {
+            return;
+        };
+        
+        let panel = egui::SidePanel::left("file_tree")
+            .resizable(true)
+            .default_width(self.config.window.file_tree_width)
+            .width_range(150.0..=500.0)
+            .show(ctx, |ui| tree.show(ui));
+        self.config.window.file_tree_width = panel.response.rect.width();
+        let actions = panel.inner;
+        
+        for action in actions {
+            match action {
+                TreeAction::Open(path) => {
+                    if let Err(err) = self.open_path(&path) {
+                        log::error!("Failed to open {}: {}", path.display(), err);
+                    }
+                }
+                TreeAction::Renamed { from, to } => {
+                    // 已打开的文件（或其所在目录）被重命名时同步更新标签
+                    for i in 0..self.documents.len() {
+                        if let Some(doc) = self.documents.get_mut(i) {
+                            let new_path = doc.path.as_ref()
+                                .and_then(|path| path.strip_prefix(&from).ok())
+                                .map(|rest| to.join(rest));
+                            if let Some(new_path) = new_path {
+                                doc.set_path(&new_path);
+                            }
                         }
-                        
-                        if let Some(idx) = close_idx {
-                            if self.documents.close(idx) {
-                                if self.documents.len() == 0 {
-                                    self.active_document_index = None;
-                                } else {
-                                    self.active_document_index = Some(idx.min(self.documents.len() - 1));
-                                }
+                    }
+                    self.set_status_message(format!("Renamed to {}", to.display()));
+                }
+                TreeAction::Deleted(path) => {
+                    // 文件已不在磁盘上，保留内容但标记为未保存
+                    for i in 0..self.documents.len() {
+                        if let Some(doc) = self.documents.get_mut(i) {
+                            if doc.path.as_ref().map(|p| p.starts_with(&path)).unwrap_or(false) {
+                                doc.mark_modified();
                             }
                         }
-                    });
-                });
+                    }
+                    self.set_status_message(format!("Moved {} to trash", path.display()));
+                }
+                TreeAction::Error(message) => {
+                    log::error!("{}", message);
+                    self.set_status_message(message);
+                }
+            }
         }
     }
     
     fn show_document_area(&mut self, ctx: &egui::Context) {
         let panel = egui::CentralPanel::default();
         
-        if self.show_document_map {
-            egui::SidePanel::right("document_map")
-                .resizable(true)
-                .default_width(200.0)
-                .width_range(150.0..=400.0)
-                .show(ctx, |ui| {
-                    ui.heading("Document Map");
+        panel.show(ctx, |ui| {
+            if self.panes.len() == 1 {
+                self.show_active_document(ui);
+                return;
+            }
+            
+            let mut activate = None;
+            let mut close_pane = false;
+            
+            for (i, rect) in self.panes.layout(ui.max_rect()).into_iter().enumerate() {
+                let is_active = i == self.panes.active();
+                let mut pane_ui = ui.child_ui(rect, egui::Layout::top_down(egui::Align::Min));
+                pane_ui.set_clip_rect(rect.intersect(ui.clip_rect()));
+                
+                pane_ui.push_id(("editor_pane", i), |ui| {
+                    let document = if is_active {
+                        self.active_document_index
+                    } else {
+                        self.panes.get_mut(i).and_then(|pane| pane.document)
+                    };
+                    let title = document
+                        .and_then(|idx| self.documents.get(idx))
+                        .map(|doc| doc.filename.clone())
+                        .unwrap_or_else(|| "Empty".to_string());
+                    
+                    ui.horizontal(|ui| {
+                        if is_active {
+                            ui.strong(title);
+                        } else {
+                            ui.weak(title);
+                        }
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.small_button("×").on_hover_text("Close Pane").clicked() {
+                                activate = Some(i);
+                                close_pane = true;
+                            }
+                        });
+                    });
                     ui.separator();
-                    // 这里实现文档映射功能
-                    if let Some(idx) = self.active_document_index {
-                        if let Some(doc) = self.documents.get(idx) {
-                            // 显示文档的简化缩略图
-                            let lines = doc.content.lines().take(100);
-                            for line in lines {
-                                let shortened = if line.len() > 30 {
-                                    format!("{}...", &line[0..30])
-                                } else {
-                                    line.to_string()
-                                };
-                                ui.label(egui::RichText::new(shortened).weak().small());
+                    
+                    if is_active {
+                        self.show_active_document(ui);
+                    } else if let Some(pane) = self.panes.get_mut(i) {
+                        // 同一文档在多个窗格中共享同一缓冲区
+                        match pane.document.and_then(|idx| self.documents.get_mut(idx)) {
+                            Some(doc) => doc.ui_with_view(ui, &mut pane.view),
+                            None => {
+                                ui.weak("No document");
                             }
                         }
                     }
                 });
-        }
-        
-        panel.show(ctx, |ui| {
-            if let Some(idx) = self.active_document_index {
-                if let Some(doc) = self.documents.get_mut(idx) {
-                    doc.ui(ui);
+                
+                if is_active {
+                    ui.painter().rect_stroke(rect, 2.0, egui::Stroke::new(1.0, ui.visuals().selection.bg_fill));
                 }
-            } else {
-                // Show welcome screen
-                ui.vertical_centered(|ui| {
-                    ui.add_space(100.0);
-                    ui.heading("Welcome to Notion++");
-                    ui.add_space(20.0);
-                    ui.label("A modern, cross-platform text editor written in Rust");
-                    ui.add_space(40.0);
-                    
-                    if ui.button("New Document").clicked() {
-                        self.new_document();
-                    }
-                    
-                    if ui.button("Open Document").clicked() {
-                        if let Err(err) = self.open_document() {
-                            log::error!("Failed to open document: {}", err);
-                        }
-                    }
+                
+                // 点击窗格任意位置即切换为活动窗格
+                let pressed = ui.input(|input| {
+                    input.pointer.any_pressed()
+                        && input.pointer.interact_pos().map(|pos| rect.contains(pos)).unwrap_or(false)
                 });
+                if pressed && activate.is_none() {
+                    activate = Some(i);
+                }
+            }
+            
+            if let Some(i) = activate {
+                self.panes.activate(i, &mut self.active_document_index, &mut self.documents);
+                if close_pane {
+                    self.panes.close_active(&mut self.active_document_index, &mut self.documents);
+                }
             }
         });
     }
     
+    fn show_active_document(&mut self, ui: &mut egui::Ui) {
+        if let Some(idx) = self.active_document_index {
+            if let Some(doc) = self.documents.get_mut(idx) {
+                doc.ui(ui);
+            }
+        } else {
+            // Show welcome screen
+            ui.vertical_centered(|ui| {
+                ui.add_space(100.0);
+                ui.heading("Welcome to Notion++");
+                ui.add_space(20.0);
+                ui.label("A modern, cross-platform text editor written in Rust");
+                ui.add_space(40.0);
+                
+                if ui.button("New Document").clicked() {
+                    self.new_document();
+                }
+                
+                if ui.button("Open Document").clicked() {
+                    if let Err(err) = self.open_document() {
+                        log::error!("Failed to open document: {}", err);
+                    }
+                }
+            });
+        }
+    }
+    
     fn show_status_bar(&mut self, ctx: &egui::Context) {
         egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
             ui.horizontal(|ui| {
                 // 左侧: 状态消息
-                if let Some((message, time)) = &self.status_message {
+                if let Some(pending) = self.keymap.pending_text() {
+                    ui.label(pending);
+                } else if let Some((message, time)) = &self.status_message {
                     let elapsed = time.elapsed().as_secs();
                     // 3秒后消息消失
                     if elapsed < 3 {
@@ -528,6 +1416,8 @@ impl NotionApp {
         let mut syntax_highlighting = self.config.syntax_highlighting;
         let mut auto_save = self.config.auto_save;
         let mut auto_save_interval_secs = self.config.auto_save_interval_secs;
+        let mut tab_width = self.config.tab_width;
+        let mut wrap_column = self.config.wrap_column;
         let mut theme_name = self.theme.name.clone();
         
         egui::Window::new("Settings")
@@ -568,6 +1458,17 @@ impl NotionApp {
                     need_save = true;
                 }
                 
+                ui.horizontal(|ui| {
+                    ui.label("Tab Width:");
+                    if ui.add(egui::DragValue::new(&mut tab_width).clamp_range(1..=16)).changed() {
+                        need_save = true;
+                    }
+                    ui.label("Wrap Column:");
+                    if ui.add(egui::DragValue::new(&mut wrap_column).clamp_range(10..=400)).changed() {
+                        need_save = true;
+                    }
+                });
+                
                 if ui.checkbox(&mut auto_save, "Auto Save").changed() {
                     need_save = true;
                 }
@@ -604,6 +1505,8 @@ impl NotionApp {
             self.config.syntax_highlighting = syntax_highlighting;
             self.config.auto_save = auto_save;
             self.config.auto_save_interval_secs = auto_save_interval_secs;
+            self.config.tab_width = tab_width;
+            self.config.wrap_column = wrap_column;
             
             if let Err(err) = self.config.save() {
                 log::error!("Failed to save config: {}", err);
@@ -666,6 +1569,10 @@ impl NotionApp {
                         button_clicked = Some("find");
                     }
                     
+                    if ui.button("Bookmark All").clicked() {
+                        button_clicked = Some("bookmark");
+                    }
+                    
                     if ui.button("Close").clicked() {
                         button_clicked = Some("close");
                     }
@@ -678,7 +1585,10 @@ impl NotionApp {
         
         if let Some(action) = button_clicked {
             match action {
-                "find" => self.find_text(),
+                "find" => {
+                    self.find_text();
+                }
+                "bookmark" => self.bookmark_matching_lines(),
                 "close" => find_open = false,
                 _ => {}
             }
@@ -730,7 +1640,9 @@ impl NotionApp {
         
         if let Some(action) = button_clicked {
             match action {
-                "find" => self.find_text(),
+                "find" => {
+                    self.find_text();
+                }
                 "replace" => self.replace_text(),
                 "close" => replace_open = false,
                 _ => {}
@@ -740,47 +1652,209 @@ impl NotionApp {
         self.show_replace_dialog = replace_open;
     }
     
-    fn show_document_map_panel(&mut self, _ctx: &egui::Context) {
-        // 文档映射面板在show_document_area中实现
+    fn show_command_palette(&mut self, ctx: &egui::Context) {
+        let query = self.command_palette.query().to_string();
+        
+        // 最近使用的命令排在最前面，其余按匹配得分排序
+        let mut matches: Vec<CommandMatch> = Command::ALL
+            .iter()
+            .copied()
+            .filter(|&cmd| cmd != Command::OpenCommandPalette && self.command_enabled(cmd))
+            .filter_map(|cmd| {
+                let text = format!("{}: {}", cmd.category(), cmd.title());
+                let m = fuzzy_match(&query, &text)?;
+                let recent = self.config.recent_commands.iter().position(|id| id == cmd.id());
+                Some(CommandMatch { cmd, text, score: m.score, positions: m.positions, recent })
+            })
+            .collect();
+        matches.sort_by(|a, b| match (a.recent, b.recent) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => b.score.cmp(&a.score),
+        });
+        
+        let rows: Vec<PickerRow> = matches
+            .iter()
+            .map(|m| PickerRow {
+                text: m.text.clone(),
+                highlights: m.positions.clone(),
+                detail: match (self.keymap.binding_text(m.cmd), m.recent.is_some()) {
+                    (Some(binding), _) => Some(binding),
+                    (None, true) => Some("recently used".to_string()),
+                    (None, false) => None,
+                },
+            })
+            .collect();
+        
+        if let Some(idx) = self.command_palette.show(ctx, &rows) {
+            let cmd = matches[idx].cmd;
+            self.config.add_recent_command(cmd.id());
+            self.save_config();
+            self.execute_command(ctx, cmd);
+        }
+    }
+    
+    fn show_clipboard_picker(&mut self, ctx: &egui::Context) {
+        let query = self.clipboard_picker.query().to_string();
+        let mut matches: Vec<(usize, i32, PickerRow)> = self.clipboard_history
+            .iter()
+            .enumerate()
+            .filter_map(|(i, text)| {
+                let preview = clipboard::preview(text);
+                let m = fuzzy_match(&query, &preview)?;
+                let lines = text.lines().count();
+                let row = PickerRow {
+                    text: preview,
+                    highlights: m.positions,
+                    detail: (lines > 1).then(|| format!("{} lines", lines)),
+                };
+                Some((i, m.score, row))
+            })
+            .collect();
+        // 没有输入时保持最新的在前
+        if !query.is_empty() {
+            matches.sort_by_key(|(i, score, _)| (std::cmp::Reverse(*score), *i));
+        }
+        let (indices, rows): (Vec<usize>, Vec<PickerRow>) = matches.into_iter().map(|(i, _, row)| (i, row)).unzip();
+        
+        let Some(chosen) = self.clipboard_picker.show(ctx, &rows) else {
+            return;
+        };
+        let Some(text) = self.clipboard_history.get(indices[chosen]).map(str::to_string) else {
+            return;
+        };
+        if let Some(doc) = self.active_document_index.and_then(|idx| self.documents.get_mut(idx)) {
+            doc.replace_selection(&text);
+            doc.request_focus();
+        }
+        // 粘贴的条目成为当前剪贴板内容，并回到历史的最前面
+        self.clipboard_history.push(text.clone());
+        ctx.output_mut(|o| o.copied_text = text);
+    }
+    
+    fn show_document_map_panel(&mut self, ctx: &egui::Context) {
+        let panel = egui::SidePanel::right("document_map")
+            .resizable(true)
+            .default_width(self.config.window.document_map_width)
+            .width_range(80.0..=300.0)
+            .show(ctx, |ui| {
+                ui.heading("Document Map");
+                ui.separator();
+                
+                let doc = self.active_document_index.and_then(|idx| self.documents.get_mut(idx));
+                if let Some(doc) = doc {
+                    self.minimap.show(ui, doc);
+                }
+            });
+        self.config.window.document_map_width = panel.response.rect.width();
     }
     
     fn show_function_list_panel(&mut self, ctx: &egui::Context) {
-        egui::SidePanel::left("function_list")
+        let panel = egui::SidePanel::left("function_list")
             .resizable(true)
-            .default_width(200.0)
+            .default_width(self.config.window.function_list_width)
             .width_range(150.0..=400.0)
             .show(ctx, |ui| {
                 ui.heading("Function List");
                 ui.separator();
                 
                 if let Some(idx) = self.active_document_index {
-                    let mut jump_to_line = None;
-                    
-                    // 先获取内容进行解析
-                    if let Some(doc) = self.documents.get(idx) {
-                        // 简单解析函数列表
-                        let lines: Vec<_> = doc.content.lines().collect();
-                        for (i, line) in lines.iter().enumerate() {
-                            let line = line.trim();
-                            // 非常简单的判断，实际应用中应该使用正则表达式或专门的解析器
-                            if line.contains("fn ") || line.contains("function ") || line.contains("def ") || 
-                               line.contains("class ") || line.contains("struct ") || line.contains("impl ") {
-                                if ui.selectable_label(false, line).clicked() {
-                                    // 跳转到该函数行，保存行号
-                                    jump_to_line = Some(i);
-                                }
-                            }
-                        }
-                    }
+                    let jump_to_line = self.documents.get(idx)
+                        .and_then(|doc| self.function_list.show(ui, doc));
                     
-                    // 然后再执行跳转
+                    // 跳转到选中的符号所在行
                     if let Some(line) = jump_to_line {
                         if let Some(doc) = self.documents.get_mut(idx) {
-                            doc.current_line = line;
-                            doc.scroll_to_line(line);
+                            doc.goto_line(line);
                         }
                     }
                 }
             });
+        self.config.window.function_list_width = panel.response.rect.width();
+    }
+}
+
+#[cfg(unix)]
+impl NotionApp {
+    pub fn handle_rpc_calls(&mut self, rx: &Receiver<RpcCall>) {
+        for call in rx.try_iter() {
+            let result = self.execute_rpc(&call.method, &call.params);
+            call.respond(result);
+        }
+    }
+    
+    fn execute_rpc(&mut self, method: &str, params: &serde_json::Value) -> Result<serde_json::Value, RpcError> {
+        use serde_json::json;
+        
+        match method {
+            "open" => {
+                let path = rpc::param_str(params, "path")?;
+                self.open_path(Path::new(path))?;
+                Ok(json!({ "index": self.active_document_index }))
+            }
+            "goto_line" => {
+                // 行号从1开始
+                let line = rpc::param_usize(params, "line")?.max(1);
+                let doc = self.active_document_mut()?;
+                doc.goto_line(line - 1);
+                Ok(json!({ "line": doc.current_line + 1 }))
+            }
+            "insert" => {
+                let text = rpc::param_str(params, "text")?;
+                self.active_document_mut()?.insert_text(text);
+                Ok(serde_json::Value::Null)
+            }
+            "find" => {
+                self.find_text = rpc::param_str(params, "text")?.to_string();
+                let position = self.find_text();
+                Ok(json!({ "found": position.is_some(), "position": position }))
+            }
+            "get_active_buffer" => {
+                let idx = self.active_document_index;
+                let doc = self.active_document_mut()?;
+                Ok(json!({
+                    "index": idx,
+                    "filename": doc.filename,
+                    "path": doc.path,
+                    "content": doc.content,
+                    "modified": doc.is_modified,
+                    "line": doc.current_line + 1,
+                    "column": doc.current_column + 1,
+                }))
+            }
+            "list_tabs" => {
+                // window 0 is the main window, extra windows follow in the order they were opened
+                let windows = std::iter::once((&self.documents, self.active_document_index))
+                    .chain(self.windows.iter().map(|window| (&window.documents, window.active_document_index)));
+                let mut tabs = Vec::new();
+                for (window, (documents, active)) in windows.enumerate() {
+                    for i in 0..documents.len() {
+                        if let Some(doc) = documents.get(i) {
+                            tabs.push(json!({
+                                "window": window,
+                                "index": i,
+                                "filename": doc.filename,
+                                "path": doc.path,
+                                "modified": doc.is_modified,
+                                "active": Some(i) == active,
+                            }));
+                        }
+                    }
+                }
+                Ok(json!(tabs))
+            }
+            "save_all" => {
+                let saved = self.save_all()?;
+                Ok(json!({ "saved": saved }))
+            }
+            _ => Err(RpcError::new(rpc::METHOD_NOT_FOUND, format!("unknown method \"{}\"", method))),
+        }
     }
-} 
\ No newline at end of file
+    
+    fn active_document_mut(&mut self) -> Result<&mut Document, RpcError> {
+        self.active_document_index
+            .and_then(|idx| self.documents.get_mut(idx))
+            .ok_or_else(|| RpcError::new(rpc::APP_ERROR, "no active document"))
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::BTreeSet;
+
+use crate::clipboard;
+use crate::commands::Command;
+use crate::cursors::LineShift;
+use crate::editor::Document;
+
+/// Bookmarked lines of a document. They move along with lines inserted or
+/// removed above them and disappear with their line.
+#[derive(Debug, Default)]
+pub struct Bookmarks {
+    lines: BTreeSet<usize>,
+}
+
+impl Bookmarks {
+    pub fn contains(&self, line: usize) -> bool {
+        self.lines.contains(&line)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn toggle(&mut self, line: usize) {
+        if !self.lines.remove(&line) {
+            self.lines.insert(line);
+        }
+    }
+
+    pub fn insert(&mut self, line: usize) {
+        self.lines.insert(line);
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    // The next bookmark after `line`, wrapping around at the end
+    pub fn next(&self, line: usize) -> Option<usize> {
+        self.lines.range(line + 1..).next().or_else(|| self.lines.iter().next()).copied()
+    }
+
+    // The previous bookmark before `line`, wrapping around at the start
+    pub fn previous(&self, line: usize) -> Option<usize> {
+        self.lines.range(..line).next_back().or_else(|| self.lines.iter().next_back()).copied()
+    }
+
+    pub fn lines(&self) -> Vec<usize> {
+        self.lines.iter().copied().collect()
+    }
+
+    pub fn set_lines(&mut self, lines: &[usize]) {
+        self.lines = lines.iter().copied().collect();
+    }
+
+    pub fn shift(&mut self, shift: &LineShift) {
+        self.lines = self.lines.iter().filter_map(|&line| shift.apply(line)).collect();
+    }
+
+    // 删除行之后超出文本末尾的书签
+    pub fn truncate(&mut self, line_count: usize) {
+        self.lines.retain(|&line| line < line_count);
+    }
+}
+
+// Bookmark commands that need nothing but the document, shared by the main
+// window and extra windows. Copy and Cut also put the lines on the clipboard.
+pub fn execute(ctx: &eframe::egui::Context, doc: &mut Document, cmd: Command) {
+    match cmd {
+        Command::ToggleBookmark => doc.toggle_bookmark(doc.current_line),
+        Command::NextBookmark => doc.goto_bookmark(true),
+        Command::PreviousBookmark => doc.goto_bookmark(false),
+        Command::ClearBookmarks => doc.clear_bookmarks(),
+        Command::CopyBookmarkedLines | Command::CutBookmarkedLines => {
+            let text = if cmd == Command::CutBookmarkedLines {
+                doc.cut_bookmarked_lines()
+            } else {
+                doc.bookmarked_text()
+            };
+            if let Some(text) = text {
+                clipboard::copy(ctx, text);
+            }
+        }
+        Command::DeleteBookmarkedLines => {
+            doc.cut_bookmarked_lines();
+        }
+        _ => return,
+    }
+    doc.request_focus();
+}
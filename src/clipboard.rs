@@ -0,0 +1,112 @@
+use anyhow::{Result, Context};
+use eframe::egui;
+use std::collections::VecDeque;
+
+use crate::commands::Command;
+use crate::editor::Document;
+
+// 剪贴板历史最多保留的条目数
+const MAX_ENTRIES: usize = 20;
+
+/// Text copied or cut in the editor, newest first, for Paste From History.
+pub struct ClipboardHistory {
+    entries: VecDeque<String>,
+}
+
+impl ClipboardHistory {
+    pub fn new() -> Self {
+        Self { entries: VecDeque::new() }
+    }
+
+    // Copying the same text again moves it back to the front
+    pub fn push(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        self.entries.retain(|entry| *entry != text);
+        self.entries.push_front(text);
+        self.entries.truncate(MAX_ENTRIES);
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(String::as_str)
+    }
+}
+
+/// Put text copied or cut in the editor on the system clipboard, and queue it
+/// for the clipboard history. Copying a file path or the like sets
+/// `copied_text` directly and stays out of the history.
+pub fn copy(ctx: &egui::Context, text: String) {
+    ctx.data_mut(|d| d.get_temp_mut_or_default::<Vec<String>>(egui::Id::new("clipboard_copies")).push(text.clone()));
+    ctx.output_mut(|o| o.copied_text = text);
+}
+
+// Clips queued by `copy` since the last call, oldest first. Extra windows
+// share the context, so their copies end up here too.
+pub fn take_copies(ctx: &egui::Context) -> Vec<String> {
+    ctx.data_mut(|d| d.remove_temp::<Vec<String>>(egui::Id::new("clipboard_copies"))).unwrap_or_default()
+}
+
+/// Current text on the system clipboard. egui only hands it over as part of a
+/// Ctrl+V key press, so menu and palette commands read it themselves.
+pub fn system_text() -> Result<String> {
+    let mut clipboard = arboard::Clipboard::new().with_context(|| "Failed to open the clipboard")?;
+    let text = clipboard.get_text().with_context(|| "Clipboard holds no text")?;
+    Ok(text.replace("\r\n", "\n"))
+}
+
+// Text copied from a block selection is pasted as a rectangle again. The
+// system clipboard only carries plain text, so the last block copy is
+// remembered here and recognised by its content.
+pub fn remember_block_copy(ctx: &egui::Context, text: Option<&str>) {
+    let id = egui::Id::new("block_clip");
+    ctx.data_mut(|d| match text {
+        Some(text) => d.insert_temp(id, text.to_string()),
+        None => d.remove::<String>(id),
+    });
+}
+
+pub fn block_copy(ctx: &egui::Context) -> Option<String> {
+    ctx.data(|d| d.get_temp::<String>(egui::Id::new("block_clip")))
+}
+
+// First line of a clip, shortened for one row of the history picker
+pub fn preview(text: &str) -> String {
+    let line = text.lines().find(|line| !line.trim().is_empty()).unwrap_or("").trim();
+    let mut preview: String = line.chars().take(80).collect();
+    if line.chars().count() > 80 {
+        preview.push('…');
+    }
+    preview
+}
+
+// Cut, Copy and Paste from a menu, the palette or a rebound key. Ctrl+X/C/V
+// pressed in the editor never get here; the editor handles those events itself.
+pub fn execute(ctx: &egui::Context, doc: &mut Document, cmd: Command) {
+    match cmd {
+        Command::Copy | Command::Cut => {
+            let block = doc.is_block_selection();
+            let text = if cmd == Command::Cut { doc.cut() } else { doc.copy_text() };
+            if let Some(text) = text {
+                remember_block_copy(ctx, block.then_some(text.as_str()));
+                copy(ctx, text);
+            }
+        }
+        Command::Paste => match system_text() {
+            Ok(text) if block_copy(ctx).as_ref() == Some(&text) => doc.paste_block(&text),
+            Ok(text) => doc.paste(&text),
+            Err(err) => log::error!("Failed to paste: {}", err),
+        },
+        _ => return,
+    }
+    // 菜单点击会让编辑框失去焦点
+    doc.request_focus();
+}
@@ -0,0 +1,193 @@
+use anyhow::{Result, anyhow};
+use eframe::egui;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Text,
+    Numbers,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberBase {
+    Decimal,
+    Hex,
+}
+
+// How numbers shorter than the longest one are filled up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Padding {
+    None,
+    Zeros,
+    Spaces,
+}
+
+/// What the Column Editor inserts on each line.
+pub enum ColumnInsert {
+    Text(String),
+    Numbers { initial: i64, step: i64, base: NumberBase, padding: Padding },
+}
+
+impl ColumnInsert {
+    // The text for each of `count` lines
+    pub fn pieces(&self, count: usize) -> Vec<String> {
+        match self {
+            ColumnInsert::Text(text) => vec![text.clone(); count],
+            ColumnInsert::Numbers { initial, step, base, padding } => {
+                let numbers: Vec<String> = (0..count as i64)
+                    .map(|i| format_number(initial.saturating_add(step.saturating_mul(i)), *base))
+                    .collect();
+                let width = numbers.iter().map(String::len).max().unwrap_or(0);
+                numbers.into_iter().map(|n| pad_number(n, width, *padding)).collect()
+            }
+        }
+    }
+}
+
+fn format_number(value: i64, base: NumberBase) -> String {
+    match base {
+        NumberBase::Decimal => value.to_string(),
+        NumberBase::Hex if value < 0 => format!("-{:X}", value.unsigned_abs()),
+        NumberBase::Hex => format!("{:X}", value),
+    }
+}
+
+fn pad_number(number: String, width: usize, padding: Padding) -> String {
+    match padding {
+        Padding::None => number,
+        Padding::Spaces => format!("{:>width$}", number),
+        // 补零放在负号之后
+        Padding::Zeros => match number.strip_prefix('-') {
+            Some(digits) => format!("-{:0>w$}", digits, w = width - 1),
+            None => format!("{:0>width$}", number),
+        },
+    }
+}
+
+/// Notepad++-style dialog that inserts text or a number sequence down a column.
+pub struct ColumnEditor {
+    is_open: bool,
+    mode: Mode,
+    text: String,
+    // kept as typed so a half-entered number does not jump around
+    initial: String,
+    step: String,
+    base: NumberBase,
+    padding: Padding,
+    error: Option<String>,
+}
+
+impl ColumnEditor {
+    pub fn new() -> Self {
+        Self {
+            is_open: false,
+            mode: Mode::Text,
+            text: String::new(),
+            initial: "1".to_string(),
+            step: "1".to_string(),
+            base: NumberBase::Decimal,
+            padding: Padding::None,
+            error: None,
+        }
+    }
+
+    pub fn open(&mut self) {
+        self.is_open = true;
+        self.error = None;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<ColumnInsert> {
+        let mut open = self.is_open;
+        let mut result = None;
+        let mut close = false;
+
+        egui::Window::new("Column Editor")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                ui.radio_value(&mut self.mode, Mode::Text, "Text to insert");
+                ui.add_enabled_ui(self.mode == Mode::Text, |ui| {
+                    ui.indent("column_text", |ui| {
+                        ui.text_edit_singleline(&mut self.text);
+                    });
+                });
+
+                ui.add_space(4.0);
+                ui.radio_value(&mut self.mode, Mode::Numbers, "Number to insert");
+                ui.add_enabled_ui(self.mode == Mode::Numbers, |ui| {
+                    ui.indent("column_numbers", |ui| {
+                        egui::Grid::new("column_number_grid").num_columns(2).show(ui, |ui| {
+                            ui.label("Initial number:");
+                            ui.text_edit_singleline(&mut self.initial);
+                            ui.end_row();
+
+                            ui.label("Increase by:");
+                            ui.text_edit_singleline(&mut self.step);
+                            ui.end_row();
+
+                            ui.label("Format:");
+                            ui.horizontal(|ui| {
+                                ui.radio_value(&mut self.base, NumberBase::Decimal, "Dec");
+                                ui.radio_value(&mut self.base, NumberBase::Hex, "Hex");
+                            });
+                            ui.end_row();
+
+                            ui.label("Leading:");
+                            ui.horizontal(|ui| {
+                                ui.radio_value(&mut self.padding, Padding::None, "None");
+                                ui.radio_value(&mut self.padding, Padding::Zeros, "Zeros");
+                                ui.radio_value(&mut self.padding, Padding::Spaces, "Spaces");
+                            });
+                            ui.end_row();
+                        });
+                    });
+                });
+
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("OK").clicked() {
+                        match self.insert() {
+                            Ok(insert) => {
+                                result = Some(insert);
+                                close = true;
+                            }
+                            Err(err) => self.error = Some(err.to_string()),
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        self.is_open = open && !close;
+        result
+    }
+
+    fn insert(&self) -> Result<ColumnInsert> {
+        match self.mode {
+            Mode::Text => Ok(ColumnInsert::Text(self.text.clone())),
+            Mode::Numbers => {
+                let radix = if self.base == NumberBase::Hex { 16 } else { 10 };
+                let parse = |text: &str, what: &str| {
+                    i64::from_str_radix(text.trim(), radix).map_err(|_| anyhow!("{} is not a valid number", what))
+                };
+                Ok(ColumnInsert::Numbers {
+                    initial: parse(&self.initial, "Initial number")?,
+                    step: parse(&self.step, "Increase by")?,
+                    base: self.base,
+                    padding: self.padding,
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+// Every user-facing action has a stable ID so it can be bound to keys, listed
+// in menus and looked up by name from the keymap file.
+macro_rules! commands {
+    ($($variant:ident => $id:literal, $title:literal;)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Command {
+            $($variant,)*
+        }
+
+        impl Command {
+            pub const ALL: &'static [Command] = &[$(Command::$variant,)*];
+
+            pub fn id(self) -> &'static str {
+                match self {
+                    $(Command::$variant => $id,)*
+                }
+            }
+
+            pub fn title(self) -> &'static str {
+                match self {
+                    $(Command::$variant => $title,)*
+                }
+            }
+        }
+    };
+}
+
+commands! {
+    NewFile => "file.new", "New";
+    NewWindow => "file.new_window", "New Window";
+    OpenFile => "file.open", "Open...";
+    OpenFolder => "file.open_folder", "Open Folder...";
+    QuickOpen => "file.quick_open", "Quick Open...";
+    Save => "file.save", "Save";
+    SaveAs => "file.save_as", "Save As...";
+    CloseTab => "file.close_tab", "Close";
+    CloseOtherTabs => "file.close_other_tabs", "Close Others";
+    CloseTabsToRight => "file.close_tabs_to_right", "Close to the Right";
+    ReopenClosedTab => "file.reopen_closed_tab", "Reopen Closed Tab";
+    Exit => "file.exit", "Exit";
+    Undo => "edit.undo", "Undo";
+    Redo => "edit.redo", "Redo";
+    Cut => "edit.cut", "Cut";
+    Copy => "edit.copy", "Copy";
+    Paste => "edit.paste", "Paste";
+    PasteFromHistory => "edit.paste_from_history", "Paste From History...";
+    AddNextOccurrence => "edit.add_next_occurrence", "Add Next Occurrence";
+    SelectAllOccurrences => "edit.select_all_occurrences", "Select All Occurrences";
+    AddCursorAbove => "edit.add_cursor_above", "Add Cursor Above";
+    AddCursorBelow => "edit.add_cursor_below", "Add Cursor Below";
+    ColumnEditor => "edit.column_editor", "Column Editor...";
+    GotoMatchingBracket => "edit.goto_matching_bracket", "Go to Matching Bracket";
+    ToggleLineComment => "edit.toggle_line_comment", "Toggle Line Comment";
+    ToggleBlockComment => "edit.toggle_block_comment", "Toggle Block Comment";
+    DuplicateLine => "edit.duplicate_line", "Duplicate Line or Selection";
+    MoveLineUp => "edit.move_line_up", "Move Line Up";
+    MoveLineDown => "edit.move_line_down", "Move Line Down";
+    DeleteLine => "edit.delete_line", "Delete Line";
+    JoinLines => "edit.join_lines", "Join Lines";
+    SortLinesAscending => "edit.sort_lines_ascending", "Sort Lines Ascending";
+    SortLinesDescending => "edit.sort_lines_descending", "Sort Lines Descending";
+    SortLinesIgnoreCaseAscending => "edit.sort_lines_ignore_case_ascending", "Sort Lines Ascending, Ignoring Case";
+    SortLinesIgnoreCaseDescending => "edit.sort_lines_ignore_case_descending", "Sort Lines Descending, Ignoring Case";
+    SortLinesNumericAscending => "edit.sort_lines_numeric_ascending", "Sort Lines as Numbers Ascending";
+    SortLinesNumericDescending => "edit.sort_lines_numeric_descending", "Sort Lines as Numbers Descending";
+    SortLinesNaturalAscending => "edit.sort_lines_natural_ascending", "Sort Lines in Natural Order Ascending";
+    SortLinesNaturalDescending => "edit.sort_lines_natural_descending", "Sort Lines in Natural Order Descending";
+    RemoveDuplicateLines => "edit.remove_duplicate_lines", "Remove Duplicate Lines";
+    RemoveEmptyLines => "edit.remove_empty_lines", "Remove Empty Lines";
+    ReverseLines => "edit.reverse_lines", "Reverse Lines";
+    UpperCase => "edit.upper_case", "UPPERCASE";
+    LowerCase => "edit.lower_case", "lowercase";
+    TitleCase => "edit.title_case", "Title Case";
+    SentenceCase => "edit.sentence_case", "Sentence case";
+    InvertCase => "edit.invert_case", "iNVERT cASE";
+    SnakeCase => "edit.snake_case", "snake_case";
+    CamelCase => "edit.camel_case", "camelCase";
+    KebabCase => "edit.kebab_case", "kebab-case";
+    TrimTrailingWhitespace => "edit.trim_trailing_whitespace", "Trim Trailing Whitespace";
+    TrimLeadingWhitespace => "edit.trim_leading_whitespace", "Trim Leading Whitespace";
+    TabsToSpaces => "edit.tabs_to_spaces", "Tabs to Spaces";
+    SpacesToTabs => "edit.spaces_to_tabs", "Leading Spaces to Tabs";
+    HardWrap => "edit.hard_wrap", "Hard Wrap at Wrap Column";
+    ReflowParagraphs => "edit.reflow_paragraphs", "Reflow Paragraphs";
+    ToggleBookmark => "edit.toggle_bookmark", "Toggle Bookmark";
+    NextBookmark => "edit.next_bookmark", "Next Bookmark";
+    PreviousBookmark => "edit.previous_bookmark", "Previous Bookmark";
+    ClearBookmarks => "edit.clear_bookmarks", "Clear All Bookmarks";
+    BookmarkMatchingLines => "edit.bookmark_matching_lines", "Bookmark Lines Matching Search";
+    CopyBookmarkedLines => "edit.copy_bookmarked_lines", "Copy Bookmarked Lines";
+    CutBookmarkedLines => "edit.cut_bookmarked_lines", "Cut Bookmarked Lines";
+    DeleteBookmarkedLines => "edit.delete_bookmarked_lines", "Delete Bookmarked Lines";
+    Find => "edit.find", "Find...";
+    Replace => "edit.replace", "Replace...";
+    NextTab => "view.next_tab", "Next Tab";
+    PreviousTab => "view.previous_tab", "Previous Tab";
+    NextRecentTab => "view.next_recent_tab", "Next Recently Used Tab";
+    PreviousRecentTab => "view.previous_recent_tab", "Previous Recently Used Tab";
+    TogglePinTab => "view.toggle_pin_tab", "Pin Tab";
+    SplitRight => "view.split_right", "Split Right";
+    SplitDown => "view.split_down", "Split Down";
+    ClosePane => "view.close_pane", "Close Pane";
+    FocusNextPane => "view.focus_next_pane", "Focus Next Pane";
+    Fold => "view.fold", "Fold";
+    Unfold => "view.unfold", "Unfold";
+    FoldAll => "view.fold_all", "Fold All";
+    UnfoldAll => "view.unfold_all", "Unfold All";
+    FoldLevel1 => "view.fold_level_1", "Fold Level 1";
+    FoldLevel2 => "view.fold_level_2", "Fold Level 2";
+    FoldLevel3 => "view.fold_level_3", "Fold Level 3";
+    FoldLevel4 => "view.fold_level_4", "Fold Level 4";
+    FoldLevel5 => "view.fold_level_5", "Fold Level 5";
+    ToggleWordWrap => "view.toggle_word_wrap", "Word Wrap";
+    ToggleLineNumbers => "view.toggle_line_numbers", "Line Numbers";
+    ToggleSyntaxHighlighting => "view.toggle_syntax_highlighting", "Syntax Highlighting";
+    ToggleFileTree => "view.toggle_file_tree", "Folder Sidebar";
+    ToggleDocumentMap => "view.toggle_document_map", "Document Map";
+    ToggleFunctionList => "view.toggle_function_list", "Function List";
+    Settings => "view.settings", "Settings";
+    OpenCommandPalette => "view.command_palette", "Command Palette...";
+    ThemeLight => "theme.light", "Light";
+    ThemeDark => "theme.dark", "Dark";
+    ThemeBlue => "theme.blue", "Blue";
+    ThemeGreen => "theme.green", "Green";
+    ThemeSolarized => "theme.solarized", "Solarized";
+    About => "help.about", "About";
+}
+
+impl Command {
+    pub fn from_id(id: &str) -> Option<Command> {
+        Self::ALL.iter().copied().find(|cmd| cmd.id() == id)
+    }
+
+    // Menu the command belongs to, taken from the ID prefix
+    pub fn category(self) -> &'static str {
+        match self.id().split('.').next() {
+            Some("file") => "File",
+            Some("edit") => "Edit",
+            Some("view") => "View",
+            Some("theme") => "Theme",
+            _ => "Help",
+        }
+    }
+
+    pub fn theme_name(self) -> Option<&'static str> {
+        match self {
+            Command::ThemeLight | Command::ThemeDark | Command::ThemeBlue
+            | Command::ThemeGreen | Command::ThemeSolarized => Some(self.title()),
+            _ => None,
+        }
+    }
+}
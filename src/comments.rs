@@ -0,0 +1,301 @@
+use std::ops::Range;
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+use crate::commands::Command;
+use crate::cursors::{self, Selection, Splice};
+use crate::editor::Document;
+
+// (语法名, 行注释, 块注释)
+type FallbackTokens = (&'static str, Option<&'static str>, Option<(&'static str, &'static str)>);
+
+// 语法包的元数据里没有注释符号时使用的后备表
+const FALLBACK_TOKENS: &[FallbackTokens] = &[
+    ("C", Some("//"), Some(("/*", "*/"))),
+    ("C++", Some("//"), Some(("/*", "*/"))),
+    ("C#", Some("//"), Some(("/*", "*/"))),
+    ("Objective-C", Some("//"), Some(("/*", "*/"))),
+    ("Java", Some("//"), Some(("/*", "*/"))),
+    ("JavaScript", Some("//"), Some(("/*", "*/"))),
+    ("TypeScript", Some("//"), Some(("/*", "*/"))),
+    ("Rust", Some("//"), Some(("/*", "*/"))),
+    ("Go", Some("//"), Some(("/*", "*/"))),
+    ("Scala", Some("//"), Some(("/*", "*/"))),
+    ("Groovy", Some("//"), Some(("/*", "*/"))),
+    ("D", Some("//"), Some(("/*", "*/"))),
+    ("PHP", Some("//"), Some(("/*", "*/"))),
+    ("CSS", None, Some(("/*", "*/"))),
+    ("Python", Some("#"), None),
+    ("Ruby", Some("#"), Some(("=begin", "=end"))),
+    ("Perl", Some("#"), None),
+    ("Bourne Again Shell (bash)", Some("#"), None),
+    ("Makefile", Some("#"), None),
+    ("YAML", Some("#"), None),
+    ("TOML", Some("#"), None),
+    ("R", Some("#"), None),
+    ("SQL", Some("--"), Some(("/*", "*/"))),
+    ("Lua", Some("--"), Some(("--[[", "]]"))),
+    ("Haskell", Some("--"), Some(("{-", "-}"))),
+    ("Erlang", Some("%"), None),
+    ("LaTeX", Some("%"), None),
+    ("TeX", Some("%"), None),
+    ("MATLAB", Some("%"), Some(("%{", "%}"))),
+    ("Lisp", Some(";"), None),
+    ("Clojure", Some(";"), None),
+    ("Batch File", Some("REM"), None),
+    ("OCaml", None, Some(("(*", "*)"))),
+    ("Pascal", Some("//"), Some(("{", "}"))),
+    ("HTML", None, Some(("<!--", "-->"))),
+    ("XML", None, Some(("<!--", "-->"))),
+    ("Markdown", None, Some(("<!--", "-->"))),
+];
+
+/// The comment markers of a language. Either may be missing: CSS has no
+/// line comments and Python has no block comments.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommentTokens {
+    pub line: Option<String>,
+    pub block: Option<(String, String)>,
+}
+
+impl CommentTokens {
+    /// Markers from the syntax's metadata (`TM_COMMENT_START` and friends),
+    /// with the fallback table filling in whatever the metadata lacks.
+    pub fn for_syntax(syntax: Option<&SyntaxReference>, syntax_set: &SyntaxSet) -> Self {
+        let Some(syntax) = syntax else {
+            return Self::default();
+        };
+        let metadata = syntax_set.metadata().metadata_for_scope(&[syntax.scope]);
+        // 元数据里的符号通常带一个尾随空格，例如 "// "
+        let mut tokens = Self {
+            line: metadata.line_comment().map(|t| t.trim().to_string()).filter(|t| !t.is_empty()),
+            block: metadata
+                .block_comment()
+                .map(|(open, close)| (open.trim().to_string(), close.trim().to_string()))
+                .filter(|(open, close)| !open.is_empty() && !close.is_empty()),
+        };
+        if let Some((_, line, block)) = FALLBACK_TOKENS.iter().find(|(name, _, _)| *name == syntax.name) {
+            if tokens.line.is_none() {
+                tokens.line = line.map(str::to_string);
+            }
+            if tokens.block.is_none() {
+                tokens.block = block.map(|(open, close)| (open.to_string(), close.to_string()));
+            }
+        }
+        tokens
+    }
+}
+
+/// Comment out the lines touched by `sel`, or uncomment them when
+/// every non-blank one is already commented. Markers go at the smallest
+/// indentation of the lines so a commented block keeps its shape; blank
+/// lines are left alone. Languages without line comments wrap each line in
+/// block markers instead. Indentation is compared in display columns, with
+/// tab stops every `tab_width` columns.
+pub fn toggle_line_comment(text: &str, sel: Selection, tokens: &CommentTokens, tab_width: usize) -> Option<Splice> {
+    let (open, close) = match (&tokens.line, &tokens.block) {
+        (Some(line), _) => (line.as_str(), None),
+        (None, Some((open, close))) => (open.as_str(), Some(close.as_str())),
+        (None, None) => return None,
+    };
+
+    let Range { start, end } = cursors::selected_lines(text, sel);
+    let (anchor, head) = (sel.anchor, sel.head);
+
+    let lines: Vec<&str> = text[start..end].split('\n').collect();
+    let indent_of = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+    let is_blank = |line: &str| line.trim().is_empty();
+    let is_commented = |line: &str| {
+        let body = line[indent_of(line)..].trim_end();
+        match close {
+            Some(close) => body.len() >= open.len() + close.len() && body.starts_with(open) && body.ends_with(close),
+            None => body.starts_with(open),
+        }
+    };
+
+    let code: Vec<&str> = lines.iter().copied().filter(|line| !is_blank(line)).collect();
+    if code.is_empty() {
+        return None;
+    }
+    let uncomment = code.iter().all(|line| is_commented(line));
+    // 按显示列比较缩进，制表符和空格混用的行也能对齐
+    let min_column = code.iter().map(|line| indent_column(&line[..indent_of(line)], tab_width)).min().unwrap_or(0);
+
+    let mut new_text = String::new();
+    let (mut new_anchor, mut new_head) = (anchor, head);
+    let mut old_line_start = start;
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            new_text.push('\n');
+        }
+        let new_line_start = start + new_text.len();
+        let (new_line, at, open_delta) = if is_blank(line) {
+            (line.to_string(), 0, 0)
+        } else if uncomment {
+            uncomment_line(line, indent_of(line), open, close)
+        } else {
+            comment_line(line, byte_at_column(line, min_column, tab_width), open, close)
+        };
+
+        // 把选区两端映射到修改后的行上
+        let old_line_end = old_line_start + line.len();
+        for (pos, old) in [(&mut new_anchor, anchor), (&mut new_head, head)] {
+            if (old_line_start..=old_line_end).contains(&old) {
+                let column = old - old_line_start;
+                let column = if column <= at {
+                    column
+                } else {
+                    (column as isize + open_delta).max(at as isize) as usize
+                };
+                *pos = new_line_start + column.min(new_line.len());
+            }
+        }
+
+        new_text.push_str(&new_line);
+        old_line_start = old_line_end + 1;
+    }
+
+    // 选区结束在下一行行首时，保持在那一行行首
+    let delta = new_text.len() as isize - (end - start) as isize;
+    for (pos, old) in [(&mut new_anchor, anchor), (&mut new_head, head)] {
+        if old > end {
+            *pos = (old as isize + delta) as usize;
+        }
+    }
+    Some(Splice::new(start..end, new_text, Selection::new(new_anchor, new_head)))
+}
+
+// Display width of an indentation
+fn indent_column(indent: &str, tab_width: usize) -> usize {
+    indent.chars().fold(0, |column, c| match c {
+        '\t' => column + tab_width - column % tab_width,
+        _ => column + 1,
+    })
+}
+
+// Byte offset in the indentation of `line` where display column `column`
+// starts, or the start of the tab that covers it
+fn byte_at_column(line: &str, column: usize, tab_width: usize) -> usize {
+    let mut width = 0;
+    for (i, c) in line.char_indices() {
+        width = match c {
+            '\t' => width + tab_width - width % tab_width,
+            ' ' => width + 1,
+            _ => return i,
+        };
+        if width > column {
+            return i;
+        }
+    }
+    line.len()
+}
+
+// Insert the markers at byte `at` of `line`; returns the new line, where
+// the opening marker went and how much it grew the line there
+fn comment_line(line: &str, at: usize, open: &str, close: Option<&str>) -> (String, usize, isize) {
+    let (body, cr) = match line.strip_suffix('\r') {
+        Some(body) => (body, "\r"),
+        None => (line, ""),
+    };
+    let mut new_line = format!("{}{} {}", &body[..at], open, &body[at..]);
+    if let Some(close) = close {
+        let trimmed = new_line.trim_end().len();
+        new_line.insert_str(trimmed, &format!(" {}", close));
+    }
+    new_line.push_str(cr);
+    (new_line, at, open.len() as isize + 1)
+}
+
+// Remove the markers (and one space inside each) from a commented line
+fn uncomment_line(line: &str, indent: usize, open: &str, close: Option<&str>) -> (String, usize, isize) {
+    let (body, cr) = match line.strip_suffix('\r') {
+        Some(body) => (body, "\r"),
+        None => (line, ""),
+    };
+    let trailing = &body[body.trim_end().len()..];
+    let mut inner = &body.trim_end()[indent + open.len()..];
+    if let Some(close) = close {
+        inner = inner.strip_suffix(close).unwrap_or(inner);
+        inner = inner.strip_suffix(' ').unwrap_or(inner);
+    }
+    let removed = open.len() + usize::from(inner.starts_with(' '));
+    let inner = inner.strip_prefix(' ').unwrap_or(inner);
+    let new_line = format!("{}{}{}{}", &body[..indent], inner, trailing, cr);
+    (new_line, indent, -(removed as isize))
+}
+
+/// Wrap the selection in block markers, or unwrap it when it already is a
+/// block comment. Without a selection this applies to the text of the
+/// current line. Languages without block comments toggle line comments.
+pub fn toggle_block_comment(text: &str, sel: Selection, tokens: &CommentTokens, tab_width: usize) -> Option<Splice> {
+    let Some((open, close)) = &tokens.block else {
+        return toggle_line_comment(text, sel, tokens, tab_width);
+    };
+
+    let mut range = sel.range();
+    if range.is_empty() {
+        let start = cursors::line_start(text, range.start);
+        let line = text[start..cursors::line_end(text, range.start)].trim_end();
+        let indent = line.len() - line.trim_start().len();
+        if line.trim().is_empty() {
+            // 空行上插入一对空的块注释，光标放在中间
+            let caret = range.start;
+            let comment = format!("{}  {}", open, close);
+            let inside = caret + open.len() + 1;
+            return Some(Splice::new(caret..caret, comment, Selection::caret(inside)));
+        }
+        range = start + indent..start + line.len();
+    }
+
+    // 允许选区两端带有空白；只有空白时没有可注释的内容
+    let selected = &text[range.clone()];
+    if selected.trim().is_empty() {
+        return None;
+    }
+    let leading = selected.len() - selected.trim_start().len();
+    let trailing = selected.len() - selected.trim_end().len();
+    let inner = range.start + leading..range.end - trailing;
+    let body = &text[inner.clone()];
+
+    let new_text = if body.len() >= open.len() + close.len() && body.starts_with(open.as_str()) && body.ends_with(close.as_str()) {
+        let unwrapped = &body[open.len()..body.len() - close.len()];
+        let unwrapped = unwrapped.strip_prefix(' ').unwrap_or(unwrapped);
+        unwrapped.strip_suffix(' ').unwrap_or(unwrapped).to_string()
+    } else {
+        format!("{} {} {}", open, body, close)
+    };
+    let (start, end) = (inner.start, inner.start + new_text.len());
+    let selection = if sel.anchor <= sel.head { Selection::new(start, end) } else { Selection::new(end, start) };
+    Some(Splice::new(inner, new_text, selection))
+}
+
+// Comment commands, shared by the main window and extra windows
+pub fn execute(doc: &mut Document, cmd: Command) {
+    match cmd {
+        Command::ToggleLineComment => doc.toggle_comment(false),
+        Command::ToggleBlockComment => doc.toggle_comment(true),
+        _ => return,
+    }
+    doc.request_focus();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c_tokens() -> CommentTokens {
+        CommentTokens { line: Some("//".to_string()), block: Some(("/*".to_string(), "*/".to_string())) }
+    }
+
+    #[test]
+    fn block_comment_leaves_blank_selection_alone() {
+        assert!(toggle_block_comment("a   b", Selection::new(1, 4), &c_tokens(), 4).is_none());
+    }
+
+    #[test]
+    fn block_comment_wraps_and_unwraps_selection() {
+        let splice = toggle_block_comment("a bc d", Selection::new(1, 5), &c_tokens(), 4).unwrap();
+        assert_eq!((splice.range.clone(), splice.text.as_str()), (2..4, "/* bc */"));
+        let splice = toggle_block_comment("a /* bc */ d", Selection::new(2, 10), &c_tokens(), 4).unwrap();
+        assert_eq!(splice.text, "bc");
+    }
+}
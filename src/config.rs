@@ -13,7 +13,58 @@ pub struct Config {
     pub syntax_highlighting: bool,
     pub auto_save: bool,
     pub auto_save_interval_secs: u64,
+    // columns per tab stop, for converting between tabs and spaces
+    #[serde(default = "default_tab_width")]
+    pub tab_width: usize,
+    // where Hard Wrap and Reflow Paragraph break lines
+    #[serde(default = "default_wrap_column")]
+    pub wrap_column: usize,
     pub recent_files: Vec<String>,
+    // command IDs, most recently used first
+    #[serde(default)]
+    pub recent_commands: Vec<String>,
+    #[serde(default)]
+    pub window: WindowLayout,
+}
+
+// Main window geometry and side panels, saved on exit and restored on launch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowLayout {
+    // outer position in points; None lets the window manager choose
+    pub position: Option<[f32; 2]>,
+    pub size: [f32; 2],
+    pub maximized: bool,
+    pub show_file_tree: bool,
+    pub show_function_list: bool,
+    pub show_document_map: bool,
+    pub file_tree_width: f32,
+    pub function_list_width: f32,
+    pub document_map_width: f32,
+}
+
+impl Default for WindowLayout {
+    fn default() -> Self {
+        Self {
+            position: None,
+            size: [1280.0, 720.0],
+            maximized: false,
+            show_file_tree: true,
+            show_function_list: false,
+            show_document_map: false,
+            file_tree_width: 220.0,
+            function_list_width: 200.0,
+            document_map_width: 120.0,
+        }
+    }
+}
+
+fn default_tab_width() -> usize {
+    4
+}
+
+fn default_wrap_column() -> usize {
+    80
 }
 
 impl Default for Config {
@@ -26,13 +77,17 @@ impl Default for Config {
             syntax_highlighting: true,
             auto_save: false,
             auto_save_interval_secs: 60,
+            tab_width: default_tab_width(),
+            wrap_column: default_wrap_column(),
             recent_files: Vec::new(),
+            recent_commands: Vec::new(),
+            window: WindowLayout::default(),
         }
     }
 }
 
 impl Config {
-    fn config_dir() -> Option<PathBuf> {
+    pub fn config_dir() -> Option<PathBuf> {
         let proj_dirs = ProjectDirs::from("com", "notion-pp", "notion-pp")?;
         let config_dir = proj_dirs.config_dir();
         
@@ -47,6 +102,15 @@ impl Config {
         Some(config_dir.to_path_buf())
     }
     
+    // Directory for sockets and other per-session files; falls back to the temp dir
+    // on platforms without an XDG runtime dir.
+    pub fn runtime_dir() -> PathBuf {
+        ProjectDirs::from("com", "notion-pp", "notion-pp")
+            .and_then(|dirs| dirs.runtime_dir().map(|dir| dir.to_path_buf()))
+            .filter(|dir| dir.exists() || fs::create_dir_all(dir).is_ok())
+            .unwrap_or_else(std::env::temp_dir)
+    }
+
     fn config_file_path() -> Option<PathBuf> {
         let config_dir = Self::config_dir()?;
         Some(config_dir.join("config.json"))
@@ -84,6 +148,12 @@ impl Config {
         Ok(())
     }
     
+    pub fn add_recent_command(&mut self, id: &str) {
+        self.recent_commands.retain(|c| c != id);
+        self.recent_commands.insert(0, id.to_string());
+        self.recent_commands.truncate(20);
+    }
+    
     pub fn add_recent_file(&mut self, path: &str) {
         // Remove if already exists
         self.recent_files.retain(|p| p != path);
@@ -0,0 +1,358 @@
+use crate::commands::Command;
+use crate::cursors::{self, Selection, Splice};
+use crate::editor::Document;
+
+/// A way of rewriting text, applied to the selection or the whole document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    UpperCase,
+    LowerCase,
+    TitleCase,
+    SentenceCase,
+    InvertCase,
+    SnakeCase,
+    CamelCase,
+    KebabCase,
+    TrimTrailing,
+    TrimLeading,
+    TabsToSpaces,
+    SpacesToTabs,
+    HardWrap,
+    Reflow,
+}
+
+impl Transform {
+    // Whitespace and wrapping work on whole lines, the rest on exactly what is selected
+    fn whole_lines(self) -> bool {
+        matches!(
+            self,
+            Transform::TrimTrailing
+                | Transform::TrimLeading
+                | Transform::TabsToSpaces
+                | Transform::SpacesToTabs
+                | Transform::HardWrap
+                | Transform::Reflow
+        )
+    }
+
+    pub fn apply(self, text: &str, tab_width: usize, wrap_column: usize) -> String {
+        let tab_width = tab_width.max(1);
+        match self {
+            Transform::UpperCase => text.to_uppercase(),
+            Transform::LowerCase => text.to_lowercase(),
+            Transform::TitleCase => title_case(text),
+            Transform::SentenceCase => sentence_case(text),
+            Transform::InvertCase => invert_case(text),
+            Transform::SnakeCase => map_identifiers(text, |words| join_lower(words, "_")),
+            Transform::CamelCase => map_identifiers(text, camel_case),
+            Transform::KebabCase => map_identifiers(text, |words| join_lower(words, "-")),
+            Transform::TrimTrailing => map_lines(text, |line| line.trim_end_matches([' ', '\t']).to_string()),
+            Transform::TrimLeading => map_lines(text, |line| line.trim_start_matches([' ', '\t']).to_string()),
+            Transform::TabsToSpaces => map_lines(text, |line| expand_tabs(line, tab_width)),
+            Transform::SpacesToTabs => map_lines(text, |line| indent_with_tabs(line, tab_width)),
+            Transform::HardWrap => hard_wrap(text, wrap_column, tab_width),
+            Transform::Reflow => reflow(text, wrap_column, tab_width),
+        }
+    }
+}
+
+/// Rewrite the selection with `kind`, or the whole document when
+/// nothing is selected. The result stays selected; without a selection the
+/// caret keeps its line and column. None when nothing changed.
+pub fn transform(text: &str, sel: Selection, kind: Transform, tab_width: usize, wrap_column: usize) -> Option<Splice> {
+    let range = if sel.is_empty() {
+        0..text.len()
+    } else if kind.whole_lines() {
+        cursors::selected_lines(text, sel)
+    } else {
+        sel.range()
+    };
+    let old = &text[range.clone()];
+    let new = kind.apply(old, tab_width, wrap_column);
+    if new == old {
+        return None;
+    }
+
+    let selection = if sel.is_empty() {
+        let (line, column) = cursors::line_column(text, sel.head);
+        let caret = match cursors::line_starts(&new).nth(line) {
+            Some(start) => cursors::column_position(&new, start, column).0,
+            None => new.len(),
+        };
+        Selection::caret(caret)
+    } else {
+        let (start, end) = (range.start, range.start + new.len());
+        if sel.anchor <= sel.head { Selection::new(start, end) } else { Selection::new(end, start) }
+    };
+    Some(Splice::new(range, new, selection))
+}
+
+fn title_case(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_word = false;
+    for c in text.chars() {
+        if in_word {
+            out.extend(c.to_lowercase());
+        } else {
+            out.extend(c.to_uppercase());
+        }
+        // 撇号不断开单词，例如 don't
+        in_word = c.is_alphanumeric() || (in_word && c == '\'');
+    }
+    out
+}
+
+// Lower case, with a capital at the start and after `.`, `!` or `?`
+fn sentence_case(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut capitalize = true;
+    for c in text.chars() {
+        if c.is_alphabetic() {
+            if capitalize {
+                out.extend(c.to_uppercase());
+                capitalize = false;
+            } else {
+                out.extend(c.to_lowercase());
+            }
+            continue;
+        }
+        out.push(c);
+        if matches!(c, '.' | '!' | '?') {
+            capitalize = true;
+        } else if c.is_numeric() {
+            capitalize = false;
+        }
+    }
+    out
+}
+
+fn invert_case(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_uppercase() {
+            out.extend(c.to_lowercase());
+        } else {
+            out.extend(c.to_uppercase());
+        }
+    }
+    out
+}
+
+// Rewrite every identifier (a run of letters, digits, `_` and `-`) from the
+// words it is made of. Leading and trailing `_` or `-` are kept, so
+// `__init__` stays as it is.
+fn map_identifiers(text: &str, join: impl Fn(&[String]) -> String) -> String {
+    let is_part = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(is_part) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c: char| !is_part(c)).unwrap_or(rest.len());
+        let identifier = &rest[..end];
+        let core = identifier.trim_matches(['_', '-']);
+        let words = split_words(core);
+        // 以数字开头的不是标识符，例如 2-3
+        if words.is_empty() || core.starts_with(|c: char| c.is_ascii_digit()) {
+            out.push_str(identifier);
+        } else {
+            let prefix = &identifier[..identifier.len() - identifier.trim_start_matches(['_', '-']).len()];
+            let suffix = &identifier[identifier.trim_end_matches(['_', '-']).len()..];
+            out.push_str(prefix);
+            out.push_str(&join(&words));
+            out.push_str(suffix);
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+// Words of `fooBar`, `foo_bar`, `foo-bar` or `HTTPServer`
+fn split_words(identifier: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for part in identifier.split(['_', '-']).filter(|part| !part.is_empty()) {
+        let chars: Vec<char> = part.chars().collect();
+        let mut word = String::new();
+        for (i, &c) in chars.iter().enumerate() {
+            let prev = i.checked_sub(1).map(|i| chars[i]);
+            let next = chars.get(i + 1).copied();
+            // fooBar 在 B 处断开，HTTPServer 在 S 处断开
+            let boundary = c.is_uppercase()
+                && prev.is_some_and(|p| p.is_lowercase() || p.is_numeric()
+                    || (p.is_uppercase() && next.is_some_and(char::is_lowercase)));
+            if boundary && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            word.push(c);
+        }
+        words.push(word);
+    }
+    words
+}
+
+fn join_lower(words: &[String], separator: &str) -> String {
+    words.iter().map(|word| word.to_lowercase()).collect::<Vec<_>>().join(separator)
+}
+
+fn camel_case(words: &[String]) -> String {
+    let mut out = String::new();
+    for (i, word) in words.iter().enumerate() {
+        let word = word.to_lowercase();
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) if i > 0 => {
+                out.extend(first.to_uppercase());
+                out.push_str(chars.as_str());
+            }
+            _ => out.push_str(&word),
+        }
+    }
+    out
+}
+
+// Apply `f` to each line, keeping the line breaks (and `\r` before them)
+fn map_lines(text: &str, f: impl Fn(&str) -> String) -> String {
+    text.split('\n')
+        .map(|line| match line.strip_suffix('\r') {
+            Some(line) => f(line) + "\r",
+            None => f(line),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Display width of `text` starting at column 0
+fn width(text: &str, tab_width: usize) -> usize {
+    text.chars().fold(0, |column, c| match c {
+        '\t' => column + tab_width - column % tab_width,
+        _ => column + 1,
+    })
+}
+
+// Replace every tab with spaces up to the next tab stop
+fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = tab_width - column % tab_width;
+            out.push_str(&" ".repeat(spaces));
+            column += spaces;
+        } else {
+            out.push(c);
+            column += 1;
+        }
+    }
+    out
+}
+
+// Rewrite the indentation with as many tabs as fit; spaces inside the line
+// are left alone since they usually line things up
+fn indent_with_tabs(line: &str, tab_width: usize) -> String {
+    let body = line.trim_start_matches([' ', '\t']);
+    let column = width(&line[..line.len() - body.len()], tab_width);
+    format!("{}{}{}", "\t".repeat(column / tab_width), " ".repeat(column % tab_width), body)
+}
+
+// Break lines longer than `column` at spaces; the pieces keep the line's
+// indentation and its line ending. Existing line breaks stay.
+fn hard_wrap(text: &str, column: usize, tab_width: usize) -> String {
+    text.split('\n')
+        .map(|line| {
+            let (line, cr) = match line.strip_suffix('\r') {
+                Some(line) => (line, "\r"),
+                None => (line, ""),
+            };
+            let body = line.trim_start_matches([' ', '\t']);
+            let indent = &line[..line.len() - body.len()];
+            if width(line, tab_width) <= column {
+                return format!("{}{}", line, cr);
+            }
+            // 新加的换行和原来的行尾一致，CRLF 文件里也是 \r\n
+            let pieces = wrap_words(indent, body.split_whitespace(), column, tab_width);
+            format!("{}{}", pieces.join(&format!("{}\n", cr)), cr)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Join the lines of each paragraph (separated by blank lines) and wrap them
+// again at `column`, with the indentation of the paragraph's first line
+fn reflow(text: &str, column: usize, tab_width: usize) -> String {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let crlf = lines.iter().any(|line| line.ends_with('\r'));
+    let mut out: Vec<String> = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let flush = |paragraph: &mut Vec<&str>, out: &mut Vec<String>| {
+        if let Some(first) = paragraph.first() {
+            let indent = &first[..first.len() - first.trim_start_matches([' ', '\t']).len()];
+            let words = paragraph.iter().flat_map(|line| line.split_whitespace());
+            out.extend(wrap_words(indent, words, column, tab_width));
+            paragraph.clear();
+        }
+    };
+    for line in &lines {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.trim().is_empty() {
+            flush(&mut paragraph, &mut out);
+            out.push(line.to_string());
+        } else {
+            paragraph.push(line);
+        }
+    }
+    flush(&mut paragraph, &mut out);
+    // 段落重排后行数会变，统一使用原来的换行风格
+    out.join(if crlf { "\r\n" } else { "\n" })
+}
+
+// Fill lines up to `column` with `words`; a word longer than that gets a line of its own
+fn wrap_words<'a>(indent: &str, words: impl Iterator<Item = &'a str>, column: usize, tab_width: usize) -> Vec<String> {
+    let indent_width = width(indent, tab_width);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    for word in words {
+        let word_width = word.chars().count();
+        if !line.is_empty() && indent_width + line_width + 1 + word_width > column {
+            lines.push(format!("{}{}", indent, std::mem::take(&mut line)));
+            line_width = 0;
+        }
+        if !line.is_empty() {
+            line.push(' ');
+            line_width += 1;
+        }
+        line.push_str(word);
+        line_width += word_width;
+    }
+    if !line.is_empty() {
+        lines.push(format!("{}{}", indent, line));
+    }
+    lines
+}
+
+// Convert commands, shared by the main window and extra windows
+pub fn execute(doc: &mut Document, cmd: Command) {
+    let kind = match cmd {
+        Command::UpperCase => Transform::UpperCase,
+        Command::LowerCase => Transform::LowerCase,
+        Command::TitleCase => Transform::TitleCase,
+        Command::SentenceCase => Transform::SentenceCase,
+        Command::InvertCase => Transform::InvertCase,
+        Command::SnakeCase => Transform::SnakeCase,
+        Command::CamelCase => Transform::CamelCase,
+        Command::KebabCase => Transform::KebabCase,
+        Command::TrimTrailingWhitespace => Transform::TrimTrailing,
+        Command::TrimLeadingWhitespace => Transform::TrimLeading,
+        Command::TabsToSpaces => Transform::TabsToSpaces,
+        Command::SpacesToTabs => Transform::SpacesToTabs,
+        Command::HardWrap => Transform::HardWrap,
+        Command::ReflowParagraphs => Transform::Reflow,
+        _ => return,
+    };
+    let sel = doc.primary_selection();
+    if let Some(splice) = transform(&doc.content, sel, kind, doc.tab_width, doc.wrap_column) {
+        doc.apply_splice(splice);
+    }
+    doc.request_focus();
+}
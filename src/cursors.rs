@@ -0,0 +1,606 @@
+use std::ops::Range;
+
+use crate::commands::Command;
+use crate::editor::Document;
+
+/// A caret with an optional selection, as byte offsets into the document.
+/// `head` is where the caret is drawn; `anchor` is the other end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Selection {
+    pub anchor: usize,
+    pub head: usize,
+}
+
+impl Selection {
+    pub fn new(anchor: usize, head: usize) -> Self {
+        Self { anchor, head }
+    }
+
+    pub fn caret(pos: usize) -> Self {
+        Self { anchor: pos, head: pos }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.anchor.min(self.head)..self.anchor.max(self.head)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+}
+
+/// A rectangular selection between two (line, column) corners. Columns count
+/// characters and may lie past the end of a short line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+    pub anchor: (usize, usize),
+    pub head: (usize, usize),
+}
+
+impl Block {
+    pub fn new(anchor: (usize, usize), head: (usize, usize)) -> Self {
+        Self { anchor, head }
+    }
+
+    pub fn lines(&self) -> Range<usize> {
+        self.anchor.0.min(self.head.0)..self.anchor.0.max(self.head.0) + 1
+    }
+
+    pub fn left(&self) -> usize {
+        self.anchor.1.min(self.head.1)
+    }
+
+    // One selection per line, the head's line first so it becomes the primary
+    pub fn selections(&self, text: &str) -> Vec<Selection> {
+        let lines = self.lines();
+        let mut selections = Vec::new();
+        for (line, start) in line_starts(text).enumerate().skip(lines.start).take(lines.len()) {
+            let sel = Selection::new(at_column(text, start, self.anchor.1), at_column(text, start, self.head.1));
+            if line == self.head.0 {
+                selections.insert(0, sel);
+            } else {
+                selections.push(sel);
+            }
+        }
+        selections
+    }
+}
+
+/// Replace `range` with `text`, then select `selection`. Commands that
+/// rewrite a stretch of the document return one of these.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Splice {
+    pub range: Range<usize>,
+    pub text: String,
+    pub selection: Selection,
+    // see `Change::line_map`
+    pub line_map: Option<Vec<Option<usize>>>,
+}
+
+impl Splice {
+    pub fn new(range: Range<usize>, text: String, selection: Selection) -> Self {
+        Self { range, text, selection, line_map: None }
+    }
+
+    // For commands that move whole lines around, so bookmarks and folds
+    // can go with them
+    pub fn with_line_map(mut self, line_map: Vec<Option<usize>>) -> Self {
+        self.line_map = Some(line_map);
+        self
+    }
+}
+
+/// One replacement in the text: `removed`, found at byte `start`, becomes
+/// `inserted`. An edit is a list of these in text order, every `start` an
+/// offset into the text as it was before the edit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub start: usize,
+    pub removed: String,
+    pub inserted: String,
+    // Where each line of `removed` went among the lines of `inserted`,
+    // counted from the line `start` is on; None for a line that is gone.
+    // Without it lines are paired up in order.
+    pub line_map: Option<Vec<Option<usize>>>,
+}
+
+impl Change {
+    pub fn new(text: &str, range: Range<usize>, inserted: &str) -> Self {
+        Self {
+            start: range.start,
+            removed: text[range].to_string(),
+            inserted: inserted.to_string(),
+            line_map: None,
+        }
+    }
+
+    // End of `removed` in the text before the edit
+    pub fn end(&self) -> usize {
+        self.start + self.removed.len()
+    }
+}
+
+/// A change made at every cursor at once.
+pub enum Edit<'a> {
+    // type over each selection
+    Insert(&'a str),
+    // one piece per cursor, in document order, e.g. pasting one line per cursor
+    InsertEach(Vec<&'a str>),
+    DeleteBackward { word: bool },
+    DeleteForward { word: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Left,
+    Right,
+    WordLeft,
+    WordRight,
+    Up,
+    Down,
+    LineStart,
+    LineEnd,
+}
+
+// The changes that make `edit` at every selection, and the carets after it
+// in the same order as `selections`. Ranges that overlap an earlier one are
+// cut short so no text is touched twice.
+pub fn edit_changes(text: &str, selections: &[Selection], edit: &Edit) -> (Vec<Change>, Vec<Selection>) {
+    let mut order: Vec<usize> = (0..selections.len()).collect();
+    order.sort_by_key(|&i| selections[i].range().start);
+
+    let mut result = vec![Selection::default(); selections.len()];
+    let mut changes = Vec::new();
+    let mut last = 0;
+    let mut delta = 0isize;
+    for (n, &i) in order.iter().enumerate() {
+        let sel = selections[i];
+        let mut range = match edit {
+            Edit::DeleteBackward { word } if sel.is_empty() => {
+                let start = if *word { word_start_before(text, sel.head) } else { prev_char(text, sel.head) };
+                start..sel.head
+            }
+            Edit::DeleteForward { word } if sel.is_empty() => {
+                let end = if *word { word_end_after(text, sel.head) } else { next_char(text, sel.head) };
+                sel.head..end
+            }
+            _ => sel.range(),
+        };
+        range.start = range.start.max(last);
+        range.end = range.end.max(range.start);
+
+        let inserted = match edit {
+            Edit::Insert(insert) => insert,
+            Edit::InsertEach(pieces) => pieces.get(n).copied().unwrap_or(""),
+            _ => "",
+        };
+        result[i] = Selection::caret(range.start.saturating_add_signed(delta) + inserted.len());
+        delta += inserted.len() as isize - range.len() as isize;
+        last = range.end;
+        if !range.is_empty() || !inserted.is_empty() {
+            changes.push(Change::new(text, range, inserted));
+        }
+    }
+    (changes, result)
+}
+
+// Make `changes` (in text order) in one pass over the text
+pub fn apply_changes(text: &mut String, changes: &[Change]) {
+    if let [change] = changes {
+        text.replace_range(change.start..change.end(), &change.inserted);
+        return;
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for change in changes {
+        out.push_str(&text[last..change.start]);
+        out.push_str(&change.inserted);
+        last = change.end();
+    }
+    out.push_str(&text[last..]);
+    *text = out;
+}
+
+// The changes that take `changes` back, as offsets into the text after them
+pub fn invert_changes(changes: &[Change]) -> Vec<Change> {
+    let mut delta = 0isize;
+    changes
+        .iter()
+        .map(|change| {
+            let start = change.start.saturating_add_signed(delta);
+            delta += change.inserted.len() as isize - change.removed.len() as isize;
+            // 反过来：新的每一行来自原来的哪一行
+            let line_map = change.line_map.as_ref().map(|map| {
+                let mut back = vec![None; change.inserted.matches('\n').count() + 1];
+                for (old, new) in map.iter().enumerate() {
+                    if let Some(slot) = new.and_then(|new| back.get_mut(new)) {
+                        *slot = Some(old);
+                    }
+                }
+                back
+            });
+            Change { start, removed: change.inserted.clone(), inserted: change.removed.clone(), line_map }
+        })
+        .collect()
+}
+
+// The change that turns `old` into `new`, found from where they start and
+// stop being the same. None if they are equal.
+pub fn diff(old: &str, new: &str) -> Option<Change> {
+    let mut prefix = old.bytes().zip(new.bytes()).take_while(|(a, b)| a == b).count();
+    if prefix == old.len() && prefix == new.len() {
+        return None;
+    }
+    while !old.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let longest = old.len().min(new.len()) - prefix;
+    let mut suffix = old.bytes().rev().zip(new.bytes().rev()).take(longest).take_while(|(a, b)| a == b).count();
+    while !old.is_char_boundary(old.len() - suffix) {
+        suffix -= 1;
+    }
+    Some(Change {
+        start: prefix,
+        removed: old[prefix..old.len() - suffix].to_string(),
+        inserted: new[prefix..new.len() - suffix].to_string(),
+        line_map: None,
+    })
+}
+
+// Where a selection ends up after a caret movement. Without `extend` a
+// selection collapses to the side it was moved towards.
+pub fn move_selection(text: &str, sel: Selection, motion: Motion, extend: bool) -> Selection {
+    if !extend && !sel.is_empty() {
+        match motion {
+            Motion::Left => return Selection::caret(sel.range().start),
+            Motion::Right => return Selection::caret(sel.range().end),
+            _ => {}
+        }
+    }
+    let head = match motion {
+        Motion::Left => prev_char(text, sel.head),
+        Motion::Right => next_char(text, sel.head),
+        Motion::WordLeft => word_start_before(text, sel.head),
+        Motion::WordRight => word_end_after(text, sel.head),
+        Motion::Up => line_above(text, sel.head).unwrap_or(0),
+        Motion::Down => line_below(text, sel.head).unwrap_or(text.len()),
+        Motion::LineStart => line_start(text, sel.head),
+        Motion::LineEnd => line_end(text, sel.head),
+    };
+    if extend {
+        Selection::new(sel.anchor, head)
+    } else {
+        Selection::caret(head)
+    }
+}
+
+/// How line numbers moved in an edit, worked out from its changes. Lines
+/// after a change move by the line breaks it removed and inserted. The lines
+/// it rewrote are paired up with the new ones in order (or by its line map);
+/// those left over are gone.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineShift {
+    // in text order, lines counted before the edit
+    steps: Vec<LineStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LineStep {
+    // first line the change touches
+    line: usize,
+    // how many lines it rewrote and how many it left in their place
+    old: usize,
+    new: usize,
+    map: Option<Vec<Option<usize>>>,
+}
+
+impl LineShift {
+    // `text` is the text before the changes
+    pub fn new(text: &str, changes: &[Change]) -> Self {
+        let mut steps = Vec::with_capacity(changes.len());
+        let (mut line, mut counted) = (0, 0);
+        for change in changes {
+            line += text[counted..change.start].matches('\n').count();
+            counted = change.start;
+            let removed = change.removed.matches('\n').count();
+            let inserted = change.inserted.matches('\n').count();
+            // 从行首开始、以换行结束的修改替换的是整行，第一行本身也算在内；
+            // 否则第一行只是被改动，仍然保留
+            let whole_lines = (change.start == 0 || text.as_bytes()[change.start - 1] == b'\n')
+                && (change.removed.is_empty() || change.removed.ends_with('\n'))
+                && (change.inserted.is_empty() || change.inserted.ends_with('\n'));
+            let partial = usize::from(!whole_lines || change.line_map.is_some());
+            steps.push(LineStep {
+                line,
+                old: removed + partial,
+                new: inserted + partial,
+                map: change.line_map.clone(),
+            });
+        }
+        Self { steps }
+    }
+
+    // Where `line` is now; None if the line was removed
+    pub fn apply(&self, line: usize) -> Option<usize> {
+        let mut delta = 0isize;
+        for step in &self.steps {
+            if line < step.line {
+                break;
+            }
+            let index = line - step.line;
+            if index < step.old {
+                let new = match &step.map {
+                    Some(map) => map.get(index).copied().flatten()?,
+                    None => (index < step.new).then_some(index)?,
+                };
+                return Some(step.line.saturating_add_signed(delta) + new);
+            }
+            delta += step.new as isize - step.old as isize;
+        }
+        Some(line.saturating_add_signed(delta))
+    }
+}
+
+/// Where byte offsets went in an edit, for views of the text that weren't
+/// looking when it happened. An offset inside replaced text moves to the end
+/// of what replaced it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OffsetShift {
+    // (start, removed length, inserted length), in text order
+    spans: Vec<(usize, usize, usize)>,
+}
+
+impl OffsetShift {
+    pub fn new(changes: &[Change]) -> Self {
+        let spans = changes.iter().map(|c| (c.start, c.removed.len(), c.inserted.len())).collect();
+        Self { spans }
+    }
+
+    pub fn apply(&self, pos: usize) -> usize {
+        let mut delta = 0isize;
+        for &(start, removed, inserted) in &self.spans {
+            if pos < start {
+                break;
+            }
+            if pos < start + removed {
+                return start.saturating_add_signed(delta) + inserted;
+            }
+            delta += inserted as isize - removed as isize;
+        }
+        pos.saturating_add_signed(delta)
+    }
+}
+
+// Same column (in characters) on the previous line, None on the first line
+pub fn line_above(text: &str, pos: usize) -> Option<usize> {
+    let start = line_start(text, pos);
+    if start == 0 {
+        return None;
+    }
+    let column = text[start..pos].chars().count();
+    Some(at_column(text, line_start(text, start - 1), column))
+}
+
+pub fn line_below(text: &str, pos: usize) -> Option<usize> {
+    let end = line_end(text, pos);
+    if end == text.len() {
+        return None;
+    }
+    let column = text[line_start(text, pos)..pos].chars().count();
+    Some(at_column(text, end + 1, column))
+}
+
+pub fn line_starts(text: &str) -> impl Iterator<Item = usize> + '_ {
+    std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1))
+}
+
+// Line number and character column of `pos`
+pub fn line_column(text: &str, pos: usize) -> (usize, usize) {
+    let start = line_start(text, pos);
+    (text[..start].matches('\n').count(), text[start..pos].chars().count())
+}
+
+// Position of `column` on the line starting at `start`, and how many columns
+// the line falls short of it
+pub fn column_position(text: &str, start: usize, column: usize) -> (usize, usize) {
+    let length = text[start..line_end(text, start)].chars().count();
+    (at_column(text, start, column), column.saturating_sub(length))
+}
+
+// The lines `sel` touches, without the last line's break. A selection
+// ending at the start of a line leaves that line out.
+pub fn selected_lines(text: &str, sel: Selection) -> Range<usize> {
+    let range = sel.range();
+    let start = line_start(text, range.start);
+    if !range.is_empty() && range.end > start && line_start(text, range.end) == range.end {
+        start..range.end - 1
+    } else {
+        start..line_end(text, range.end)
+    }
+}
+
+pub fn line_start(text: &str, pos: usize) -> usize {
+    text[..pos].rfind('\n').map(|i| i + 1).unwrap_or(0)
+}
+
+pub fn line_end(text: &str, pos: usize) -> usize {
+    text[pos..].find('\n').map(|i| pos + i).unwrap_or(text.len())
+}
+
+// The word under or right before `pos`, if any
+pub fn word_at(text: &str, pos: usize) -> Option<Range<usize>> {
+    let start = text[..pos]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_word_char(c))
+        .last()
+        .map(|(i, _)| i)
+        .unwrap_or(pos);
+    let end = text[pos..]
+        .char_indices()
+        .find(|&(_, c)| !is_word_char(c))
+        .map(|(i, _)| pos + i)
+        .unwrap_or(text.len());
+    (start < end).then_some(start..end)
+}
+
+// Whether `range` is a whole word, i.e. no word characters touch it on
+// either side
+pub fn is_whole_word(text: &str, range: Range<usize>) -> bool {
+    let before = text[..range.start].chars().next_back();
+    let after = text[range.end..].chars().next();
+    !before.map(is_word_char).unwrap_or(false) && !after.map(is_word_char).unwrap_or(false)
+}
+
+// 单词边界：字母数字和下划线算一类，其余标点算另一类，空白被跳过
+fn char_class(c: char) -> u8 {
+    if is_word_char(c) {
+        2
+    } else if c.is_whitespace() {
+        0
+    } else {
+        1
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn word_start_before(text: &str, pos: usize) -> usize {
+    let trimmed = text[..pos].trim_end_matches(|c: char| c.is_whitespace() && c != '\n');
+    let Some(last) = trimmed.chars().next_back() else {
+        return 0;
+    };
+    if last == '\n' {
+        // 行首的空白跳到行首，已经在行首则跳到上一行末尾
+        return if trimmed.len() < pos { trimmed.len() } else { trimmed.len() - 1 };
+    }
+    let class = char_class(last);
+    trimmed
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| char_class(c) == class)
+        .last()
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+fn word_end_after(text: &str, pos: usize) -> usize {
+    let after = &text[pos..];
+    let trimmed = after.trim_start_matches(|c: char| c.is_whitespace() && c != '\n');
+    let skipped = after.len() - trimmed.len();
+    let Some(first) = trimmed.chars().next() else {
+        return text.len();
+    };
+    if first == '\n' {
+        return pos + skipped + if skipped > 0 { 0 } else { 1 };
+    }
+    let class = char_class(first);
+    let len = trimmed
+        .char_indices()
+        .find(|&(_, c)| char_class(c) != class)
+        .map(|(i, _)| i)
+        .unwrap_or(trimmed.len());
+    pos + skipped + len
+}
+
+fn prev_char(text: &str, pos: usize) -> usize {
+    text[..pos].char_indices().next_back().map(|(i, _)| i).unwrap_or(0)
+}
+
+fn next_char(text: &str, pos: usize) -> usize {
+    text[pos..].chars().next().map(|c| pos + c.len_utf8()).unwrap_or(pos)
+}
+
+fn at_column(text: &str, line_start: usize, column: usize) -> usize {
+    let end = line_end(text, line_start);
+    text[line_start..end]
+        .char_indices()
+        .nth(column)
+        .map(|(i, _)| line_start + i)
+        .unwrap_or(end)
+}
+
+// Multi-cursor commands, shared by the main window and extra windows
+pub fn execute(doc: &mut Document, cmd: Command) {
+    match cmd {
+        Command::AddNextOccurrence => doc.add_next_occurrence(),
+        Command::SelectAllOccurrences => doc.select_all_occurrences(),
+        Command::AddCursorAbove => doc.add_cursor_vertically(false),
+        Command::AddCursorBelow => doc.add_cursor_vertically(true),
+        _ => return,
+    }
+    doc.request_focus();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shift(text: &str, range: Range<usize>, inserted: &str) -> LineShift {
+        LineShift::new(text, &[Change::new(text, range, inserted)])
+    }
+
+    #[test]
+    fn lines_below_a_change_follow_it() {
+        let text = "a\nb\nc\nd\n";
+        // 在第 1 行的行尾插入一行
+        let moved = shift(text, 3..3, "\nx");
+        assert_eq!(moved.apply(1), Some(1));
+        assert_eq!(moved.apply(2), Some(3));
+        // 在第 1 行的行首插入一整行，第 1 行跟着下移
+        let moved = shift(text, 2..2, "x\n");
+        assert_eq!(moved.apply(0), Some(0));
+        assert_eq!(moved.apply(1), Some(2));
+        // 删除第 1、2 行
+        let moved = shift(text, 2..6, "");
+        assert_eq!(moved.apply(1), None);
+        assert_eq!(moved.apply(2), None);
+        assert_eq!(moved.apply(3), Some(1));
+    }
+
+    #[test]
+    fn changes_above_and_below_count_separately() {
+        let text = "a\nb\nc\nd\ne\n";
+        // 第 0 行后面插入一行，第 3 行整行删除：只看公共前缀会把第 4 行算成被删除
+        let changes = [Change::new(text, 1..1, "\nx"), Change::new(text, 6..8, "")];
+        let moved = LineShift::new(text, &changes);
+        assert_eq!(moved.apply(2), Some(3));
+        assert_eq!(moved.apply(3), None);
+        assert_eq!(moved.apply(4), Some(4));
+    }
+
+    #[test]
+    fn rewritten_lines_pair_up_or_follow_the_map() {
+        let text = "a\nb\nc\n";
+        let moved = shift(text, 0..3, "A\nB");
+        assert_eq!(moved.apply(1), Some(1));
+        let mut change = Change::new(text, 0..3, "b\na");
+        change.line_map = Some(vec![Some(1), Some(0)]);
+        let moved = LineShift::new(text, &[change]);
+        assert_eq!(moved.apply(0), Some(1));
+        assert_eq!(moved.apply(1), Some(0));
+        assert_eq!(moved.apply(2), Some(2));
+    }
+
+    #[test]
+    fn inverted_changes_restore_the_text() {
+        let text = "one two\nthree";
+        let (changes, _) = edit_changes(text, &[Selection::new(0, 3), Selection::caret(8)], &Edit::Insert("x\n"));
+        let mut edited = text.to_string();
+        apply_changes(&mut edited, &changes);
+        assert_eq!(edited, "x\n two\nx\nthree");
+        apply_changes(&mut edited, &invert_changes(&changes));
+        assert_eq!(edited, text);
+    }
+
+    #[test]
+    fn diff_finds_the_changed_stretch() {
+        let change = diff("ab\ncd", "ab\n\ncd").unwrap();
+        assert_eq!((change.start, change.removed.as_str(), change.inserted.as_str()), (3, "", "\n"));
+        let change = diff("aé", "aè").unwrap();
+        assert_eq!((change.start, change.removed.as_str(), change.inserted.as_str()), (1, "é", "è"));
+        assert_eq!(diff("same", "same"), None);
+    }
+}
@@ -1,44 +1,147 @@
-use eframe::egui;
+use eframe::egui::{self, text::{CCursor, CCursorRange}};
+use std::collections::VecDeque;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::fs;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use anyhow::{Result, Context};
 use syntect::highlighting::ThemeSet;
 use syntect::parsing::{SyntaxSet, SyntaxReference};
 
+use crate::analysis::{self, Analysis, AnalysisKey, Background};
+use crate::bookmarks::{self, Bookmarks};
+use crate::clipboard;
+use crate::commands::Command;
+use crate::comments::{self, CommentTokens};
+use crate::config::Config;
+use crate::convert;
+use crate::cursors::{self, Block, Change, Edit, LineShift, Motion, OffsetShift, Selection, Splice};
+use crate::folding::{self, Folding};
+use crate::gutter::{Gutter, GutterAction, LineInfo};
+use crate::history::{EditKind, History};
+use crate::language::{self, LanguageRules};
+use crate::line_ops;
+
+// 最多记住的最近编辑，供其他窗格的视图跟着移动
+const MAX_RECENT_EDITS: usize = 100;
+
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(1);
+
+// Revisions are counted across all documents, so one never repeats
+fn next_revision() -> u64 {
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+// Where a pane is looking in a document. The document itself holds the view of
+// the active pane; other panes keep theirs here.
+#[derive(Debug, Clone, Default)]
+pub struct ViewState {
+    pub scroll_offset: f32,
+    pub cursor_position: usize,
+    pub selection: Option<(usize, usize)>,
+    pub current_line: usize,
+    pub current_column: usize,
+    pub viewport_height: f32,
+    pub content_height: f32,
+    pub extra_cursors: Vec<Selection>,
+    pub block: Option<Block>,
+    // (anchor, head) to hand to the text box on the next frame
+    pub pending_selection: Option<(usize, usize)>,
+    pub focus_requested: bool,
+    // revision of the document the offsets above belong to
+    pub revision: u64,
+}
+
 pub struct Document {
     pub path: Option<PathBuf>,
     pub content: String,
     pub filename: String,
     pub is_modified: bool,
+    // pinned tabs stay at the front of the tab bar
+    pub pinned: bool,
     pub scroll_offset: f32,
     pub cursor_position: usize,
     pub syntax: Option<SyntaxReference>,
     pub line_numbers: bool,
     pub word_wrap: bool,
+    pub tab_width: usize,
+    pub wrap_column: usize,
     pub selection: Option<(usize, usize)>,
     pub current_line: usize,
     pub current_column: usize,
-    syntax_set: SyntaxSet,
-    theme_set: ThemeSet,
+    // size of the editor scroll area as of the last frame, for the document map
+    pub viewport_height: f32,
+    pub content_height: f32,
+    // cursors besides the text box's own, see cursors.rs
+    pub extra_cursors: Vec<Selection>,
+    // set while the cursors come from an Alt+drag block selection
+    block: Option<Block>,
+    // selection set from code, applied to the text box before it is drawn
+    pending_selection: Option<(usize, usize)>,
+    focus_requested: bool,
+    // fold regions and which of them are collapsed, shared by every pane
+    folding: Folding,
+    bookmarks: Bookmarks,
+    history: History,
+    // the text as of the last change that went through `change_text`, to
+    // find out what the text box changed
+    known_text: String,
+    // changes with every edit; see `next_revision`
+    revision: u64,
+    // the latest edits and the revision before the oldest of them
+    recent_edits: VecDeque<RecentEdit>,
+    edits_since: u64,
+    // fold regions worked out on a worker thread
+    analysis: Background<AnalysisKey, Analysis>,
+    syntax_set: Arc<SyntaxSet>,
+    theme_set: Arc<ThemeSet>,
+}
+
+// Where offsets and lines went in an edit, so that views and results from
+// before it can follow
+struct RecentEdit {
+    // the revision the edit led to
+    revision: u64,
+    offsets: OffsetShift,
+    lines: LineShift,
 }
 
 impl Document {
     pub fn new() -> Self {
+        let revision = next_revision();
         Self {
             path: None,
             content: String::new(),
             filename: "Untitled".to_string(),
             is_modified: false,
+            pinned: false,
             scroll_offset: 0.0,
             cursor_position: 0,
             syntax: None,
             line_numbers: true,
             word_wrap: true,
+            tab_width: 4,
+            wrap_column: 80,
             selection: None,
             current_line: 0,
             current_column: 0,
-            syntax_set: SyntaxSet::load_defaults_newlines(),
-            theme_set: ThemeSet::load_defaults(),
+            viewport_height: 0.0,
+            content_height: 0.0,
+            extra_cursors: Vec::new(),
+            block: None,
+            pending_selection: None,
+            focus_requested: false,
+            folding: Folding::default(),
+            bookmarks: Bookmarks::default(),
+            history: History::default(),
+            known_text: String::new(),
+            revision,
+            recent_edits: VecDeque::new(),
+            edits_since: revision,
+            analysis: Background::new(),
+            syntax_set: Arc::new(SyntaxSet::load_defaults_newlines()),
+            theme_set: Arc::new(ThemeSet::load_defaults()),
         }
     }
     
@@ -53,22 +156,40 @@ impl Document {
         let syntax_set = SyntaxSet::load_defaults_newlines();
         let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
         let syntax = syntax_set.find_syntax_by_extension(extension).cloned();
+        let revision = next_revision();
             
         Ok(Self {
             path: Some(path.to_path_buf()),
+            known_text: content.clone(),
             content,
             filename,
             is_modified: false,
+            pinned: false,
             scroll_offset: 0.0,
             cursor_position: 0,
             syntax,
             line_numbers: true,
             word_wrap: true,
+            tab_width: 4,
+            wrap_column: 80,
             selection: None,
             current_line: 0,
             current_column: 0,
-            syntax_set,
-            theme_set: ThemeSet::load_defaults(),
+            viewport_height: 0.0,
+            content_height: 0.0,
+            extra_cursors: Vec::new(),
+            block: None,
+            pending_selection: None,
+            focus_requested: false,
+            folding: Folding::default(),
+            bookmarks: Bookmarks::default(),
+            history: History::default(),
+            revision,
+            recent_edits: VecDeque::new(),
+            edits_since: revision,
+            analysis: Background::new(),
+            syntax_set: Arc::new(syntax_set),
+            theme_set: Arc::new(ThemeSet::load_defaults()),
         })
     }
     
@@ -86,13 +207,85 @@ impl Document {
         fs::write(path, &self.content)
             .with_context(|| format!("Failed to write to file: {}", path.display()))?;
             
+        self.set_path(path);
+        self.is_modified = false;
+        self.history.mark_saved();
+        
+        Ok(())
+    }
+    
+    // Take over the view and editing settings from the config
+    pub fn apply_config(&mut self, config: &Config) {
+        self.line_numbers = config.line_numbers;
+        self.word_wrap = config.word_wrap;
+        self.tab_width = config.tab_width;
+        self.wrap_column = config.wrap_column;
+    }
+    
+    // 文件被移动或重命名后更新路径和标签名
+    pub fn set_path(&mut self, path: &Path) {
         self.path = Some(path.to_path_buf());
         self.filename = path.file_name()
             .map(|f| f.to_string_lossy().to_string())
             .unwrap_or_else(|| "Untitled".to_string());
-        self.is_modified = false;
+    }
+    
+    pub fn theme_set(&self) -> &Arc<ThemeSet> {
+        &self.theme_set
+    }
+    
+    pub fn view_state(&self) -> ViewState {
+        ViewState {
+            scroll_offset: self.scroll_offset,
+            cursor_position: self.cursor_position,
+            selection: self.selection,
+            current_line: self.current_line,
+            current_column: self.current_column,
+            viewport_height: self.viewport_height,
+            content_height: self.content_height,
+            extra_cursors: self.extra_cursors.clone(),
+            block: self.block,
+            pending_selection: self.pending_selection,
+            focus_requested: self.focus_requested,
+            revision: self.revision,
+        }
+    }
+    
+    // The view's offsets move along with the edits made since it was taken,
+    // e.g. in another pane. Edits too old to remember just keep them in bounds.
+    pub fn set_view_state(&mut self, view: &ViewState) {
+        let edits: Vec<OffsetShift> = self.edits_after(view.revision)
+            .map(|edits| edits.map(|edit| edit.offsets.clone()).collect())
+            .unwrap_or_default();
+        let text = &self.content;
+        let map = |pos: usize| floor_char_boundary(text, edits.iter().fold(pos, |pos, shift| shift.apply(pos)));
         
-        Ok(())
+        self.scroll_offset = view.scroll_offset;
+        self.cursor_position = map(view.cursor_position);
+        self.selection = view.selection.map(|(start, end)| (map(start), map(end)));
+        self.viewport_height = view.viewport_height;
+        self.content_height = view.content_height;
+        self.extra_cursors = view.extra_cursors.iter().map(|sel| Selection::new(map(sel.anchor), map(sel.head))).collect();
+        self.pending_selection = view.pending_selection.map(|(anchor, head)| (map(anchor), map(head)));
+        self.focus_requested = view.focus_requested;
+        if view.revision == self.revision {
+            self.current_line = view.current_line;
+            self.current_column = view.current_column;
+            self.block = view.block;
+        } else {
+            // 块选择的行列在编辑之后不再对得上
+            self.block = None;
+            self.update_line_and_column();
+        }
+    }
+    
+    // Draw the document as seen from another pane
+    pub fn ui_with_view(&mut self, ui: &mut egui::Ui, view: &mut ViewState) {
+        let own = self.view_state();
+        self.set_view_state(view);
+        self.ui(ui);
+        *view = self.view_state();
+        self.set_view_state(&own);
     }
     
     pub fn get_line_count(&self) -> usize {
@@ -109,10 +302,692 @@ impl Document {
         self.scroll_offset = line as f32 * 18.0; // 近似行高
     }
     
+    // 行首的字节偏移，超出范围时返回文本末尾
+    pub fn line_start_offset(&self, line: usize) -> usize {
+        if line == 0 {
+            return 0;
+        }
+        self.content
+            .match_indices('\n')
+            .nth(line - 1)
+            .map(|(pos, _)| pos + 1)
+            .unwrap_or(self.content.len())
+    }
+    
+    pub fn goto_line(&mut self, line: usize) {
+        let line = line.min(self.get_line_count() - 1);
+        let start = self.line_start_offset(line);
+        self.select(start, start);
+        self.scroll_to_line(line);
+    }
+    
+    // 在光标处插入文本，光标移动到插入内容之后
+    pub fn insert_text(&mut self, text: &str) {
+        let pos = floor_char_boundary(&self.content, self.cursor_position);
+        self.apply_splice(Splice::new(pos..pos, text.to_string(), Selection::caret(pos + text.len())));
+    }
+    
+    // Replace the whole text without it counting as an edit, e.g. with
+    // unsaved changes brought back from a closed tab. There is nothing to undo.
+    pub fn set_content(&mut self, content: String, modified: bool) {
+        self.known_text.clone_from(&content);
+        self.content = content;
+        self.revision = next_revision();
+        self.recent_edits.clear();
+        self.edits_since = self.revision;
+        self.bookmarks.clear();
+        self.folding.unfold_all();
+        self.history = History::default();
+        if modified {
+            self.history.forget_saved();
+        }
+        self.is_modified = modified;
+    }
+    
+    // The file on disk is gone or changed: even undoing every edit doesn't
+    // bring the document back to a saved state
+    pub fn mark_modified(&mut self) {
+        self.is_modified = true;
+        self.history.forget_saved();
+    }
+    
+    // Take back the last edit and put the cursors back where they were
+    pub fn undo(&mut self) {
+        let Some(step) = self.history.undo().cloned() else {
+            return;
+        };
+        for changes in step.edits.iter().rev() {
+            self.change_text(&cursors::invert_changes(changes));
+        }
+        self.block = None;
+        self.set_selections(step.before);
+        self.is_modified = self.history.is_modified();
+    }
+    
+    pub fn redo(&mut self) {
+        let Some(step) = self.history.redo().cloned() else {
+            return;
+        };
+        for changes in &step.edits {
+            self.change_text(changes);
+        }
+        self.block = None;
+        self.set_selections(step.after);
+        self.is_modified = self.history.is_modified();
+    }
+    
+    // Select from `anchor` to `head` (byte offsets), the caret ending up at `head`.
+    // Any extra cursors are dropped. The text box picks it up the next time it is drawn.
+    pub fn select(&mut self, anchor: usize, head: usize) {
+        self.clear_extra_cursors();
+        self.set_primary(Selection::new(anchor, head));
+    }
+    
+    // 下一帧让编辑框获得键盘焦点，例如在菜单中执行命令之后
+    pub fn request_focus(&mut self) {
+        self.focus_requested = true;
+    }
+    
+    // Byte range of the line containing `pos`, including its line break
+    pub fn line_range_at(&self, pos: usize) -> Range<usize> {
+        let pos = floor_char_boundary(&self.content, pos);
+        let start = self.content[..pos].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let end = self.content[pos..].find('\n').map(|i| pos + i + 1).unwrap_or(self.content.len());
+        start..end
+    }
+    
+    // The text box's own cursor, which every single-cursor feature works with
+    pub fn primary_selection(&self) -> Selection {
+        match self.selection {
+            Some((start, end)) if self.cursor_position == start => Selection::new(end, start),
+            Some((start, end)) => Selection::new(start, end),
+            None => Selection::caret(self.cursor_position),
+        }
+    }
+    
+    // Primary cursor first, then the extra ones
+    pub fn selections(&self) -> Vec<Selection> {
+        std::iter::once(self.primary_selection())
+            .chain(self.extra_cursors.iter().copied())
+            .collect()
+    }
+    
+    // What Copy puts on the clipboard: the selected text (one line per cursor),
+    // or else the whole lines the carets are on. None for an empty document.
+    pub fn copy_text(&self) -> Option<String> {
+        let mut selections = self.selections();
+        selections.sort_by_key(|sel| sel.range().start);
+        
+        // 块选择保留空行，粘贴时才能还原成矩形
+        if self.block.is_some() {
+            let lines: Vec<&str> = selections.iter().map(|sel| &self.content[sel.range()]).collect();
+            return Some(lines.join("\n"));
+        }
+        if selections.iter().any(|sel| !sel.is_empty()) {
+            let parts: Vec<&str> = selections
+                .iter()
+                .filter(|sel| !sel.is_empty())
+                .map(|sel| &self.content[sel.range()])
+                .collect();
+            return Some(parts.join("\n"));
+        }
+        if self.content.is_empty() {
+            return None;
+        }
+        let mut lines = String::new();
+        let mut last_line = None;
+        for sel in &selections {
+            let range = self.line_range_at(sel.head);
+            if last_line == Some(range.start) {
+                continue;
+            }
+            last_line = Some(range.start);
+            lines.push_str(&self.content[range]);
+            if !lines.ends_with('\n') {
+                lines.push('\n');
+            }
+        }
+        Some(lines)
+    }
+    
+    // Remove the selections, or the current lines if nothing is selected, and
+    // return what was removed the way Copy would have copied it
+    pub fn cut(&mut self) -> Option<String> {
+        let text = self.copy_text()?;
+        let mut selections = self.selections();
+        if self.block.is_none() && selections.iter().all(|sel| sel.is_empty()) {
+            for sel in &mut selections {
+                let mut range = self.line_range_at(sel.head);
+                // 最后一行没有换行符时，连同上一行的换行符一起删除
+                if range.end == self.content.len() && !self.content.ends_with('\n') && range.start > 0 {
+                    range.start -= 1;
+                }
+                *sel = Selection::new(range.start, range.end);
+            }
+        }
+        self.edit_selections(selections, &Edit::Insert(""));
+        Some(text)
+    }
+    
+    // Type `text` over every selection, or insert it at every caret
+    pub fn replace_selection(&mut self, text: &str) {
+        self.edit_selections(self.selections(), &Edit::Insert(text));
+    }
+    
+    // Like `replace_selection`, but a clip with one line per cursor is spread
+    // over the cursors, one line each
+    pub fn paste(&mut self, text: &str) {
+        let lines: Vec<&str> = text.strip_suffix('\n').unwrap_or(text).split('\n').collect();
+        if !self.extra_cursors.is_empty() && lines.len() == self.extra_cursors.len() + 1 {
+            self.edit_selections(self.selections(), &Edit::InsertEach(lines));
+        } else {
+            self.replace_selection(text);
+        }
+    }
+    
+    // Paste text copied from a block selection as a rectangle: one line per
+    // line from the caret down, at the caret's column. Short lines are padded
+    // with spaces and missing lines are added at the end.
+    pub fn paste_block(&mut self, text: &str) {
+        let lines: Vec<&str> = text.split('\n').collect();
+        if self.cursor_count() > 1 && self.cursor_count() == lines.len() {
+            self.edit_selections(self.selections(), &Edit::InsertEach(lines));
+            return;
+        }
+        if self.selections().iter().any(|sel| !sel.is_empty()) {
+            self.replace_selection("");
+        }
+        
+        let (first_line, column) = cursors::line_column(&self.content, self.cursor_position);
+        let starts: Vec<usize> = cursors::line_starts(&self.content).skip(first_line).take(lines.len()).collect();
+        let mut selections = Vec::new();
+        let mut pieces = Vec::new();
+        for (&start, line) in starts.iter().zip(&lines) {
+            let (pos, short) = cursors::column_position(&self.content, start, column);
+            selections.push(Selection::caret(pos));
+            pieces.push(format!("{}{}", " ".repeat(short), line));
+        }
+        if lines.len() > starts.len() {
+            let rest: String = lines[starts.len()..]
+                .iter()
+                .map(|line| format!("\n{}{}", " ".repeat(column), line))
+                .collect();
+            selections.push(Selection::caret(self.content.len()));
+            pieces.push(rest);
+        }
+        
+        let pieces = pieces.iter().map(String::as_str).collect();
+        let (changes, carets) = cursors::edit_changes(&self.content, &selections, &Edit::InsertEach(pieces));
+        if let Some(&last) = carets.last() {
+            self.edit(changes, vec![last], EditKind::Command);
+        }
+    }
+    
+    pub fn is_block_selection(&self) -> bool {
+        self.block.is_some()
+    }
+    
+    // Column Editor: insert one piece of text per line, down the column of the
+    // block selection or of every cursor, or else from the caret to the last line.
+    // `make` is given the number of lines.
+    pub fn insert_column(&mut self, make: impl FnOnce(usize) -> Vec<String>) {
+        let mut targets: Vec<(Selection, usize)> = Vec::new();
+        if let Some(block) = self.block {
+            let lines = block.lines();
+            for start in cursors::line_starts(&self.content).skip(lines.start).take(lines.len()) {
+                let (pos, short) = cursors::column_position(&self.content, start, block.left());
+                targets.push((Selection::caret(pos), short));
+            }
+        } else if !self.extra_cursors.is_empty() {
+            let mut selections = self.selections();
+            selections.sort_by_key(|sel| sel.range().start);
+            targets = selections.into_iter().map(|sel| (Selection::caret(sel.range().start), 0)).collect();
+        } else {
+            let (first_line, column) = cursors::line_column(&self.content, self.cursor_position);
+            for start in cursors::line_starts(&self.content).skip(first_line) {
+                let (pos, short) = cursors::column_position(&self.content, start, column);
+                targets.push((Selection::caret(pos), short));
+            }
+        }
+        
+        let pieces: Vec<String> = make(targets.len())
+            .into_iter()
+            .zip(&targets)
+            .map(|(piece, (_, short))| format!("{}{}", " ".repeat(*short), piece))
+            .collect();
+        let selections = targets.into_iter().map(|(sel, _)| sel).collect();
+        self.edit_selections(selections, &Edit::InsertEach(pieces.iter().map(String::as_str).collect()));
+    }
+    
+    // Ctrl+D: select the word under the caret, then add the next occurrence
+    // of the selected text as a new cursor
+    pub fn add_next_occurrence(&mut self) {
+        let primary = self.primary_selection();
+        if primary.is_empty() {
+            if let Some(word) = cursors::word_at(&self.content, primary.head) {
+                self.set_primary(Selection::new(word.start, word.end));
+            }
+            return;
+        }
+        
+        let needle = &self.content[primary.range()];
+        let taken: Vec<usize> = self.selections().iter().map(|sel| sel.range().start).collect();
+        let free: Vec<usize> = self.content
+            .match_indices(needle)
+            .map(|(pos, _)| pos)
+            .filter(|pos| !taken.contains(pos))
+            .collect();
+        // 从当前位置往后找，到末尾后从头开始
+        let from = primary.range().end;
+        let Some(start) = free.iter().copied().find(|&pos| pos >= from).or(free.first().copied()) else {
+            return;
+        };
+        let end = start + needle.len();
+        self.extra_cursors.push(primary);
+        self.set_primary(Selection::new(start, end));
+    }
+    
+    // Ctrl+Shift+L: a cursor on every occurrence of the selection, or on every
+    // whole-word occurrence of the word under the caret
+    pub fn select_all_occurrences(&mut self) {
+        let primary = self.primary_selection();
+        let whole_word = primary.is_empty();
+        let range = if whole_word {
+            match cursors::word_at(&self.content, primary.head) {
+                Some(word) => word,
+                None => return,
+            }
+        } else {
+            primary.range()
+        };
+        let needle = &self.content[range.clone()];
+        let mut all: Vec<Selection> = self.content
+            .match_indices(needle)
+            .map(|(pos, _)| Selection::new(pos, pos + needle.len()))
+            .filter(|sel| !whole_word || cursors::is_whole_word(&self.content, sel.range()))
+            .collect();
+        // 包含原光标的那一处作为主光标
+        let current = all.iter().position(|sel| sel.range() == range).unwrap_or(0);
+        let primary = all.remove(current);
+        self.extra_cursors = all;
+        self.set_primary(primary);
+    }
+    
+    // A new caret on the line above the topmost one, or below the bottommost
+    pub fn add_cursor_vertically(&mut self, below: bool) {
+        let heads = self.selections().into_iter().map(|sel| sel.head);
+        let edge = if below { heads.max() } else { heads.min() };
+        let Some(edge) = edge else {
+            return;
+        };
+        let target = if below {
+            cursors::line_below(&self.content, edge)
+        } else {
+            cursors::line_above(&self.content, edge)
+        };
+        if let Some(pos) = target {
+            self.extra_cursors.push(Selection::caret(pos));
+        }
+    }
+    
+    pub fn cursor_count(&self) -> usize {
+        self.extra_cursors.len() + 1
+    }
+
+    pub fn bookmarked_lines(&self) -> Vec<usize> {
+        self.bookmarks.lines()
+    }
+
+    pub fn set_bookmarked_lines(&mut self, lines: &[usize]) {
+        self.bookmarks.set_lines(lines);
+        self.bookmarks.truncate(self.content.matches('\n').count() + 1);
+    }
+
+    pub fn has_bookmarks(&self) -> bool {
+        !self.bookmarks.is_empty()
+    }
+
+    pub fn toggle_bookmark(&mut self, line: usize) {
+        self.refresh_lines();
+        self.bookmarks.toggle(line);
+    }
+
+    // F2 / Shift+F2: move to the next or previous bookmark, wrapping around
+    pub fn goto_bookmark(&mut self, forward: bool) {
+        self.refresh_lines();
+        let line = if forward {
+            self.bookmarks.next(self.current_line)
+        } else {
+            self.bookmarks.previous(self.current_line)
+        };
+        if let Some(line) = line {
+            self.goto_line(line);
+        }
+    }
+
+    pub fn clear_bookmarks(&mut self) {
+        self.bookmarks.clear();
+    }
+
+    // Bookmark every line containing `pattern`; returns how many lines matched
+    pub fn bookmark_lines_matching(&mut self, pattern: &str) -> usize {
+        if pattern.is_empty() {
+            return 0;
+        }
+        self.refresh_lines();
+        let mut count = 0;
+        for (line, text) in self.content.lines().enumerate() {
+            if text.contains(pattern) {
+                self.bookmarks.insert(line);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    // The bookmarked lines, each with its line break
+    pub fn bookmarked_text(&self) -> Option<String> {
+        let text: String = self.content
+            .split_inclusive('\n')
+            .enumerate()
+            .filter(|(line, _)| self.bookmarks.contains(*line))
+            .map(|(_, text)| if text.ends_with('\n') { text.to_string() } else { format!("{}\n", text) })
+            .collect();
+        (!text.is_empty()).then_some(text)
+    }
+
+    // Remove the bookmarked lines in one change and return their text
+    pub fn cut_bookmarked_lines(&mut self) -> Option<String> {
+        self.refresh_lines();
+        let text = self.bookmarked_text()?;
+        let pos = self.line_start_offset(self.bookmarks.lines()[0]);
+        let mut changes = Vec::new();
+        let mut start = 0;
+        for (line, text) in self.content.split_inclusive('\n').enumerate() {
+            if self.bookmarks.contains(line) {
+                changes.push(Change::new(&self.content, start..start + text.len(), ""));
+            }
+            start += text.len();
+        }
+        self.edit(changes, vec![Selection::caret(pos)], EditKind::Command);
+        Some(text)
+    }
+
+    // Move the caret to the bracket matching the one next to it
+    pub fn goto_matching_bracket(&mut self) {
+        let caret = floor_char_boundary(&self.content, self.cursor_position);
+        if let Some((bracket, partner)) = self.language_rules().bracket_pair_near(&self.content, caret) {
+            // 光标在括号后面时跳到对应括号的后面
+            let pos = if bracket < caret { partner + 1 } else { partner };
+            self.select(pos, pos);
+        }
+    }
+
+    // Ctrl+/ and Ctrl+Shift+/: comment or uncomment the selected lines, or
+    // wrap the selection in a block comment
+    pub fn toggle_comment(&mut self, block: bool) {
+        let tokens = CommentTokens::for_syntax(self.syntax.as_ref(), &self.syntax_set);
+        let sel = self.primary_selection();
+        let splice = if block {
+            comments::toggle_block_comment(&self.content, sel, &tokens, self.tab_width)
+        } else {
+            comments::toggle_line_comment(&self.content, sel, &tokens, self.tab_width)
+        };
+        if let Some(splice) = splice {
+            self.apply_splice(splice);
+        }
+    }
+
+    // Make the change a command worked out and select what it says
+    pub fn apply_splice(&mut self, splice: Splice) {
+        let mut change = Change::new(&self.content, splice.range, &splice.text);
+        change.line_map = splice.line_map;
+        self.edit(vec![change], vec![splice.selection], EditKind::Command);
+    }
+
+    fn language_rules(&self) -> LanguageRules {
+        LanguageRules::for_syntax(self.syntax.as_ref().map(|s| s.name.as_str()))
+    }
+
+    // Start lines of the collapsed fold regions, for the session
+    pub fn folded_lines(&self) -> Vec<usize> {
+        self.folding.folded_lines()
+    }
+
+    pub fn set_folded_lines(&mut self, lines: &[usize]) {
+        self.folding.set_folded_lines(lines);
+    }
+
+    pub fn toggle_fold(&mut self, line: usize) {
+        self.refresh_lines();
+        self.folding.toggle(line);
+        self.move_caret_out_of_folds();
+    }
+
+    // Fold or unfold the innermost region around the caret
+    pub fn fold_at_caret(&mut self, fold: bool) {
+        self.refresh_lines();
+        if fold {
+            let region = self.folding.regions()
+                .iter()
+                .take_while(|r| r.start <= self.current_line)
+                .filter(|r| (r.start == self.current_line || r.hides(self.current_line)) && !self.folding.is_folded(r.start))
+                .last()
+                .copied();
+            if let Some(region) = region {
+                self.folding.fold(region.start);
+            }
+        } else if let Some(region) = self.folding.innermost_at(self.current_line) {
+            self.folding.unfold(region.start);
+        }
+        self.move_caret_out_of_folds();
+    }
+
+    pub fn fold_all(&mut self) {
+        self.refresh_lines();
+        self.folding.fold_all();
+        self.move_caret_out_of_folds();
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folding.unfold_all();
+    }
+
+    pub fn fold_to_level(&mut self, level: usize) {
+        self.refresh_lines();
+        self.folding.fold_to_level(level);
+        self.move_caret_out_of_folds();
+    }
+
+    // The edits made since `revision`, or None if it is too old to tell
+    fn edits_after(&self, revision: u64) -> Option<impl Iterator<Item = &RecentEdit>> {
+        (revision >= self.edits_since).then(|| self.recent_edits.iter().filter(move |edit| edit.revision > revision))
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    // Whether the text was at `revision` now or after one of the edits still
+    // remembered. Revisions of other documents never are.
+    pub fn had_revision(&self, revision: u64) -> bool {
+        revision == self.revision || revision == self.edits_since || self.recent_edits.iter().any(|edit| edit.revision == revision)
+    }
+
+    // The latest analysis for the current syntax, with its key; it may be
+    // from before the latest edits
+    pub fn analysis(&self) -> Option<(&AnalysisKey, &Analysis)> {
+        let (key, analysis) = self.analysis.get()?;
+        (key.syntax.as_deref() == self.syntax.as_ref().map(|s| s.name.as_str())).then_some((key, analysis))
+    }
+
+    fn analysis_key(&self) -> AnalysisKey {
+        AnalysisKey { revision: self.revision, syntax: self.syntax.as_ref().map(|s| s.name.clone()) }
+    }
+
+    // 文本或语法变了就在后台重新分析
+    fn start_analysis(&mut self, ctx: &egui::Context) {
+        let key = self.analysis_key();
+        if !self.analysis.wants(&key) {
+            return;
+        }
+        let text = self.content.clone();
+        let syntax = self.syntax.clone();
+        let syntax_set = Arc::clone(&self.syntax_set);
+        self.analysis.start(ctx, key, move || analysis::analyze(text, syntax.as_ref(), &syntax_set));
+    }
+
+    // Take over fold regions from a finished analysis. One made before the
+    // latest edits is moved along with them.
+    fn refresh_lines(&mut self) {
+        if !self.analysis.poll() {
+            return;
+        }
+        let Some((key, analysis)) = self.analysis.get() else {
+            return;
+        };
+        if key.syntax.as_deref() != self.syntax.as_ref().map(|s| s.name.as_str()) {
+            return;
+        }
+        let Some(edits) = self.edits_after(key.revision) else {
+            return;
+        };
+        let mut regions = analysis.regions.clone();
+        for edit in edits {
+            regions = folding::shift_regions(&regions, &edit.lines);
+        }
+        self.folding.set_regions(regions);
+    }
+
+    // 光标所在的行被折叠时，移到折叠块的第一行末尾
+    fn move_caret_out_of_folds(&mut self) {
+        if let Some(region) = self.folding.folded_region_hiding(self.current_line) {
+            let pos = cursors::line_end(&self.content, self.line_start_offset(region.start));
+            self.select(pos, pos);
+        }
+    }
+
+    // Arrow keys step over collapsed lines. A caret that lands in them any
+    // other way, by typing, Find or Go to Line, opens the fold instead.
+    fn step_over_folds(&mut self, line_before: usize, edited: bool) {
+        let Some(region) = self.folding.folded_region_hiding(self.current_line) else {
+            return;
+        };
+        let last_line = self.content.matches('\n').count();
+        let target = if edited {
+            None
+        } else if line_before <= region.start && region.end < last_line {
+            Some(region.end + 1)
+        } else if line_before > region.end {
+            Some(region.start)
+        } else {
+            None
+        };
+        match target {
+            Some(line) => {
+                let start = self.line_start_offset(line);
+                let (pos, _) = cursors::column_position(&self.content, start, self.current_column);
+                let anchor = self.primary_selection().anchor;
+                self.set_primary(Selection::new(anchor, pos));
+            }
+            None => self.folding.reveal(self.current_line),
+        }
+    }
+
+    fn edit_selections(&mut self, selections: Vec<Selection>, edit: &Edit) {
+        let (changes, selections) = cursors::edit_changes(&self.content, &selections, edit);
+        self.edit(changes, selections, EditKind::Command);
+    }
+    
+    // Typing or deleting at every cursor
+    fn type_at_cursors(&mut self, edit: &Edit) {
+        let (changes, selections) = cursors::edit_changes(&self.content, &self.selections(), edit);
+        self.edit(changes, selections, EditKind::Typing);
+    }
+    
+    // Make `changes`, put the cursors at `selections` and remember it all as
+    // a step to undo
+    fn edit(&mut self, changes: Vec<Change>, selections: Vec<Selection>, kind: EditKind) {
+        let before = self.selections();
+        self.change_text(&changes);
+        // 编辑之后仍保留多个光标，但不再是矩形
+        self.block = None;
+        self.set_selections(selections);
+        if !changes.is_empty() {
+            self.history.record(changes, before, self.selections(), kind);
+        }
+    }
+    
+    fn change_text(&mut self, changes: &[Change]) {
+        cursors::apply_changes(&mut self.content, changes);
+        self.text_changed(changes);
+    }
+    
+    // Every change to the text ends up here, the text box's own once it has
+    // made them, so that bookmarks and collapsed folds stay on their lines.
+    // `changes` turned `known_text` into `content`.
+    fn text_changed(&mut self, changes: &[Change]) {
+        if changes.is_empty() {
+            return;
+        }
+        let shift = LineShift::new(&self.known_text, changes);
+        self.bookmarks.shift(&shift);
+        self.folding.shift(&shift);
+        self.known_text.clone_from(&self.content);
+        
+        self.revision = next_revision();
+        self.recent_edits.push_back(RecentEdit { revision: self.revision, offsets: OffsetShift::new(changes), lines: shift });
+        if self.recent_edits.len() > MAX_RECENT_EDITS {
+            if let Some(edit) = self.recent_edits.pop_front() {
+                self.edits_since = edit.revision;
+            }
+        }
+        self.is_modified = true;
+    }
+    
+    // First selection becomes the primary cursor; carets that ended up on top
+    // of each other are merged
+    fn set_selections(&mut self, mut selections: Vec<Selection>) {
+        if selections.is_empty() {
+            return;
+        }
+        let primary = selections.remove(0);
+        let mut extra: Vec<Selection> = Vec::new();
+        for sel in selections {
+            if sel != primary && !extra.contains(&sel) {
+                extra.push(sel);
+            }
+        }
+        self.extra_cursors = extra;
+        self.set_primary(primary);
+    }
+    
+    fn clear_extra_cursors(&mut self) {
+        self.extra_cursors.clear();
+        self.block = None;
+    }
+    
+    fn set_block(&mut self, block: Block) {
+        self.set_selections(block.selections(&self.content));
+        self.block = Some(block);
+    }
+    
+    fn set_primary(&mut self, sel: Selection) {
+        let anchor = floor_char_boundary(&self.content, sel.anchor);
+        let head = floor_char_boundary(&self.content, sel.head);
+        self.cursor_position = head;
+        self.selection = (anchor != head).then(|| (anchor.min(head), anchor.max(head)));
+        self.pending_selection = Some((anchor, head));
+        self.update_line_and_column();
+    }
+    
     pub fn ui(&mut self, ui: &mut egui::Ui) {
         let _panel_width = ui.available_width();
         let _panel_height = ui.available_height();
         
+        self.start_analysis(ui.ctx());
+        self.refresh_lines();
+        
         // 创建滚动区域以支持垂直滚动
         let mut scroll_area = egui::ScrollArea::vertical()
             .id_source("editor_scroll")
@@ -124,60 +999,53 @@ impl Document {
         scroll_area = scroll_area.scroll_offset(offset);
         
         // 显示滚动区域内容
-        scroll_area.show(ui, |ui| {
+        let output = scroll_area.show(ui, |ui| {
             let avail_width = ui.available_width();
             let _start_rect = ui.min_rect();
             
             ui.horizontal_top(|ui| {
-                // 如果启用了行号，在左侧添加行号面板
-                if self.line_numbers {
-                    let line_count = self.get_line_count();
-                    let digit_count = (line_count as f32).log10().floor() as usize + 1;
-                    let line_number_width = digit_count as f32 * 10.0 + 16.0;
-                    
-                    ui.vertical(|ui| {
-                        ui.set_min_width(line_number_width);
-                        ui.style_mut().override_text_style = Some(egui::TextStyle::Monospace);
-                        
-                        let _line_height = ui.text_style_height(&egui::TextStyle::Monospace);
-                        let lines = self.content.lines().count().max(1);
-                        
-                        for line_number in 1..=lines {
-                            let text = format!("{:>width$}", line_number, width = digit_count);
-                            let is_current_line = line_number - 1 == self.current_line;
-                            
-                            if is_current_line {
-                                ui.label(egui::RichText::new(text).strong().color(ui.visuals().strong_text_color()));
-                            } else {
-                                ui.label(egui::RichText::new(text).weak().color(ui.visuals().weak_text_color()));
-                            }
-                        }
-                        
-                        // 如果内容为空，至少显示一个行号
-                        if lines == 0 {
-                            ui.label("1");
+                // 左侧的行号栏，文本排版之后按排版结果绘制
+                let font = egui::FontId::monospace(14.0);
+                let gutter = Gutter::new(ui, font.clone(), self.content.matches('\n').count() + 1, self.line_numbers);
+                let (gutter_rect, _) = ui.allocate_exact_size(egui::vec2(gutter.width(), 0.0), egui::Sense::hover());
+                
+                // 主要文本编辑区域
+                let text_edit_width = avail_width - gutter.width() - ui.spacing().item_spacing.x;
+                
+                let text_edit_id = ui.make_persistent_id("editor_text");
+                
+                let len = self.content.len();
+                self.extra_cursors.retain(|sel| sel.anchor.max(sel.head) <= len);
+                if ui.memory(|m| m.has_focus(text_edit_id)) {
+                    self.handle_clipboard_events(ui);
+                    self.handle_block_keys(ui);
+                    if !self.extra_cursors.is_empty() || self.block.is_some() {
+                        // 全选之后额外的光标就不再有意义
+                        if ui.input(|i| i.modifiers.command && i.key_pressed(egui::Key::A)) {
+                            self.clear_extra_cursors();
                         }
-                    });
-                    
-                    // 在行号和内容之间添加分隔线
-                    let line_pos = ui.cursor().min.x;
-                    let top = ui.min_rect().top();
-                    let bottom = top + (self.get_line_count() as f32) * ui.text_style_height(&egui::TextStyle::Monospace);
-                    
-                    ui.painter().line_segment(
-                        [egui::pos2(line_pos, top), egui::pos2(line_pos, bottom)],
-                        ui.visuals().widgets.noninteractive.bg_stroke,
-                    );
+                        self.handle_multi_cursor_events(ui);
+                    } else {
+                        self.handle_typing_aids(ui);
+                    }
                 }
+                self.apply_pending_selection(ui.ctx(), text_edit_id);
+                let selections_before = self.selections();
+                let primary_before = selections_before[0];
+                let line_before = self.current_line;
                 
-                // 主要文本编辑区域
-                let text_edit_width = if self.line_numbers {
-                    avail_width - 30.0 // 减去行号宽度和内边距
-                } else {
-                    avail_width
+                // 折叠的行用零高度排版，没有折叠时使用默认排版
+                let hidden = if self.folding.has_folds() { self.folding.hidden_bytes(&self.content) } else { Vec::new() };
+                let fold_ends: Vec<usize> = hidden.iter().map(|range| range.start).collect();
+                let text_color = ui.visuals().override_text_color.unwrap_or_else(|| ui.visuals().widgets.inactive.text_color());
+                let layout_font = font.clone();
+                let mut layouter = move |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                    let job = folding::layout_job(text, &hidden, layout_font.clone(), text_color, wrap_width);
+                    ui.fonts(|f| f.layout_job(job))
                 };
                 
                 let mut text_edit = egui::TextEdit::multiline(&mut self.content)
+                    .id(text_edit_id)
                     .desired_width(text_edit_width)
                     .desired_rows(30)
                     .lock_focus(true)
@@ -187,37 +1055,71 @@ impl Document {
                     .interactive(true);
                 
                 // 使用固定宽度字体，但支持中日韩文字
-                text_edit = text_edit.font(egui::FontId::monospace(14.0));
+                text_edit = text_edit.font(font);
                 
                 // 单词换行设置
                 if !self.word_wrap {
                     text_edit = text_edit.desired_width(f32::INFINITY);
                 }
                 
-                let response = ui.add(text_edit);
+                if !fold_ends.is_empty() {
+                    text_edit = text_edit.layouter(&mut layouter);
+                }
+                
+                let output = text_edit.show(ui);
                 
-                if response.changed() {
-                    self.is_modified = true;
-                    
-                    // 当文本改变时更新光标位置和行列（支持UTF-8多字节字符）
-                    self.update_cursor_position_from_content();
+                let change = output.response.changed().then(|| cursors::diff(&self.known_text, &self.content)).flatten();
+                if let Some(change) = &change {
+                    self.text_changed(std::slice::from_ref(change));
                 }
                 
-                // 处理文本选中情况
-                if response.has_focus() {
-                    // 简化光标位置更新和选择逻辑
-                    if response.clicked() || response.dragged() {
-                        // 当点击或拖动时，更新光标位置
-                        self.update_cursor_position_from_content();
-                        
-                        // 简单模拟选择：当拖动时选择最后几个字符
-                        if response.dragged() && self.cursor_position > 0 {
-                            let start = if self.cursor_position > 10 { self.cursor_position - 10 } else { 0 };
-                            self.selection = Some((start, self.cursor_position));
-                        } else {
-                            self.selection = None;
-                        }
-                    }
+                // Alt+click 添加光标，Alt+拖动选择矩形块，普通点击取消多光标
+                let response = &output.response;
+                let alt = ui.input(|i| i.modifiers.alt);
+                let alt_click = response.clicked() && alt;
+                if (response.clicked() || response.drag_started()) && !alt {
+                    self.clear_extra_cursors();
+                }
+                let block_drag = alt && (response.drag_started() || response.dragged() && self.block.is_some());
+                
+                // 从编辑框同步光标和选区（字符索引转换为字节偏移）
+                if let Some(range) = output.state.cursor.char_range() {
+                    let [start, end] = range.sorted();
+                    let start = byte_offset(&self.content, start.index);
+                    let end = byte_offset(&self.content, end.index);
+                    self.cursor_position = byte_offset(&self.content, range.primary.index);
+                    self.selection = (start != end).then_some((start, end));
+                    self.update_line_and_column();
+                    self.step_over_folds(line_before, output.response.changed());
+                }
+                if let Some(change) = change {
+                    self.history.record(vec![change], selections_before, self.selections(), EditKind::Typing);
+                }
+                if alt_click && primary_before != self.primary_selection() && !self.extra_cursors.contains(&primary_before) {
+                    self.extra_cursors.push(primary_before);
+                }
+                if block_drag {
+                    self.drag_block(ui, &output.galley, output.galley_pos);
+                }
+                
+                if !self.extra_cursors.is_empty() {
+                    self.paint_extra_cursors(ui, &output.galley, output.galley_pos);
+                }
+                self.paint_fold_ellipses(ui, &output.galley, output.galley_pos, &fold_ends);
+                if self.selection.is_none() {
+                    self.paint_bracket_match(ui, &output.galley, output.galley_pos);
+                }
+                
+                let gutter_rect = egui::Rect::from_x_y_ranges(gutter_rect.x_range(), output.response.rect.y_range());
+                let (folds, bookmarks) = (&self.folding, &self.bookmarks);
+                let action = gutter.show(ui, gutter_rect, &output.galley, output.galley_pos, self.current_line, |line| LineInfo {
+                    fold: folds.region_at(line).map(|_| folds.is_folded(line)),
+                    bookmark: bookmarks.contains(line),
+                });
+                match action {
+                    Some(GutterAction::ToggleFold(line)) => self.toggle_fold(line),
+                    Some(GutterAction::ToggleBookmark(line)) => self.toggle_bookmark(line),
+                    None => {}
                 }
             });
             
@@ -234,6 +1136,8 @@ impl Document {
                 self.scroll_offset = self.scroll_offset.min(max_offset);
             }
         });
+        self.viewport_height = output.inner_rect.height();
+        self.content_height = output.content_size.y;
         
         // 在编辑器底部显示状态栏
         ui.horizontal(|ui| {
@@ -243,6 +1147,10 @@ impl Document {
                 ui.label(format!("Sel: {} chars", end - start));
             }
             
+            if !self.extra_cursors.is_empty() {
+                ui.label(format!("{} cursors", self.cursor_count()));
+            }
+            
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                 if let Some(syntax) = &self.syntax {
                     ui.label(format!("Syntax: {}", syntax.name));
@@ -262,42 +1170,549 @@ impl Document {
         });
     }
     
-    // 新增方法：更新光标位置并考虑UTF-8多字节字符
-    fn update_cursor_position_from_content(&mut self) {
-        let cursor_pos = self.content.len();
-        self.cursor_position = cursor_pos;
+    // Copy and Cut go through `copy_text`/`cut`: egui would take the whole
+    // text when nothing is selected, and knows nothing of extra cursors
+    fn handle_clipboard_events(&mut self, ui: &egui::Ui) {
+        let (copy, cut) = ui.input_mut(|i| {
+            let copy = i.events.contains(&egui::Event::Copy);
+            let cut = i.events.contains(&egui::Event::Cut);
+            i.events.retain(|event| !matches!(event, egui::Event::Copy | egui::Event::Cut));
+            (copy, cut)
+        });
+        let block = self.is_block_selection();
+        let text = if cut {
+            self.cut()
+        } else if copy {
+            self.copy_text()
+        } else {
+            None
+        };
+        if let Some(text) = text {
+            clipboard::remember_block_copy(ui.ctx(), block.then_some(text.as_str()));
+            clipboard::copy(ui.ctx(), text);
+        }
         
-        // 计算当前行和列，考虑UTF-8多字节字符
-        let text_before_cursor = &self.content[..cursor_pos];
-        self.current_line = text_before_cursor.chars().filter(|&c| c == '\n').count();
+        let block_copy = clipboard::block_copy(ui.ctx());
+        let block_paste = ui.input_mut(|i| {
+            let index = i.events.iter().position(|event| {
+                matches!(event, egui::Event::Paste(text) if Some(text) == block_copy.as_ref())
+            })?;
+            match i.events.remove(index) {
+                egui::Event::Paste(text) => Some(text),
+                _ => None,
+            }
+        });
+        if let Some(text) = block_paste {
+            self.paste_block(&text);
+        }
+    }
+    
+    // Alt+Shift+arrows grow a block selection from the caret
+    fn handle_block_keys(&mut self, ui: &egui::Ui) {
+        let mut moves = Vec::new();
+        ui.input_mut(|i| {
+            for key in [egui::Key::ArrowUp, egui::Key::ArrowDown, egui::Key::ArrowLeft, egui::Key::ArrowRight] {
+                while i.consume_key(egui::Modifiers::ALT | egui::Modifiers::SHIFT, key) {
+                    moves.push(key);
+                }
+            }
+        });
+        if moves.is_empty() {
+            return;
+        }
         
-        let last_newline_pos = text_before_cursor.rfind('\n');
+        let mut block = self.block.unwrap_or_else(|| {
+            let corner = cursors::line_column(&self.content, self.cursor_position);
+            Block::new(corner, corner)
+        });
+        let last_line = self.get_line_count().saturating_sub(1);
+        for key in moves {
+            let (line, column) = &mut block.head;
+            match key {
+                egui::Key::ArrowUp => *line = line.saturating_sub(1),
+                egui::Key::ArrowDown => *line = (*line + 1).min(last_line),
+                egui::Key::ArrowLeft => *column = column.saturating_sub(1),
+                _ => *column += 1,
+            }
+        }
+        self.set_block(block);
+    }
+    
+    // Alt+drag: the block spans from where the drag started to the pointer.
+    // Past the end of a line the column is counted in space widths of the
+    // editor's font. Block columns are character columns, so a tab counts as
+    // one column however wide it is drawn.
+    fn drag_block(&mut self, ui: &egui::Ui, galley: &egui::Galley, origin: egui::Pos2) {
+        let Some((press, pointer)) = ui.input(|i| Some((i.pointer.press_origin()?, i.pointer.interact_pos()?))) else {
+            return;
+        };
+        let font = galley
+            .job
+            .sections
+            .first()
+            .map(|section| section.format.font_id.clone())
+            .unwrap_or_else(|| egui::TextStyle::Monospace.resolve(ui.style()));
+        let space_width = ui.fonts(|f| f.glyph_width(&font, ' ')).max(1.0);
+        let corner = |pos: egui::Pos2| {
+            let cursor = galley.cursor_from_pos(pos - origin);
+            let mut column = cursor.pcursor.offset;
+            if let Some(row) = galley.rows.get(cursor.rcursor.row) {
+                let beyond = pos.x - (origin.x + row.rect.right());
+                if beyond > 0.0 {
+                    column += (beyond / space_width).round() as usize;
+                }
+            }
+            (cursor.pcursor.paragraph, column)
+        };
+        self.set_block(Block::new(corner(press), corner(pointer)));
+    }
+    
+    // With extra cursors the text box only sees the events it can not get
+    // wrong; typing, deleting, pasting and caret movement happen here at every
+    // cursor. Typing and deleting undo like single-cursor typing; a paste is a
+    // step of its own.
+    fn handle_multi_cursor_events(&mut self, ui: &egui::Ui) {
+        let events = ui.input_mut(|i| {
+            let (ours, theirs) = std::mem::take(&mut i.events).into_iter().partition(is_multi_cursor_event);
+            i.events = theirs;
+            ours
+        });
         
-        // 计算列位置（需要按字符计算而非字节）
-        if let Some(pos) = last_newline_pos {
-            // 有换行符，计算最后一行的列位置
-            let last_line_text = &text_before_cursor[(pos + 1)..];
-            self.current_column = last_line_text.chars().count();
-        } else {
-            // 没有换行符，整个文本就是一行
-            self.current_column = text_before_cursor.chars().count();
+        for event in events {
+            match event {
+                egui::Event::Text(text) => self.type_at_cursors(&Edit::Insert(&text)),
+                egui::Event::Paste(text) => self.paste(&text),
+                egui::Event::Key { key, modifiers, .. } => {
+                    let word = modifiers.command || modifiers.alt;
+                    let motion = match key {
+                        egui::Key::Escape => {
+                            self.clear_extra_cursors();
+                            return;
+                        }
+                        egui::Key::Enter => {
+                            self.type_at_cursors(&Edit::Insert("\n"));
+                            continue;
+                        }
+                        egui::Key::Tab => {
+                            self.type_at_cursors(&Edit::Insert("\t"));
+                            continue;
+                        }
+                        egui::Key::Backspace => {
+                            self.type_at_cursors(&Edit::DeleteBackward { word });
+                            continue;
+                        }
+                        egui::Key::Delete => {
+                            self.type_at_cursors(&Edit::DeleteForward { word });
+                            continue;
+                        }
+                        egui::Key::ArrowLeft if word => Motion::WordLeft,
+                        egui::Key::ArrowRight if word => Motion::WordRight,
+                        egui::Key::ArrowLeft => Motion::Left,
+                        egui::Key::ArrowRight => Motion::Right,
+                        egui::Key::ArrowUp => Motion::Up,
+                        egui::Key::ArrowDown => Motion::Down,
+                        egui::Key::Home => Motion::LineStart,
+                        egui::Key::End => Motion::LineEnd,
+                        _ => continue,
+                    };
+                    let moved = self.selections()
+                        .into_iter()
+                        .map(|sel| cursors::move_selection(&self.content, sel, motion, modifiers.shift))
+                        .collect();
+                    self.set_selections(moved);
+                }
+                _ => {}
+            }
+        }
+    }
+    
+    // Auto-closing pairs, indentation on Enter and aligning a closing bracket
+    // typed at the start of a line, for a single caret. Once the text box has
+    // to handle an event, the ones after it are left to it too so that
+    // everything happens in order.
+    fn handle_typing_aids(&mut self, ui: &egui::Ui) {
+        let rules = self.language_rules();
+        let events = ui.input_mut(|i| std::mem::take(&mut i.events));
+        let mut rest: Vec<egui::Event> = Vec::new();
+        for event in events {
+            let handled = !rest.iter().any(is_text_box_edit) && match &event {
+                egui::Event::Text(text) => {
+                    let mut chars = text.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => self.type_char(&rules, c),
+                        _ => false,
+                    }
+                }
+                egui::Event::Key { key: egui::Key::Enter, pressed: true, modifiers, .. } if !modifiers.command && !modifiers.alt => {
+                    self.newline_and_indent(&rules);
+                    true
+                }
+                egui::Event::Key { key: egui::Key::Backspace, pressed: true, modifiers, .. } if modifiers.is_none() => {
+                    self.delete_empty_pair(&rules)
+                }
+                _ => false,
+            };
+            if !handled {
+                rest.push(event);
+            }
+        }
+        ui.input_mut(|i| i.events = rest);
+    }
+    
+    // Returns false when the character should just be inserted as usual
+    fn type_char(&mut self, rules: &LanguageRules, c: char) -> bool {
+        let sel = self.primary_selection();
+        let range = sel.range();
+        let next = self.content[range.end..].chars().next();
+        let prev = self.content[..range.start].chars().next_back();
+        
+        // 输入的右括号或引号正好在光标后面时直接跳过
+        if sel.is_empty() && rules.is_closing(c) && next == Some(c) {
+            let pos = range.end + c.len_utf8();
+            self.select(pos, pos);
+            return true;
+        }
+        
+        if let Some(close) = rules.closing(c) {
+            if !sel.is_empty() {
+                // 用括号或引号包住选中的文本，保持选中
+                let wrapped = format!("{}{}{}", c, &self.content[range.clone()], close);
+                self.splice(range, &wrapped, Selection::new(sel.anchor + c.len_utf8(), sel.head + c.len_utf8()));
+                return true;
+            }
+            // 只在后面没有紧跟文字时补全；引号前面是字母时多半是撇号
+            let next_free = match next {
+                Some(n) => n.is_whitespace() || rules.is_closing_bracket(n) || matches!(n, ',' | ';'),
+                None => true,
+            };
+            let prev_free = match prev {
+                Some(p) if c == close => !p.is_alphanumeric() && p != '_' && p != c,
+                _ => true,
+            };
+            if !(next_free && prev_free) {
+                return false;
+            }
+            let pos = range.start + c.len_utf8();
+            self.splice(range, &format!("{}{}", c, close), Selection::caret(pos));
+            return true;
+        }
+        
+        // 行首输入右括号时与对应左括号所在行对齐
+        if sel.is_empty() && rules.is_closing_bracket(c) {
+            let line_start = cursors::line_start(&self.content, range.start);
+            if !self.content[line_start..range.start].trim().is_empty() {
+                return false;
+            }
+            let Some(open) = rules.unclosed_bracket(&self.content, line_start, c) else {
+                return false;
+            };
+            let indent = language::line_indent(&self.content, cursors::line_start(&self.content, open)).to_string();
+            let pos = line_start + indent.len() + c.len_utf8();
+            self.splice(line_start..range.start, &format!("{}{}", indent, c), Selection::caret(pos));
+            return true;
+        }
+        false
+    }
+    
+    // Enter keeps the indentation of the line and adds a level after an
+    // opening bracket (or `:` in Python); between a pair of brackets the
+    // closing one moves to a line of its own
+    fn newline_and_indent(&mut self, rules: &LanguageRules) {
+        let range = self.primary_selection().range();
+        let line_start = cursors::line_start(&self.content, range.start);
+        let indent = language::line_indent(&self.content, line_start);
+        let indent = indent[..indent.len().min(range.start - line_start)].to_string();
+        let unit = language::indent_unit(&indent);
+        let before = self.content[line_start..range.start].trim_end().chars().next_back();
+        let after = self.content[range.end..].chars().next();
+        let opens = before.is_some_and(|c| rules.indent_after.contains(&c));
+        
+        let text = match (before, after) {
+            (Some(open), Some(close)) if opens && rules.brackets.contains(&(open, close)) => {
+                format!("\n{}{}\n{}", indent, unit, indent)
+            }
+            _ if opens => format!("\n{}{}", indent, unit),
+            _ => format!("\n{}", indent),
+        };
+        let caret = range.start + 1 + indent.len() + if opens { unit.len() } else { 0 };
+        self.splice(range, &text, Selection::caret(caret));
+    }
+    
+    // Backspace between an empty pair like `(|)` removes both halves
+    fn delete_empty_pair(&mut self, rules: &LanguageRules) -> bool {
+        let sel = self.primary_selection();
+        if !sel.is_empty() {
+            return false;
+        }
+        let Some(prev) = self.content[..sel.head].chars().next_back() else {
+            return false;
+        };
+        let next = self.content[sel.head..].chars().next();
+        if next.is_none() || rules.closing(prev) != next {
+            return false;
+        }
+        let start = sel.head - prev.len_utf8();
+        self.splice(start..sel.head + prev.len_utf8(), "", Selection::caret(start));
+        true
+    }
+    
+    // Typing aids undo like the typing they stand in for
+    fn splice(&mut self, range: Range<usize>, text: &str, selection: Selection) {
+        self.edit(vec![Change::new(&self.content, range, text)], vec![selection], EditKind::Typing);
+    }
+    
+    // Outline the bracket next to the caret and its partner
+    fn paint_bracket_match(&self, ui: &egui::Ui, galley: &egui::Galley, origin: egui::Pos2) {
+        let caret = floor_char_boundary(&self.content, self.cursor_position);
+        let Some((bracket, partner)) = self.language_rules().bracket_pair_near(&self.content, caret) else {
+            return;
+        };
+        let stroke = egui::Stroke::new(1.0, ui.visuals().weak_text_color());
+        for pos in [bracket, partner] {
+            let index = char_index(&self.content, pos);
+            let start = galley.from_ccursor(CCursor::new(index));
+            let end = galley.from_ccursor(CCursor::new(index + 1));
+            let left = galley.pos_from_cursor(&start);
+            let right = if end.rcursor.row == start.rcursor.row {
+                galley.pos_from_cursor(&end).left()
+            } else {
+                left.left() + left.height() / 2.0
+            };
+            let rect = egui::Rect::from_x_y_ranges(left.left()..=right, left.y_range()).translate(origin.to_vec2());
+            ui.painter().rect_stroke(rect, 2.0, stroke);
+        }
+    }
+    
+    // Extra carets and selections, drawn over the text box's own
+    fn paint_extra_cursors(&self, ui: &egui::Ui, galley: &egui::Galley, origin: egui::Pos2) {
+        let painter = ui.painter();
+        let visuals = ui.visuals();
+        let fill = visuals.selection.bg_fill.gamma_multiply(0.6);
+        let to_screen = |index: usize| {
+            let cursor = galley.from_ccursor(CCursor::new(char_index(&self.content, index)));
+            (cursor.rcursor.row, galley.pos_from_cursor(&cursor).translate(origin.to_vec2()))
+        };
+        
+        for sel in &self.extra_cursors {
+            if !sel.is_empty() {
+                let range = sel.range();
+                let (first_row, start) = to_screen(range.start);
+                let (last_row, end) = to_screen(range.end);
+                for row in first_row..=last_row {
+                    let Some(row_rect) = galley.rows.get(row).map(|r| r.rect.translate(origin.to_vec2())) else {
+                        continue;
+                    };
+                    let left = if row == first_row { start.left() } else { row_rect.left() };
+                    let right = if row == last_row { end.left() } else { row_rect.right() };
+                    let rect = egui::Rect::from_x_y_ranges(left..=right, row_rect.y_range());
+                    painter.rect_filled(rect, 0.0, fill);
+                }
+            }
+            let (_, caret) = to_screen(sel.head);
+            painter.line_segment([caret.center_top(), caret.center_bottom()], visuals.text_cursor);
+        }
+    }
+    
+    // A "…" after the first line of each collapsed region
+    fn paint_fold_ellipses(&self, ui: &egui::Ui, galley: &egui::Galley, origin: egui::Pos2, positions: &[usize]) {
+        let painter = ui.painter();
+        let font = egui::FontId::monospace(12.0);
+        for &pos in positions {
+            let cursor = galley.from_ccursor(CCursor::new(char_index(&self.content, pos)));
+            let caret = galley.pos_from_cursor(&cursor).translate(origin.to_vec2());
+            let rect = egui::Rect::from_min_size(caret.left_top() + egui::vec2(6.0, 1.0), egui::vec2(20.0, caret.height() - 2.0));
+            painter.rect_filled(rect, 3.0, ui.visuals().faint_bg_color);
+            painter.text(rect.center(), egui::Align2::CENTER_CENTER, "…", font.clone(), ui.visuals().weak_text_color());
+        }
+    }
+    
+    fn apply_pending_selection(&mut self, ctx: &egui::Context, id: egui::Id) {
+        if let Some((anchor, head)) = self.pending_selection.take() {
+            let mut state = egui::TextEdit::load_state(ctx, id).unwrap_or_default();
+            let anchor = CCursor::new(char_index(&self.content, anchor));
+            let head = CCursor::new(char_index(&self.content, head));
+            state.cursor.set_char_range(Some(CCursorRange::two(anchor, head)));
+            egui::TextEdit::store_state(ctx, id, state);
+        }
+        if std::mem::take(&mut self.focus_requested) {
+            ctx.memory_mut(|m| m.request_focus(id));
         }
     }
     
+    // 根据光标的字节偏移计算行和列（列按字符计算）
+    fn update_line_and_column(&mut self) {
+        let cursor_pos = floor_char_boundary(&self.content, self.cursor_position);
+        let text_before_cursor = &self.content[..cursor_pos];
+        self.current_line = text_before_cursor.matches('\n').count();
+        
+        let line_start = text_before_cursor.rfind('\n').map(|pos| pos + 1).unwrap_or(0);
+        self.current_column = text_before_cursor[line_start..].chars().count();
+    }
+    
     // 计算文本的宽度（以字符数为单位，而非字节数）
     pub fn text_width(&self, text: &str) -> usize {
         text.chars().count()
     }
 }
 
+/// Commands that need nothing but the document they act on. The main window
+/// and extra windows both run them through here; returns false for any
+/// other command.
+pub fn execute(ctx: &egui::Context, doc: &mut Document, cmd: Command) -> bool {
+    match cmd {
+        Command::Cut | Command::Copy | Command::Paste => clipboard::execute(ctx, doc, cmd),
+        Command::AddNextOccurrence | Command::SelectAllOccurrences
+        | Command::AddCursorAbove | Command::AddCursorBelow => cursors::execute(doc, cmd),
+        Command::Undo => {
+            doc.undo();
+            doc.request_focus();
+        }
+        Command::Redo => {
+            doc.redo();
+            doc.request_focus();
+        }
+        Command::GotoMatchingBracket => {
+            doc.goto_matching_bracket();
+            doc.request_focus();
+        }
+        Command::DuplicateLine | Command::MoveLineUp | Command::MoveLineDown
+        | Command::DeleteLine | Command::JoinLines | Command::SortLinesAscending
+        | Command::SortLinesDescending | Command::SortLinesIgnoreCaseAscending
+        | Command::SortLinesIgnoreCaseDescending | Command::SortLinesNumericAscending
+        | Command::SortLinesNumericDescending | Command::SortLinesNaturalAscending
+        | Command::SortLinesNaturalDescending | Command::RemoveDuplicateLines
+        | Command::RemoveEmptyLines | Command::ReverseLines => line_ops::execute(doc, cmd),
+        Command::UpperCase | Command::LowerCase | Command::TitleCase | Command::SentenceCase
+        | Command::InvertCase | Command::SnakeCase | Command::CamelCase | Command::KebabCase
+        | Command::TrimTrailingWhitespace | Command::TrimLeadingWhitespace
+        | Command::TabsToSpaces | Command::SpacesToTabs | Command::HardWrap
+        | Command::ReflowParagraphs => convert::execute(doc, cmd),
+        Command::ToggleLineComment | Command::ToggleBlockComment => comments::execute(doc, cmd),
+        Command::ToggleBookmark | Command::NextBookmark | Command::PreviousBookmark
+        | Command::ClearBookmarks | Command::CopyBookmarkedLines
+        | Command::CutBookmarkedLines | Command::DeleteBookmarkedLines => bookmarks::execute(ctx, doc, cmd),
+        Command::Fold | Command::Unfold | Command::FoldAll | Command::UnfoldAll
+        | Command::FoldLevel1 | Command::FoldLevel2 | Command::FoldLevel3
+        | Command::FoldLevel4 | Command::FoldLevel5 => folding::execute(doc, cmd),
+        _ => return false,
+    }
+    true
+}
+
+// Events the editor applies itself at every cursor
+fn is_multi_cursor_event(event: &egui::Event) -> bool {
+    use egui::Key;
+    match event {
+        egui::Event::Text(_) | egui::Event::Paste(_) => true,
+        egui::Event::Key { key, pressed: true, modifiers, .. } => match key {
+            Key::Backspace | Key::Delete | Key::ArrowLeft | Key::ArrowRight => true,
+            Key::Tab => !modifiers.command && !modifiers.shift,
+            Key::Enter | Key::Escape | Key::ArrowUp | Key::ArrowDown | Key::Home | Key::End => !modifiers.command,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+// Events that change the text or move the caret inside the text box
+fn is_text_box_edit(event: &egui::Event) -> bool {
+    use egui::Key;
+    match event {
+        egui::Event::Text(_) | egui::Event::Paste(_) | egui::Event::Cut => true,
+        egui::Event::Key { key, pressed: true, modifiers, .. } => modifiers.command || matches!(
+            key,
+            Key::Enter | Key::Tab | Key::Backspace | Key::Delete | Key::ArrowLeft | Key::ArrowRight
+            | Key::ArrowUp | Key::ArrowDown | Key::Home | Key::End | Key::PageUp | Key::PageDown
+        ),
+        _ => false,
+    }
+}
+
+// Byte offset of the `index`-th character, or the end of the text
+fn byte_offset(text: &str, index: usize) -> usize {
+    text.char_indices().nth(index).map(|(pos, _)| pos).unwrap_or(text.len())
+}
+
+fn char_index(text: &str, offset: usize) -> usize {
+    text[..floor_char_boundary(text, offset)].chars().count()
+}
+
+// 把可能落在多字节字符中间的偏移向前调整到字符边界
+fn floor_char_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+// 最多保留的已关闭标签数
+const MAX_CLOSED_DOCUMENTS: usize = 20;
+
+// What is needed to bring a closed tab back
+struct ClosedDocument {
+    index: usize,
+    path: Option<PathBuf>,
+    filename: String,
+    // kept for untitled documents and unsaved changes; saved files are read again
+    content: Option<String>,
+    pinned: bool,
+    view: ViewState,
+}
+
+impl ClosedDocument {
+    fn from_document(index: usize, doc: Document) -> Self {
+        let view = doc.view_state();
+        let keep_content = doc.path.is_none() || doc.is_modified;
+        Self {
+            index,
+            path: doc.path,
+            filename: doc.filename,
+            content: keep_content.then_some(doc.content),
+            pinned: doc.pinned,
+            view,
+        }
+    }
+    
+    fn into_document(self) -> Result<Document> {
+        let mut doc = match (&self.path, self.content) {
+            (Some(path), None) => Document::from_file(path)?,
+            (Some(path), Some(content)) => {
+                // 文件可能已被删除，此时仍然恢复未保存的内容
+                let mut doc = Document::from_file(path).unwrap_or_else(|_| {
+                    let mut doc = Document::new();
+                    doc.set_path(path);
+                    doc
+                });
+                doc.set_content(content, true);
+                doc
+            }
+            (None, content) => {
+                let mut doc = Document::new();
+                doc.filename = self.filename;
+                let content = content.unwrap_or_default();
+                let modified = !content.is_empty();
+                doc.set_content(content, modified);
+                doc
+            }
+        };
+        doc.pinned = self.pinned;
+        doc.set_view_state(&self.view);
+        Ok(doc)
+    }
+}
+
 pub struct DocumentCollection {
     documents: Vec<Document>,
+    // most recently closed last
+    closed: Vec<ClosedDocument>,
 }
 
 impl DocumentCollection {
     pub fn new() -> Self {
         Self {
             documents: Vec::new(),
+            closed: Vec::new(),
         }
     }
     
@@ -313,13 +1728,92 @@ impl DocumentCollection {
         self.documents.get_mut(index)
     }
     
+    pub fn position_by_path(&self, path: &Path) -> Option<usize> {
+        self.documents.iter().position(|doc| doc.path.as_deref() == Some(path))
+    }
+    
     pub fn len(&self) -> usize {
         self.documents.len()
     }
     
+    // Remove a document without remembering it as closed, e.g. to move it to another window
+    pub fn take(&mut self, index: usize) -> Option<Document> {
+        (index < self.documents.len()).then(|| self.documents.remove(index))
+    }
+    
+    pub fn has_closed(&self) -> bool {
+        !self.closed.is_empty()
+    }
+    
+    // Bring back the most recently closed document at its old tab position.
+    // Returns where it was inserted, or None if nothing was closed.
+    pub fn reopen_closed(&mut self) -> Result<Option<usize>> {
+        let Some(closed) = self.closed.pop() else {
+            return Ok(None);
+        };
+        let index = closed.index;
+        let doc = closed.into_document()?;
+        
+        // 保持固定标签在最前面
+        let pinned_count = self.pinned_count();
+        let index = if doc.pinned {
+            index.min(pinned_count)
+        } else {
+            index.clamp(pinned_count, self.documents.len())
+        };
+        self.documents.insert(index, doc);
+        Ok(Some(index))
+    }
+    
+    // Flip the pinned flag and return where the tab belongs now: the end of the
+    // pinned group when pinning, the start of the rest when unpinning
+    pub fn toggle_pin(&mut self, index: usize) -> Option<usize> {
+        let pinned_count = self.pinned_count();
+        let doc = self.documents.get_mut(index)?;
+        doc.pinned = !doc.pinned;
+        Some(if doc.pinned { pinned_count } else { pinned_count - 1 })
+    }
+    
+    pub fn pinned_count(&self) -> usize {
+        self.documents.iter().filter(|doc| doc.pinned).count()
+    }
+    
+    // 将文档移动到新位置，其余文档依次顺移
+    pub fn move_tab(&mut self, from: usize, to: usize) -> bool {
+        if from == to || from >= self.documents.len() || to >= self.documents.len() {
+            return false;
+        }
+        let doc = self.documents.remove(from);
+        self.documents.insert(to, doc);
+        true
+    }
+    
+    // Close every unpinned document except `keep`. Returns the closed
+    // indices, highest first, so each was valid when it was closed.
+    pub fn close_other_tabs(&mut self, keep: usize) -> Vec<usize> {
+        self.close_unpinned((0..self.documents.len()).rev().filter(|&idx| idx != keep))
+    }
+    
+    // Close the unpinned documents after `index`, like `close_other_tabs`
+    pub fn close_tabs_to_right(&mut self, index: usize) -> Vec<usize> {
+        self.close_unpinned((index + 1..self.documents.len()).rev())
+    }
+    
+    fn close_unpinned(&mut self, indices: impl Iterator<Item = usize>) -> Vec<usize> {
+        let indices: Vec<usize> = indices.collect();
+        indices
+            .into_iter()
+            .filter(|&idx| !self.documents.get(idx).map(|doc| doc.pinned).unwrap_or(false) && self.close(idx))
+            .collect()
+    }
+    
     pub fn close(&mut self, index: usize) -> bool {
         if index < self.documents.len() {
-            self.documents.remove(index);
+            let doc = self.documents.remove(index);
+            self.closed.push(ClosedDocument::from_document(index, doc));
+            if self.closed.len() > MAX_CLOSED_DOCUMENTS {
+                self.closed.remove(0);
+            }
             true
         } else {
             false
@@ -0,0 +1,364 @@
+use eframe::egui;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+#[derive(Debug, Clone)]
+pub struct TreeEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub is_dir: bool,
+}
+
+/// Things the app has to react to after the tree was drawn.
+pub enum TreeAction {
+    Open(PathBuf),
+    Renamed { from: PathBuf, to: PathBuf },
+    Deleted(PathBuf),
+    Error(String),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PromptKind {
+    NewFile,
+    NewFolder,
+    Rename,
+}
+
+// Small dialog asking for a file name
+struct NamePrompt {
+    kind: PromptKind,
+    // folder for new entries, the entry itself for renames
+    target: PathBuf,
+    name: String,
+    error: Option<String>,
+}
+
+/// Folder sidebar. Directory listings are read when a folder is first expanded
+/// and thrown away again whenever the watcher reports a change inside them.
+/// Only folders that were listed are watched, each on its own, so a large
+/// `target` or `node_modules` that is never opened costs nothing.
+pub struct FileTree {
+    root: PathBuf,
+    children: HashMap<PathBuf, Vec<TreeEntry>>,
+    selected: Option<PathBuf>,
+    prompt: Option<NamePrompt>,
+    watcher: Option<RecommendedWatcher>,
+    watched: HashSet<PathBuf>,
+    changes: Option<Receiver<notify::Result<notify::Event>>>,
+}
+
+impl FileTree {
+    pub fn new(root: PathBuf, ctx: &egui::Context) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let repaint_ctx = ctx.clone();
+        let watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+            repaint_ctx.request_repaint();
+        });
+
+        let (watcher, changes) = match watcher {
+            Ok(watcher) => (Some(watcher), Some(rx)),
+            Err(err) => {
+                log::warn!("Cannot watch {} for changes: {}", root.display(), err);
+                (None, None)
+            }
+        };
+
+        Self {
+            root,
+            children: HashMap::new(),
+            selected: None,
+            prompt: None,
+            watcher,
+            watched: HashSet::new(),
+            changes,
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) -> Vec<TreeAction> {
+        self.apply_disk_changes();
+
+        let mut actions = Vec::new();
+        let root_name = self
+            .root
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.root.display().to_string());
+
+        ui.horizontal(|ui| {
+            ui.strong(root_name.to_uppercase());
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.small_button("⟳").on_hover_text("Refresh").clicked() {
+                    self.children.clear();
+                }
+                if ui.small_button("🗀").on_hover_text("New Folder").clicked() {
+                    self.start_prompt(PromptKind::NewFolder, self.root.clone());
+                }
+                if ui.small_button("🗋").on_hover_text("New File").clicked() {
+                    self.start_prompt(PromptKind::NewFile, self.root.clone());
+                }
+            });
+        });
+        ui.separator();
+
+        egui::ScrollArea::both()
+            .id_source("file_tree_scroll")
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                let root = self.root.clone();
+                self.show_dir_contents(ui, &root, &mut actions);
+            });
+
+        self.show_prompt(ui.ctx(), &mut actions);
+        actions
+    }
+
+    fn show_dir_contents(&mut self, ui: &mut egui::Ui, dir: &Path, actions: &mut Vec<TreeAction>) {
+        let entries = self.entries(dir).to_vec();
+        for entry in entries {
+            if entry.is_dir {
+                let id = ui.make_persistent_id(&entry.path);
+                egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false)
+                    .show_header(ui, |ui| {
+                        let response = ui.selectable_label(self.selected.as_ref() == Some(&entry.path), &entry.name);
+                        if response.clicked() {
+                            self.selected = Some(entry.path.clone());
+                        }
+                        self.context_menu(&response, &entry, actions);
+                    })
+                    .body(|ui| self.show_dir_contents(ui, &entry.path, actions));
+            } else {
+                let response = ui.selectable_label(self.selected.as_ref() == Some(&entry.path), &entry.name);
+                if response.clicked() {
+                    self.selected = Some(entry.path.clone());
+                    actions.push(TreeAction::Open(entry.path.clone()));
+                }
+                self.context_menu(&response, &entry, actions);
+            }
+        }
+    }
+
+    fn context_menu(&mut self, response: &egui::Response, entry: &TreeEntry, actions: &mut Vec<TreeAction>) {
+        response.context_menu(|ui| {
+            let folder = if entry.is_dir {
+                entry.path.clone()
+            } else {
+                entry.path.parent().unwrap_or(&self.root).to_path_buf()
+            };
+
+            if ui.button("New File...").clicked() {
+                self.start_prompt(PromptKind::NewFile, folder.clone());
+                ui.close_menu();
+            }
+            if ui.button("New Folder...").clicked() {
+                self.start_prompt(PromptKind::NewFolder, folder);
+                ui.close_menu();
+            }
+
+            ui.separator();
+
+            if ui.button("Rename...").clicked() {
+                self.start_prompt(PromptKind::Rename, entry.path.clone());
+                ui.close_menu();
+            }
+            if ui.button("Delete").clicked() {
+                match trash::delete(&entry.path) {
+                    Ok(()) => {
+                        self.invalidate(&entry.path);
+                        actions.push(TreeAction::Deleted(entry.path.clone()));
+                    }
+                    Err(err) => actions.push(TreeAction::Error(format!(
+                        "Failed to move {} to trash: {}",
+                        entry.path.display(),
+                        err
+                    ))),
+                }
+                ui.close_menu();
+            }
+
+            ui.separator();
+
+            if ui.button("Copy Path").clicked() {
+                ui.ctx().output_mut(|o| o.copied_text = entry.path.display().to_string());
+                ui.close_menu();
+            }
+            if ui.button("Reveal in File Manager").clicked() {
+                let folder = entry.path.parent().unwrap_or(&entry.path);
+                if let Err(err) = open::that_detached(folder) {
+                    actions.push(TreeAction::Error(format!("Failed to open {}: {}", folder.display(), err)));
+                }
+                ui.close_menu();
+            }
+        });
+    }
+
+    fn start_prompt(&mut self, kind: PromptKind, target: PathBuf) {
+        let name = match kind {
+            PromptKind::Rename => target
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            _ => String::new(),
+        };
+        self.prompt = Some(NamePrompt { kind, target, name, error: None });
+    }
+
+    fn show_prompt(&mut self, ctx: &egui::Context, actions: &mut Vec<TreeAction>) {
+        let Some(prompt) = &mut self.prompt else {
+            return;
+        };
+
+        let title = match prompt.kind {
+            PromptKind::NewFile => "New File",
+            PromptKind::NewFolder => "New Folder",
+            PromptKind::Rename => "Rename",
+        };
+        let mut open = true;
+        let mut confirmed = false;
+        let mut cancelled = false;
+
+        egui::Window::new(title)
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                let response = ui.text_edit_singleline(&mut prompt.name);
+                response.request_focus();
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    confirmed = true;
+                }
+                if let Some(error) = &prompt.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("OK").clicked() {
+                        confirmed = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancelled = true;
+                    }
+                });
+            });
+
+        if !open || cancelled {
+            self.prompt = None;
+            return;
+        }
+        if !confirmed {
+            return;
+        }
+
+        let name = prompt.name.trim().to_string();
+        if name.is_empty() || name.contains('/') || name.contains('\\') {
+            prompt.error = Some("Enter a plain file name".to_string());
+            return;
+        }
+
+        let result = match prompt.kind {
+            PromptKind::NewFile => {
+                let path = prompt.target.join(&name);
+                create_new_file(&path).map(|()| Some(TreeAction::Open(path)))
+            }
+            PromptKind::NewFolder => fs::create_dir(prompt.target.join(&name)).map(|()| None),
+            PromptKind::Rename => {
+                let from = prompt.target.clone();
+                let to = from.with_file_name(&name);
+                if to.exists() {
+                    Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, "name already taken"))
+                } else {
+                    fs::rename(&from, &to).map(|()| Some(TreeAction::Renamed { from, to }))
+                }
+            }
+        };
+
+        match result {
+            Ok(action) => {
+                let target = prompt.target.clone();
+                self.invalidate(&target.join(&name));
+                self.invalidate(&target);
+                actions.extend(action);
+                self.prompt = None;
+            }
+            Err(err) => prompt.error = Some(err.to_string()),
+        }
+    }
+
+    fn entries(&mut self, dir: &Path) -> &[TreeEntry] {
+        if !self.children.contains_key(dir) {
+            // 先监视再读取，读取期间的改动也不会漏掉
+            self.watch(dir);
+            self.children.insert(dir.to_path_buf(), read_dir_filtered(dir));
+        }
+        &self.children[dir]
+    }
+
+    fn watch(&mut self, dir: &Path) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        if self.watched.insert(dir.to_path_buf()) {
+            if let Err(err) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                log::warn!("Cannot watch {} for changes: {}", dir.display(), err);
+            }
+        }
+    }
+
+    // Drop cached listings that could contain `path`
+    fn invalidate(&mut self, path: &Path) {
+        self.children.remove(path);
+        if let Some(parent) = path.parent() {
+            self.children.remove(parent);
+        }
+        // 删除或改名的文件夹以后再列出时重新监视
+        if !path.exists() && self.watched.remove(path) {
+            if let Some(watcher) = &mut self.watcher {
+                let _ = watcher.unwatch(path);
+            }
+        }
+    }
+
+    fn apply_disk_changes(&mut self) {
+        let Some(changes) = &self.changes else {
+            return;
+        };
+        let paths: Vec<PathBuf> = changes
+            .try_iter()
+            .filter_map(|event| event.ok())
+            .filter(|event| !event.kind.is_access())
+            .flat_map(|event| event.paths)
+            .collect();
+        for path in paths {
+            self.invalidate(&path);
+        }
+    }
+}
+
+fn create_new_file(path: &Path) -> std::io::Result<()> {
+    fs::OpenOptions::new().write(true).create_new(true).open(path).map(|_| ())
+}
+
+// One level of a folder, without hidden or git-ignored entries, folders first
+fn read_dir_filtered(dir: &Path) -> Vec<TreeEntry> {
+    let mut entries: Vec<TreeEntry> = ignore::WalkBuilder::new(dir)
+        .max_depth(Some(1))
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.depth() == 1)
+        .map(|entry| TreeEntry {
+            name: entry.file_name().to_string_lossy().to_string(),
+            is_dir: entry.file_type().map(|t| t.is_dir()).unwrap_or(false),
+            path: entry.into_path(),
+        })
+        .collect();
+
+    entries.sort_by(|a, b| {
+        b.is_dir
+            .cmp(&a.is_dir)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    entries
+}
@@ -0,0 +1,407 @@
+use eframe::egui::{self, text::{LayoutJob, LayoutSection, TextFormat}};
+use std::collections::BTreeSet;
+use std::ops::Range;
+use syntect::parsing::{Scope, ScopeStack};
+use syntect::util::LinesWithEndings;
+
+use crate::analysis::LineOps;
+use crate::commands::Command;
+use crate::cursors::LineShift;
+use crate::editor::Document;
+use crate::outline::indentation;
+
+// Languages whose blocks are marked by indentation rather than brackets
+const INDENT_LANGUAGES: &[&str] = &["Python", "YAML", "Haskell", "Makefile", "Plain Text", "reStructuredText"];
+// Languages folded by heading, from `markup.heading` scopes
+const HEADING_LANGUAGES: &[&str] = &["Markdown", "MultiMarkdown"];
+
+/// Lines `start + 1 ..= end` can be hidden under line `start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoldRegion {
+    pub start: usize,
+    pub end: usize,
+    // 1 for a region that is not inside any other
+    pub level: usize,
+}
+
+impl FoldRegion {
+    // Whether `line` is one of the lines the region hides
+    pub fn hides(&self, line: usize) -> bool {
+        line > self.start && line <= self.end
+    }
+}
+
+/// Fold regions of a document and which of them are collapsed. Regions are
+/// worked out in the background after the text changes (see analysis.rs);
+/// until then they, and the collapsed ones, move along with the edits.
+#[derive(Default)]
+pub struct Folding {
+    // sorted by start line, at most one region per line
+    regions: Vec<FoldRegion>,
+    // start lines of the collapsed regions
+    folded: BTreeSet<usize>,
+    // whether any regions were worked out yet
+    computed: bool,
+}
+
+impl Folding {
+    pub fn set_regions(&mut self, regions: Vec<FoldRegion>) {
+        self.regions = regions;
+        self.computed = true;
+        let regions = &self.regions;
+        self.folded.retain(|start| regions.binary_search_by_key(start, |r| r.start).is_ok());
+    }
+
+    // Regions and collapsed regions move along with an edit, see
+    // `Document::text_changed`. A region whose first line is gone goes too.
+    pub fn shift(&mut self, shift: &LineShift) {
+        self.folded = self.folded.iter().filter_map(|&start| shift.apply(start)).collect();
+        self.regions = shift_regions(&self.regions, shift);
+    }
+
+    pub fn regions(&self) -> &[FoldRegion] {
+        &self.regions
+    }
+
+    pub fn region_at(&self, start: usize) -> Option<FoldRegion> {
+        let index = self.regions.binary_search_by_key(&start, |r| r.start).ok()?;
+        Some(self.regions[index])
+    }
+
+    // The smallest region that starts on or hides `line`
+    pub fn innermost_at(&self, line: usize) -> Option<FoldRegion> {
+        self.regions
+            .iter()
+            .take_while(|r| r.start <= line)
+            .filter(|r| r.start == line || r.hides(line))
+            .last()
+            .copied()
+    }
+
+    pub fn is_folded(&self, start: usize) -> bool {
+        self.folded.contains(&start)
+    }
+
+    pub fn has_folds(&self) -> bool {
+        !self.folded.is_empty()
+    }
+
+    pub fn fold(&mut self, start: usize) {
+        if self.region_at(start).is_some() {
+            self.folded.insert(start);
+        }
+    }
+
+    pub fn unfold(&mut self, start: usize) {
+        self.folded.remove(&start);
+    }
+
+    pub fn toggle(&mut self, start: usize) {
+        if !self.folded.remove(&start) {
+            self.fold(start);
+        }
+    }
+
+    pub fn fold_all(&mut self) {
+        self.folded = self.regions.iter().map(|r| r.start).collect();
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folded.clear();
+    }
+
+    // Collapse every region of `level` and open the ones around them
+    pub fn fold_to_level(&mut self, level: usize) {
+        for region in &self.regions {
+            if region.level == level {
+                self.folded.insert(region.start);
+            } else if region.level < level {
+                self.folded.remove(&region.start);
+            }
+        }
+    }
+
+    // Outermost collapsed region that hides `line`
+    pub fn folded_region_hiding(&self, line: usize) -> Option<FoldRegion> {
+        self.folded
+            .iter()
+            .take_while(|&&start| start < line)
+            .filter_map(|&start| self.region_at(start))
+            .find(|r| r.hides(line))
+    }
+
+    // Open every collapsed region that hides `line`
+    pub fn reveal(&mut self, line: usize) {
+        while let Some(region) = self.folded_region_hiding(line) {
+            self.folded.remove(&region.start);
+        }
+    }
+
+    /// Hidden lines as ranges of line numbers, overlapping folds merged.
+    pub fn hidden_lines(&self) -> Vec<Range<usize>> {
+        let mut hidden: Vec<Range<usize>> = Vec::new();
+        for region in self.folded.iter().filter_map(|&start| self.region_at(start)) {
+            match hidden.last_mut() {
+                Some(last) if region.start < last.end => last.end = last.end.max(region.end + 1),
+                _ => hidden.push(region.start + 1..region.end + 1),
+            }
+        }
+        hidden
+    }
+
+    /// Byte ranges of the hidden lines. Each one starts at the line break that
+    /// ends the fold's first line and stops before the break after its last,
+    /// so the lines around it keep their height.
+    pub fn hidden_bytes(&self, text: &str) -> Vec<Range<usize>> {
+        let starts: Vec<usize> = crate::cursors::line_starts(text).collect();
+        self.hidden_lines()
+            .into_iter()
+            .filter(|lines| lines.start < starts.len())
+            .map(|lines| {
+                let end = starts.get(lines.end).map(|s| s - 1).unwrap_or(text.len());
+                starts[lines.start] - 1..end
+            })
+            .collect()
+    }
+
+    pub fn folded_lines(&self) -> Vec<usize> {
+        self.folded.iter().copied().collect()
+    }
+
+    // Collapsed regions from a saved session; lines that no longer start a
+    // region are dropped the next time the regions are computed
+    pub fn set_folded_lines(&mut self, lines: &[usize]) {
+        self.folded = lines.iter().copied().collect();
+        if self.computed {
+            let regions = &self.regions;
+            self.folded.retain(|start| regions.binary_search_by_key(start, |r| r.start).is_ok());
+        }
+    }
+}
+
+/// Where `regions` are after an edit. Their last line may be gone while the
+/// first is not; then they end where the line before it went.
+pub fn shift_regions(regions: &[FoldRegion], shift: &LineShift) -> Vec<FoldRegion> {
+    regions
+        .iter()
+        .filter_map(|region| {
+            let start = shift.apply(region.start)?;
+            let end = (region.start..=region.end).rev().find_map(|line| shift.apply(line))?;
+            (end > start).then_some(FoldRegion { start, end, level: region.level })
+        })
+        .collect()
+}
+
+/// Fold regions by bracket nesting, by Markdown heading, or by indentation for
+/// languages without brackets and for plain text. `ops` comes from parsing
+/// the text with the syntax named `syntax`.
+pub fn compute_regions(text: &str, syntax: Option<&str>, ops: &LineOps) -> Vec<FoldRegion> {
+    let mut ranges = match syntax {
+        Some(syntax) if HEADING_LANGUAGES.contains(&syntax) => heading_ranges(text, ops),
+        Some(syntax) if !INDENT_LANGUAGES.contains(&syntax) => bracket_ranges(text, ops),
+        _ => Vec::new(),
+    };
+    if ranges.is_empty() {
+        ranges = indent_ranges(text);
+    }
+
+    // 同一行开始的多个区域只保留最大的一个
+    ranges.sort_by_key(|&(start, end)| (start, std::cmp::Reverse(end)));
+    ranges.dedup_by_key(|&mut (start, _)| start);
+
+    let mut regions = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+    for (start, end) in ranges.into_iter().filter(|(start, end)| end > start) {
+        while open.last().is_some_and(|&last| last < start) {
+            open.pop();
+        }
+        regions.push(FoldRegion { start, end, level: open.len() + 1 });
+        open.push(end);
+    }
+    regions
+}
+
+// Bracket pairs that span lines, skipping brackets in comments and strings.
+// A region takes in the line with its closing bracket unless that line opens
+// the next block, as in `} else {`.
+fn bracket_ranges(text: &str, line_ops: &LineOps) -> Vec<(usize, usize)> {
+    let comment = Scope::new("comment").unwrap();
+    let string = Scope::new("string").unwrap();
+
+    let mut stack = ScopeStack::new();
+    let mut open: Vec<usize> = Vec::new();
+    let mut ranges = Vec::new();
+
+    for ((line_no, line), ops) in LinesWithEndings::from(text).enumerate().zip(line_ops) {
+        let mut last = 0;
+        let mut ops = ops.iter().peekable();
+        loop {
+            let next = ops.peek().map(|(pos, _)| *pos).unwrap_or(line.len());
+            if next > last {
+                let in_text = stack.as_slice().iter().any(|s| comment.is_prefix_of(*s) || string.is_prefix_of(*s));
+                if !in_text {
+                    for c in line[last..next].chars() {
+                        match c {
+                            '{' | '[' | '(' => open.push(line_no),
+                            '}' | ']' | ')' => {
+                                if let Some(start) = open.pop() {
+                                    ranges.push((start, line_no));
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                last = next;
+            }
+
+            match ops.next() {
+                Some((_, op)) => {
+                    if stack.apply(op).is_err() {
+                        break;
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+
+    let starts: BTreeSet<usize> = ranges.iter().filter(|(start, end)| end > start).map(|&(start, _)| start).collect();
+    for (start, end) in ranges.iter_mut() {
+        if end > start && starts.contains(end) {
+            *end -= 1;
+        }
+    }
+    ranges
+}
+
+// A heading folds everything up to the next heading of the same or a higher level
+fn heading_ranges(text: &str, line_ops: &LineOps) -> Vec<(usize, usize)> {
+    let heading = Scope::new("markup.heading").unwrap();
+
+    let mut stack = ScopeStack::new();
+    let lines: Vec<&str> = text.lines().collect();
+    let mut headings: Vec<(usize, usize)> = Vec::new();
+
+    for ((line_no, line), ops) in LinesWithEndings::from(text).enumerate().zip(line_ops) {
+        let mut in_heading = stack.as_slice().iter().any(|s| heading.is_prefix_of(*s));
+        for (_, op) in ops {
+            if stack.apply(op).is_err() {
+                break;
+            }
+            in_heading |= stack.as_slice().iter().any(|s| heading.is_prefix_of(*s));
+        }
+        if !in_heading {
+            continue;
+        }
+
+        // `# Title`，或者下一行用 === / --- 标记的标题
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') {
+            headings.push((line_no, trimmed.chars().take_while(|&c| c == '#').count()));
+        } else if line_no > 0 && !trimmed.is_empty() && trimmed.chars().all(|c| c == '=') {
+            headings.push((line_no - 1, 1));
+        } else if line_no > 0 && !trimmed.is_empty() && trimmed.chars().all(|c| c == '-') {
+            headings.push((line_no - 1, 2));
+        }
+    }
+
+    let mut ranges = Vec::new();
+    for (i, &(start, level)) in headings.iter().enumerate() {
+        let mut end = headings[i + 1..]
+            .iter()
+            .find(|&&(_, other)| other <= level)
+            .map(|&(line, _)| line.saturating_sub(1))
+            .unwrap_or(lines.len().saturating_sub(1));
+        while end > start && lines.get(end).is_some_and(|line| line.trim().is_empty()) {
+            end -= 1;
+        }
+        ranges.push((start, end));
+    }
+    ranges
+}
+
+// A line folds the lines below it that are indented deeper, blank lines
+// included except at the end
+fn indent_ranges(text: &str) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut last_content = 0;
+    for (line_no, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let indent = indentation(line);
+        while let Some(&(start, open_indent)) = open.last() {
+            if open_indent < indent {
+                break;
+            }
+            open.pop();
+            ranges.push((start, last_content));
+        }
+        open.push((line_no, indent));
+        last_content = line_no;
+    }
+    ranges.extend(open.into_iter().map(|(start, _)| (start, last_content)));
+    ranges
+}
+
+/// Layout for the text box with the `hidden` byte ranges drawn at zero height.
+pub fn layout_job(text: &str, hidden: &[Range<usize>], font: egui::FontId, color: egui::Color32, wrap_width: f32) -> LayoutJob {
+    let visible = TextFormat::simple(font.clone(), color);
+    let folded = TextFormat {
+        font_id: font,
+        color: egui::Color32::TRANSPARENT,
+        line_height: Some(0.0),
+        ..Default::default()
+    };
+    let section = |range: Range<usize>, format: &TextFormat| LayoutSection {
+        leading_space: 0.0,
+        byte_range: range,
+        format: format.clone(),
+    };
+
+    let mut job = LayoutJob { text: text.to_string(), ..Default::default() };
+    job.wrap.max_width = wrap_width;
+    // 编辑时文本可能已经比折叠范围短
+    let boundary = |mut pos: usize| {
+        pos = pos.min(text.len());
+        while !text.is_char_boundary(pos) {
+            pos -= 1;
+        }
+        pos
+    };
+    let mut last = 0;
+    for range in hidden {
+        let start = boundary(range.start).max(last);
+        let end = boundary(range.end).max(start);
+        if start > last {
+            job.sections.push(section(last..start, &visible));
+        }
+        if end > start {
+            job.sections.push(section(start..end, &folded));
+        }
+        last = end;
+    }
+    if last < text.len() || job.sections.is_empty() {
+        job.sections.push(section(last..text.len(), &visible));
+    }
+    job
+}
+
+// Folding commands, shared by the main window and extra windows
+pub fn execute(doc: &mut Document, cmd: Command) {
+    match cmd {
+        Command::Fold => doc.fold_at_caret(true),
+        Command::Unfold => doc.fold_at_caret(false),
+        Command::FoldAll => doc.fold_all(),
+        Command::UnfoldAll => doc.unfold_all(),
+        Command::FoldLevel1 => doc.fold_to_level(1),
+        Command::FoldLevel2 => doc.fold_to_level(2),
+        Command::FoldLevel3 => doc.fold_to_level(3),
+        Command::FoldLevel4 => doc.fold_to_level(4),
+        Command::FoldLevel5 => doc.fold_to_level(5),
+        _ => return,
+    }
+    doc.request_focus();
+}
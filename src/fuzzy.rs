@@ -0,0 +1,177 @@
+// Subsequence fuzzy matching used by the command palette and quick open.
+// Every pattern character must appear in order in the candidate; matches at
+// word starts and runs of consecutive characters score higher, gaps lower.
+
+pub struct FuzzyMatch {
+    pub score: i32,
+    // char indices into the candidate, for highlighting
+    pub positions: Vec<usize>,
+}
+
+// Bonus for a character matched at `pos`, before gap and run scoring
+fn position_score(chars: &[char], pos: usize) -> i32 {
+    let mut score = 1;
+    if is_word_start(chars, pos) {
+        score += 8;
+    }
+    score
+}
+
+// Score for matching the next pattern character at `pos` when the previous
+// one was matched at `prev`
+fn gap_score(prev: usize, pos: usize) -> i32 {
+    match pos - prev - 1 {
+        0 => 5,
+        gap => -(gap.min(MAX_GAP_PENALTY) as i32),
+    }
+}
+
+// Gaps longer than this cost no more than this
+const MAX_GAP_PENALTY: usize = 5;
+
+/// The best-scoring way to find `pattern` as a subsequence of `candidate`,
+/// or None if it does not occur. Every alignment is considered, so picking a
+/// word start for one character never costs a match for a later one.
+pub fn fuzzy_match(pattern: &str, candidate: &str) -> Option<FuzzyMatch> {
+    let pattern: Vec<char> = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if pattern.is_empty() {
+        return Some(FuzzyMatch { score: 0, positions: Vec::new() });
+    }
+
+    let chars: Vec<char> = candidate.chars().collect();
+    let lowered: Vec<char> = chars.iter().map(|&c| lower(c)).collect();
+    let (m, n) = (pattern.len(), chars.len());
+    if m > n {
+        return None;
+    }
+
+    // best[i * n + j]: best score with pattern[i] matched at j; from[..]: where pattern[i - 1] was
+    let mut best: Vec<Option<i32>> = vec![None; m * n];
+    let mut from = vec![0; m * n];
+    for j in 0..n {
+        if lowered[j] == pattern[0] {
+            best[j] = Some(position_score(&chars, j) + if j == 0 { 10 } else { 0 });
+        }
+    }
+    for i in 1..m {
+        let (previous, current) = best.split_at_mut(i * n);
+        let previous = &previous[(i - 1) * n..];
+        // 间隔足够长时扣分不再增加，只需记住其中最好的一个
+        let mut far: Option<(i32, usize)> = None;
+        for j in i..n {
+            if let Some(k) = j.checked_sub(MAX_GAP_PENALTY + 1) {
+                if let Some(score) = previous[k] {
+                    if far.map(|(s, _)| score > s).unwrap_or(true) {
+                        far = Some((score, k));
+                    }
+                }
+            }
+            if lowered[j] != pattern[i] {
+                continue;
+            }
+            let mut choice = far.map(|(score, k)| (score + gap_score(k, j), k));
+            let near = j.saturating_sub(MAX_GAP_PENALTY);
+            for (k, score) in previous.iter().enumerate().take(j).skip(near) {
+                if let Some(score) = *score {
+                    let score = score + gap_score(k, j);
+                    if choice.map(|(s, _)| score > s).unwrap_or(true) {
+                        choice = Some((score, k));
+                    }
+                }
+            }
+            if let Some((score, k)) = choice {
+                current[j] = Some(score + position_score(&chars, j));
+                from[i * n + j] = k;
+            }
+        }
+    }
+
+    // earliest end wins ties
+    let last = &best[(m - 1) * n..];
+    let (mut pos, mut score) = (0, None);
+    for (j, s) in last.iter().enumerate() {
+        if let Some(s) = *s {
+            if score.map(|best| s > best).unwrap_or(true) {
+                pos = j;
+                score = Some(s);
+            }
+        }
+    }
+    let mut score = score?;
+
+    let mut positions = vec![0; m];
+    for i in (0..m).rev() {
+        positions[i] = pos;
+        pos = from[i * n + pos];
+    }
+
+    // shorter candidates win ties
+    score -= (chars.len() as i32 / 10).min(10);
+
+    Some(FuzzyMatch { score, positions })
+}
+
+fn lower(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn is_word_start(chars: &[char], i: usize) -> bool {
+    if i == 0 {
+        return true;
+    }
+    let prev = chars[i - 1];
+    let cur = chars[i];
+    matches!(prev, ' ' | '_' | '-' | '.' | '/' | '\\' | ':')
+        || (prev.is_lowercase() && cur.is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(pattern: &str, candidate: &str) -> Option<Vec<usize>> {
+        fuzzy_match(pattern, candidate).map(|m| m.positions)
+    }
+
+    #[test]
+    fn word_start_does_not_cost_a_later_match() {
+        // 如果 a 跳到 "_a" 的词首，后面就没有 b 了
+        assert_eq!(positions("ab", "tab_a.rs"), Some(vec![1, 2]));
+        assert_eq!(positions("ar", "tab_a.rs"), Some(vec![4, 6]));
+    }
+
+    #[test]
+    fn matches_any_subsequence() {
+        assert_eq!(positions("qop", "quick_open.rs"), Some(vec![0, 6, 7]));
+        assert_eq!(positions("QO", "quick_open.rs"), Some(vec![0, 6]));
+        assert_eq!(positions("q o", "quick_open.rs"), Some(vec![0, 6]));
+        assert!(positions("", "anything").unwrap().is_empty());
+        assert_eq!(positions("xyz", "quick_open.rs"), None);
+        assert_eq!(positions("sr", "src"), Some(vec![0, 1]));
+        assert_eq!(positions("rs", "src"), None);
+    }
+
+    #[test]
+    fn prefers_word_starts_and_runs() {
+        let score = |pattern, candidate| fuzzy_match(pattern, candidate).unwrap().score;
+        // 词首匹配优先于词中间的匹配
+        assert!(score("fl", "function_list.rs") > score("fl", "self_help.rs"));
+        // 连续的字符优先于分散的字符
+        assert!(score("edit", "editor.rs") > score("edit", "exdxixt.rs"));
+        // 开头匹配优先
+        assert!(score("app", "app.rs") > score("app", "wrapper.rs"));
+        // 同等条件下短的优先
+        assert!(score("main", "main.rs") > score("main", "src/some/long/path/main.rs"));
+    }
+
+    #[test]
+    fn picks_the_best_alignment() {
+        // "fb" 应该落在 foo_bar 的两个词首上
+        assert_eq!(positions("fb", "foo_bar"), Some(vec![0, 4]));
+        assert_eq!(positions("ob", "foo_bar"), Some(vec![2, 4]));
+    }
+}
@@ -0,0 +1,136 @@
+use eframe::egui;
+
+// 行号左侧留给书签、诊断等图标的一列
+const MARK_LANE_WIDTH: f32 = 16.0;
+// 行号右侧的折叠标记
+const FOLD_LANE_WIDTH: f32 = 14.0;
+const NUMBER_PADDING: f32 = 6.0;
+const BOOKMARK_COLOR: egui::Color32 = egui::Color32::from_rgb(70, 130, 220);
+
+/// What the gutter shows next to one line besides its number.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LineInfo {
+    // Some(folded) when a fold region starts on the line
+    pub fold: Option<bool>,
+    pub bookmark: bool,
+}
+
+pub enum GutterAction {
+    ToggleFold(usize),
+    // a click in the icon lane
+    ToggleBookmark(usize),
+}
+
+/// The column left of the text: an icon lane, line numbers and fold markers.
+/// It is painted from the text box's galley after the text is laid out, so
+/// every number sits on the first row of its line however the text wraps,
+/// and lines hidden by a fold take no room.
+pub struct Gutter {
+    line_numbers: bool,
+    number_width: f32,
+    font: egui::FontId,
+}
+
+impl Gutter {
+    pub fn new(ui: &egui::Ui, font: egui::FontId, line_count: usize, line_numbers: bool) -> Self {
+        let digits = line_count.max(1).to_string().len();
+        let digit_width = ui.fonts(|f| f.glyph_width(&font, '0'));
+        Self {
+            line_numbers,
+            number_width: if line_numbers { digits as f32 * digit_width + NUMBER_PADDING } else { 0.0 },
+            font,
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        MARK_LANE_WIDTH + self.number_width + FOLD_LANE_WIDTH
+    }
+
+    /// Paint the gutter into `rect` next to `galley`, drawn at `origin`.
+    pub fn show(
+        &self,
+        ui: &mut egui::Ui,
+        rect: egui::Rect,
+        galley: &egui::Galley,
+        origin: egui::Pos2,
+        current_line: usize,
+        info: impl Fn(usize) -> LineInfo,
+    ) -> Option<GutterAction> {
+        let mut action = None;
+        let clip = ui.clip_rect();
+        let number_right = rect.left() + MARK_LANE_WIDTH + self.number_width - NUMBER_PADDING / 2.0;
+        let fold_center = rect.right() - FOLD_LANE_WIDTH / 2.0;
+
+        let mut line = 0;
+        let mut starts_line = true;
+        for row in &galley.rows {
+            let row_line = line;
+            let first_row = starts_line;
+            starts_line = row.ends_with_newline;
+            if row.ends_with_newline {
+                line += 1;
+            }
+
+            // 折叠的行高度为零，换行产生的后续行不显示行号
+            let row_rect = row.rect.translate(origin.to_vec2());
+            if !first_row || row_rect.height() < 1.0 {
+                continue;
+            }
+            if row_rect.bottom() < clip.top() {
+                continue;
+            }
+            if row_rect.top() > clip.bottom() {
+                break;
+            }
+
+            let line_info = info(row_line);
+            let mark_lane = egui::Rect::from_min_size(
+                egui::pos2(rect.left(), row_rect.top()),
+                egui::vec2(MARK_LANE_WIDTH, row_rect.height()),
+            );
+            let response = ui.interact(mark_lane, ui.id().with(("mark_lane", row_line)), egui::Sense::click());
+            if line_info.bookmark {
+                ui.painter().circle_filled(mark_lane.center(), MARK_LANE_WIDTH / 2.0 - 3.0, BOOKMARK_COLOR);
+            } else if response.hovered() {
+                ui.painter().circle_stroke(mark_lane.center(), MARK_LANE_WIDTH / 2.0 - 3.0, egui::Stroke::new(1.0, BOOKMARK_COLOR));
+            }
+            if response.clicked() {
+                action = Some(GutterAction::ToggleBookmark(row_line));
+            }
+
+            if self.line_numbers {
+                let color = if row_line == current_line {
+                    ui.visuals().strong_text_color()
+                } else {
+                    ui.visuals().weak_text_color()
+                };
+                ui.painter().text(
+                    egui::pos2(number_right, row_rect.center().y),
+                    egui::Align2::RIGHT_CENTER,
+                    (row_line + 1).to_string(),
+                    self.font.clone(),
+                    color,
+                );
+            }
+
+            if let Some(folded) = line_info.fold {
+                let size = FOLD_LANE_WIDTH - 4.0;
+                let marker = egui::Rect::from_center_size(egui::pos2(fold_center, row_rect.center().y), egui::vec2(size, size));
+                let response = ui.interact(marker, ui.id().with(("fold_marker", row_line)), egui::Sense::click());
+                egui::collapsing_header::paint_default_icon(ui, if folded { 0.0 } else { 1.0 }, &response);
+                if response.clicked() {
+                    action = Some(GutterAction::ToggleFold(row_line));
+                }
+            }
+        }
+
+        // 行号和正文之间的分隔线
+        let x = rect.right() + ui.spacing().item_spacing.x / 2.0;
+        ui.painter().line_segment(
+            [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+            ui.visuals().widgets.noninteractive.bg_stroke,
+        );
+
+        action
+    }
+}
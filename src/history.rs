@@ -0,0 +1,157 @@
+use std::time::{Duration, Instant};
+
+use crate::cursors::{Change, Selection};
+
+// 在这段时间内连续输入的字符合并成一步撤销
+const TYPING_PAUSE: Duration = Duration::from_secs(1);
+// 最多保留的撤销步数
+const MAX_STEPS: usize = 1000;
+
+/// How an edit goes on the undo stack: each command is a step of its own,
+/// while characters typed or deleted one after another make a single step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditKind {
+    Typing,
+    Command,
+}
+
+/// One undo step: the edits it is made of, in order, and where the cursors
+/// were before and after them.
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub edits: Vec<Vec<Change>>,
+    pub before: Vec<Selection>,
+    pub after: Vec<Selection>,
+}
+
+/// Undo and redo stacks of a document, with every edit and all of its cursors.
+pub struct History {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    // when the top step was last typed into, while more typing may join it
+    typed_at: Option<Instant>,
+    // length of the undo stack when the document was saved, None once that
+    // version can't be reached any more
+    saved: Option<usize>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self { undo: Vec::new(), redo: Vec::new(), typed_at: None, saved: Some(0) }
+    }
+}
+
+impl History {
+    pub fn record(&mut self, changes: Vec<Change>, before: Vec<Selection>, after: Vec<Selection>, kind: EditKind) {
+        if self.saved.is_some_and(|saved| saved > self.undo.len()) {
+            self.saved = None;
+        }
+        self.redo.clear();
+
+        let now = Instant::now();
+        if kind == EditKind::Typing && self.continues_typing(&changes, now) {
+            if self.saved == Some(self.undo.len()) {
+                self.saved = None;
+            }
+            if let Some(step) = self.undo.last_mut() {
+                step.edits.push(changes);
+                step.after = after;
+            }
+            self.typed_at = Some(now);
+            return;
+        }
+
+        self.undo.push(Step { edits: vec![changes], before, after });
+        self.typed_at = (kind == EditKind::Typing).then_some(now);
+        if self.undo.len() > MAX_STEPS {
+            self.undo.remove(0);
+            self.saved = self.saved.and_then(|saved| saved.checked_sub(1));
+        }
+    }
+
+    // Typing joins the top step if it goes on where that step left off, on
+    // the same line and without a long pause
+    fn continues_typing(&self, changes: &[Change], now: Instant) -> bool {
+        let (Some(typed_at), Some(previous)) = (self.typed_at, self.undo.last().and_then(|step| step.edits.last())) else {
+            return false;
+        };
+        if now.duration_since(typed_at) > TYPING_PAUSE
+            || previous.len() != changes.len()
+            || changes.iter().any(|change| change.inserted.contains('\n') || change.removed.contains('\n'))
+        {
+            return false;
+        }
+        let mut delta = 0isize;
+        previous.iter().zip(changes).all(|(before, change)| {
+            let end = before.start.saturating_add_signed(delta) + before.inserted.len();
+            delta += before.inserted.len() as isize - before.removed.len() as isize;
+            change.start == end || change.end() == end
+        })
+    }
+
+    // The step to take back; it moves over to the redo stack
+    pub fn undo(&mut self) -> Option<&Step> {
+        let step = self.undo.pop()?;
+        self.redo.push(step);
+        self.typed_at = None;
+        self.redo.last()
+    }
+
+    pub fn redo(&mut self) -> Option<&Step> {
+        let step = self.redo.pop()?;
+        self.undo.push(step);
+        self.typed_at = None;
+        self.undo.last()
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.undo.len());
+        self.typed_at = None;
+    }
+
+    // 内容不再对应磁盘上的文件，例如恢复了未保存的内容
+    pub fn forget_saved(&mut self) {
+        self.saved = None;
+    }
+
+    // Whether undoing and redoing left the text different from the saved file
+    pub fn is_modified(&self) -> bool {
+        self.saved != Some(self.undo.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(start: usize, text: &str) -> Vec<Change> {
+        vec![Change { start, removed: String::new(), inserted: text.to_string(), line_map: None }]
+    }
+
+    fn record(history: &mut History, changes: Vec<Change>, kind: EditKind) {
+        history.record(changes, vec![Selection::caret(0)], vec![Selection::caret(0)], kind);
+    }
+
+    #[test]
+    fn commands_are_steps_of_their_own() {
+        let mut history = History::default();
+        record(&mut history, insert(0, "a\n"), EditKind::Command);
+        record(&mut history, insert(0, "a\n"), EditKind::Command);
+        assert!(history.undo().is_some());
+        assert!(history.undo().is_some());
+        assert!(history.undo().is_none());
+        assert!(!history.is_modified());
+    }
+
+    #[test]
+    fn typing_in_one_place_is_one_step() {
+        let mut history = History::default();
+        record(&mut history, insert(0, "a"), EditKind::Typing);
+        record(&mut history, insert(1, "b"), EditKind::Typing);
+        // 在别处输入就是新的一步
+        record(&mut history, insert(0, "c"), EditKind::Typing);
+        assert_eq!(history.undo().map(|step| step.edits.len()), Some(1));
+        assert_eq!(history.undo().map(|step| step.edits.len()), Some(2));
+        assert!(history.undo().is_none());
+    }
+}
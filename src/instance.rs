@@ -0,0 +1,92 @@
+use eframe::egui;
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::config::Config;
+
+const SOCKET_NAME: &str = "notion-pp.sock";
+
+/// Outcome of trying to become the only running editor.
+pub enum Instance {
+    /// No other editor answered, so this process should open a window. The
+    /// listener is `None` if the socket could not be created.
+    Primary(Option<InstanceListener>),
+    /// The files were handed over to an editor that is already running.
+    Forwarded,
+}
+
+pub struct InstanceListener {
+    listener: UnixListener,
+}
+
+impl Instance {
+    pub fn acquire(files: &[PathBuf]) -> Instance {
+        let socket_path = Config::runtime_dir().join(SOCKET_NAME);
+
+        if let Ok(stream) = UnixStream::connect(&socket_path) {
+            match forward_files(stream, files) {
+                Ok(()) => return Instance::Forwarded,
+                Err(err) => log::warn!("Failed to forward files to running instance: {}", err),
+            }
+        }
+
+        // Nobody answered, so whatever is left at the path is a stale socket
+        let _ = std::fs::remove_file(&socket_path);
+
+        match UnixListener::bind(&socket_path) {
+            Ok(listener) => Instance::Primary(Some(InstanceListener { listener })),
+            Err(err) => {
+                log::error!("Failed to bind instance socket {}: {}", socket_path.display(), err);
+                Instance::Primary(None)
+            }
+        }
+    }
+}
+
+impl InstanceListener {
+    /// Accepts connections on a background thread. Every batch of files sent by a
+    /// later invocation arrives on the returned channel and wakes up the UI.
+    pub fn spawn(self, ctx: egui::Context) -> Receiver<Vec<PathBuf>> {
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in self.listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        log::warn!("Instance socket accept failed: {}", err);
+                        continue;
+                    }
+                };
+
+                // 路径按原始字节传递，以 NUL 分隔，换行和非 UTF-8 的文件名也不会出错
+                let files: Vec<PathBuf> = BufReader::new(stream)
+                    .split(0)
+                    .map_while(|path| path.ok())
+                    .filter(|path| !path.is_empty())
+                    .map(|path| PathBuf::from(OsString::from_vec(path)))
+                    .collect();
+
+                if tx.send(files).is_err() {
+                    break;
+                }
+                ctx.request_repaint();
+            }
+        });
+
+        rx
+    }
+}
+
+fn forward_files(mut stream: UnixStream, files: &[PathBuf]) -> std::io::Result<()> {
+    for file in files {
+        stream.write_all(file.as_os_str().as_bytes())?;
+        stream.write_all(&[0])?;
+    }
+    stream.flush()
+}
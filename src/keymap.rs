@@ -0,0 +1,390 @@
+use eframe::egui::{self, Key, Modifiers};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+use crate::commands::Command;
+use crate::config::Config;
+
+/// One key press together with the modifiers held down, e.g. `Ctrl+Shift+P`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyChord {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl KeyChord {
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut modifiers = Modifiers::NONE;
+        let mut key = None;
+
+        for part in text.split('+').map(str::trim) {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers = modifiers | Modifiers::COMMAND,
+                "cmd" | "super" => modifiers = modifiers | Modifiers::MAC_CMD | Modifiers::COMMAND,
+                "alt" | "option" => modifiers = modifiers | Modifiers::ALT,
+                "shift" => modifiers = modifiers | Modifiers::SHIFT,
+                _ => {
+                    if key.is_some() {
+                        return None;
+                    }
+                    key = Key::from_name(part).or_else(|| Key::from_name(&part.to_ascii_uppercase()));
+                    key?;
+                }
+            }
+        }
+
+        Some(Self { modifiers, key: key? })
+    }
+
+    // `keys` is the key the layout produced, then the physical key when that
+    // differs (see `process_input`)
+    fn matches(&self, modifiers: Modifiers, keys: &[Key]) -> bool {
+        keys.contains(&self.key) && modifiers.matches_exact(self.modifiers)
+    }
+}
+
+impl std::fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.modifiers.mac_cmd {
+            write!(f, "Cmd+")?;
+        } else if self.modifiers.command || self.modifiers.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{}", self.key.name())
+    }
+}
+
+/// A binding is one chord, or two for sequences like `Ctrl+K Ctrl+W`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySequence(pub Vec<KeyChord>);
+
+impl KeySequence {
+    pub fn parse(text: &str) -> Option<Self> {
+        let chords = text
+            .split_whitespace()
+            .map(KeyChord::parse)
+            .collect::<Option<Vec<_>>>()?;
+        if chords.is_empty() || chords.len() > 2 {
+            return None;
+        }
+        Some(Self(chords))
+    }
+}
+
+impl std::fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, chord) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", chord)?;
+        }
+        Ok(())
+    }
+}
+
+const DEFAULT_BINDINGS: &[(&str, Command)] = &[
+    ("Ctrl+N", Command::NewFile),
+    ("Ctrl+Shift+N", Command::NewWindow),
+    ("Ctrl+O", Command::OpenFile),
+    ("Ctrl+K Ctrl+O", Command::OpenFolder),
+    ("Ctrl+P", Command::QuickOpen),
+    ("Ctrl+S", Command::Save),
+    ("Ctrl+Alt+S", Command::SaveAs),
+    ("Ctrl+W", Command::CloseTab),
+    ("Ctrl+Shift+T", Command::ReopenClosedTab),
+    ("Alt+F4", Command::Exit),
+    // Taken before the text box sees them, so its own undo never runs
+    ("Ctrl+Z", Command::Undo),
+    ("Ctrl+Y", Command::Redo),
+    ("Ctrl+Shift+Z", Command::Redo),
+    // The window turns these three into clipboard events that the editor
+    // handles itself, so they are listed here mainly for the menus
+    ("Ctrl+X", Command::Cut),
+    ("Ctrl+C", Command::Copy),
+    ("Ctrl+V", Command::Paste),
+    // arrives as a paste too, see `process_input`
+    ("Ctrl+Shift+V", Command::PasteFromHistory),
+    ("Ctrl+D", Command::AddNextOccurrence),
+    ("Ctrl+Shift+L", Command::SelectAllOccurrences),
+    ("Ctrl+Alt+Up", Command::AddCursorAbove),
+    ("Ctrl+Alt+Down", Command::AddCursorBelow),
+    ("Alt+C", Command::ColumnEditor),
+    ("Ctrl+Shift+\\", Command::GotoMatchingBracket),
+    ("Ctrl+/", Command::ToggleLineComment),
+    ("Ctrl+Shift+/", Command::ToggleBlockComment),
+    ("Ctrl+Shift+D", Command::DuplicateLine),
+    ("Ctrl+Shift+Up", Command::MoveLineUp),
+    ("Ctrl+Shift+Down", Command::MoveLineDown),
+    ("Ctrl+Shift+K", Command::DeleteLine),
+    ("Ctrl+J", Command::JoinLines),
+    ("Ctrl+Shift+U", Command::UpperCase),
+    ("Ctrl+U", Command::LowerCase),
+    ("Ctrl+F2", Command::ToggleBookmark),
+    ("F2", Command::NextBookmark),
+    ("Shift+F2", Command::PreviousBookmark),
+    ("Ctrl+F", Command::Find),
+    ("Ctrl+H", Command::Replace),
+    ("Ctrl+Tab", Command::NextRecentTab),
+    ("Ctrl+PageDown", Command::NextTab),
+    ("Ctrl+Shift+Tab", Command::PreviousRecentTab),
+    ("Ctrl+PageUp", Command::PreviousTab),
+    ("Ctrl+\\", Command::SplitRight),
+    ("Ctrl+K Ctrl+\\", Command::SplitDown),
+    ("Ctrl+K Ctrl+Right", Command::FocusNextPane),
+    // Shift+[ gives '{', which egui has no key for, so the window never
+    // reports Ctrl+Shift+[ at all
+    ("Ctrl+Alt+F", Command::Fold),
+    ("Ctrl+Alt+Shift+F", Command::Unfold),
+    ("Ctrl+K Ctrl+0", Command::FoldAll),
+    ("Ctrl+K Ctrl+J", Command::UnfoldAll),
+    ("Ctrl+K Ctrl+1", Command::FoldLevel1),
+    ("Ctrl+K Ctrl+2", Command::FoldLevel2),
+    ("Ctrl+K Ctrl+3", Command::FoldLevel3),
+    ("Ctrl+K Ctrl+4", Command::FoldLevel4),
+    ("Ctrl+K Ctrl+5", Command::FoldLevel5),
+    ("Alt+Z", Command::ToggleWordWrap),
+    ("Ctrl+K Ctrl+L", Command::ToggleLineNumbers),
+    ("Ctrl+K Ctrl+H", Command::ToggleSyntaxHighlighting),
+    ("Ctrl+B", Command::ToggleFileTree),
+    ("Ctrl+K Ctrl+M", Command::ToggleDocumentMap),
+    ("Ctrl+K Ctrl+F", Command::ToggleFunctionList),
+    ("Ctrl+Comma", Command::Settings),
+    ("Ctrl+Shift+P", Command::OpenCommandPalette),
+];
+
+// Entries in keymap.json: a single binding, a list of them, or null to unbind
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BindingOverride {
+    One(String),
+    Many(Vec<String>),
+}
+
+pub struct Keymap {
+    bindings: Vec<(KeySequence, Command)>,
+    // first chord of a sequence that is waiting for its second key
+    pending: Option<KeyChord>,
+}
+
+impl Keymap {
+    pub fn defaults() -> Self {
+        let bindings = DEFAULT_BINDINGS
+            .iter()
+            .filter_map(|(text, cmd)| Some((KeySequence::parse(text)?, *cmd)))
+            .collect();
+        Self { bindings, pending: None }
+    }
+
+    /// Default bindings with the user's `keymap.json` from the config dir on top.
+    pub fn load() -> Self {
+        let mut keymap = Self::defaults();
+
+        let Some(path) = Config::config_dir().map(|dir| dir.join("keymap.json")) else {
+            return keymap;
+        };
+        if !path.exists() {
+            return keymap;
+        }
+
+        let overrides = fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| {
+                serde_json::from_str::<HashMap<String, Option<BindingOverride>>>(&text)
+                    .map_err(anyhow::Error::from)
+            });
+
+        match overrides {
+            Ok(overrides) => keymap.apply_overrides(overrides),
+            Err(err) => log::error!("Failed to load keymap {}: {}", path.display(), err),
+        }
+
+        keymap
+    }
+
+    fn apply_overrides(&mut self, overrides: HashMap<String, Option<BindingOverride>>) {
+        for (id, binding) in overrides {
+            let Some(cmd) = Command::from_id(&id) else {
+                log::warn!("Unknown command in keymap: {}", id);
+                continue;
+            };
+
+            let texts = match binding {
+                None => Vec::new(),
+                Some(BindingOverride::One(text)) => vec![text],
+                Some(BindingOverride::Many(texts)) => texts,
+            };
+
+            self.bindings.retain(|(_, bound)| *bound != cmd);
+            for text in texts {
+                match KeySequence::parse(&text) {
+                    Some(seq) => {
+                        // a user binding takes the keys away from whatever had them before
+                        self.bindings.retain(|(bound, _)| *bound != seq);
+                        self.bindings.push((seq, cmd));
+                    }
+                    None => log::warn!("Invalid key binding \"{}\" for {}", text, id),
+                }
+            }
+        }
+    }
+
+    /// Text to show next to the command in menus, e.g. `Ctrl+S`.
+    pub fn binding_text(&self, cmd: Command) -> Option<String> {
+        self.bindings
+            .iter()
+            .find(|(_, bound)| *bound == cmd)
+            .map(|(seq, _)| seq.to_string())
+    }
+
+    /// Shown in the status bar while a sequence is half typed.
+    pub fn pending_text(&self) -> Option<String> {
+        self.pending.map(|chord| format!("({}) was pressed. Waiting for second key...", chord))
+    }
+
+    /// Consumes the key presses that belong to a binding and returns the commands
+    /// they trigger, so the text editor never sees them.
+    pub fn process_input(&mut self, ctx: &egui::Context) -> Vec<Command> {
+        let mut triggered = Vec::new();
+
+        ctx.input_mut(|input| {
+            let held = input.modifiers;
+            // Alt+letter still types the letter right after the key press
+            let mut swallow_text = false;
+            input.events.retain(|event| {
+                let (key, physical_key, modifiers) = match event {
+                    egui::Event::Key { key, physical_key, pressed: true, modifiers, .. } => (key, physical_key, modifiers),
+                    egui::Event::Text(_) if swallow_text => {
+                        swallow_text = false;
+                        return false;
+                    }
+                    // The window reports Ctrl+Shift+V as a paste with no key
+                    // event at all, so a binding for it is looked up here
+                    egui::Event::Paste(_) if held.command && held.shift => {
+                        return match self.match_key(held, &[Key::V]) {
+                            Some(Some(cmd)) if cmd != Command::Paste => {
+                                triggered.push(cmd);
+                                false
+                            }
+                            _ => true,
+                        };
+                    }
+                    _ => return true,
+                };
+                swallow_text = false;
+
+                // With Shift held the layout turns '/' into '?' and '\' into '|', so
+                // Ctrl+Shift+/ arrives as Ctrl+Shift+?. Symbol keys also try the key
+                // they sit on; letters and digits never do, so other layouts such as
+                // AZERTY don't trigger the binding of whatever is printed on the QWERTY key
+                let mut keys = vec![*key];
+                if let Some(physical) = physical_key {
+                    if *physical != *key && is_symbol(*key) {
+                        keys.push(*physical);
+                    }
+                }
+
+                match self.match_key(*modifiers, &keys) {
+                    Some(Some(cmd)) => {
+                        triggered.push(cmd);
+                        swallow_text = true;
+                        false
+                    }
+                    Some(None) => {
+                        swallow_text = true;
+                        false
+                    }
+                    None => true,
+                }
+            });
+        });
+
+        triggered
+    }
+
+    // Some(Some(cmd)) runs a command, Some(None) swallows the key as the start
+    // of a sequence, None leaves it alone.
+    fn match_key(&mut self, modifiers: Modifiers, keys: &[Key]) -> Option<Option<Command>> {
+        if let Some(first) = self.pending.take() {
+            let second = self.bindings.iter().find(|(seq, _)| {
+                seq.0.len() == 2 && seq.0[0] == first && seq.0[1].matches(modifiers, keys)
+            });
+            if let Some((_, cmd)) = second {
+                return Some(Some(*cmd));
+            }
+        }
+
+        let single = self
+            .bindings
+            .iter()
+            .find(|(seq, _)| seq.0.len() == 1 && seq.0[0].matches(modifiers, keys));
+        if let Some((_, cmd)) = single {
+            return Some(Some(*cmd));
+        }
+
+        let prefix = self
+            .bindings
+            .iter()
+            .find(|(seq, _)| seq.0.len() == 2 && seq.0[0].matches(modifiers, keys));
+        if let Some((seq, _)) = prefix {
+            self.pending = Some(seq.0[0]);
+            return Some(None);
+        }
+
+        None
+    }
+}
+
+// Punctuation such as '?' or '|', as opposed to letters, digits and named keys
+fn is_symbol(key: Key) -> bool {
+    let mut chars = key.symbol_or_name().chars();
+    matches!((chars.next(), chars.next()), (Some(c), None) if c.is_ascii_punctuation())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Run one frame with `events` and what is left of them after the keymap
+    fn frame(modifiers: Modifiers, events: Vec<egui::Event>) -> (Vec<Command>, Vec<egui::Event>) {
+        let ctx = egui::Context::default();
+        // egui drops the modifiers when the window focus changes, which a
+        // new context counts the first frame as, unless it stays unfocused
+        ctx.begin_frame(egui::RawInput { modifiers, events, focused: false, ..Default::default() });
+        let commands = Keymap::defaults().process_input(&ctx);
+        let left = ctx.input(|i| i.events.clone());
+        let _ = ctx.end_frame();
+        (commands, left)
+    }
+
+    fn key(key: Key, modifiers: Modifiers) -> egui::Event {
+        egui::Event::Key { key, physical_key: None, pressed: true, repeat: false, modifiers }
+    }
+
+    #[test]
+    fn alt_letter_binding_types_nothing() {
+        let (commands, left) = frame(Modifiers::ALT, vec![key(Key::Z, Modifiers::ALT), egui::Event::Text("z".to_string())]);
+        assert_eq!(commands, vec![Command::ToggleWordWrap]);
+        assert!(left.is_empty());
+    }
+
+    #[test]
+    fn shifted_paste_opens_clipboard_history() {
+        // as the window reports Ctrl on Linux and Windows
+        let ctrl = Modifiers::CTRL | Modifiers::COMMAND;
+        let (commands, left) = frame(ctrl | Modifiers::SHIFT, vec![egui::Event::Paste("x".to_string())]);
+        assert_eq!(commands, vec![Command::PasteFromHistory]);
+        assert!(left.is_empty());
+
+        let (commands, left) = frame(ctrl, vec![egui::Event::Paste("x".to_string())]);
+        assert!(commands.is_empty());
+        assert_eq!(left.len(), 1);
+    }
+}
@@ -0,0 +1,109 @@
+// 查找匹配括号时最多扫描的字节数，避免大文件每帧卡顿
+const MAX_BRACKET_SCAN: usize = 200_000;
+
+const BRACKETS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+const BRACKETS_AND_QUOTES: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+// Rust uses ' for lifetimes, so only double quotes close themselves
+const RUST_PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"')];
+const JS_PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\''), ('`', '`')];
+
+/// Typing aids for one language: which brackets match, which pairs close
+/// themselves, and after which characters Enter indents one level deeper.
+#[derive(Debug, Clone, Copy)]
+pub struct LanguageRules {
+    pub brackets: &'static [(char, char)],
+    pub auto_close: &'static [(char, char)],
+    pub indent_after: &'static [char],
+}
+
+impl LanguageRules {
+    pub fn for_syntax(name: Option<&str>) -> Self {
+        let (auto_close, indent_after): (&'static [(char, char)], &'static [char]) = match name {
+            Some("Python") => (BRACKETS_AND_QUOTES, &[':', '(', '[', '{']),
+            Some("YAML") => (BRACKETS_AND_QUOTES, &[':', '[', '{']),
+            Some("Rust") => (RUST_PAIRS, &['(', '[', '{']),
+            Some("JavaScript") | Some("TypeScript") | Some("JavaScript (Babel)") => (JS_PAIRS, &['(', '[', '{']),
+            // 普通文本里引号常用作撇号，不自动补全
+            None | Some("Plain Text") | Some("Markdown") => (BRACKETS, &[]),
+            Some(_) => (BRACKETS_AND_QUOTES, &['(', '[', '{']),
+        };
+        Self { brackets: BRACKETS, auto_close, indent_after }
+    }
+
+    // The character typing `open` also inserts after the caret
+    pub fn closing(&self, open: char) -> Option<char> {
+        self.auto_close.iter().find(|(o, _)| *o == open).map(|(_, c)| *c)
+    }
+
+    pub fn is_closing(&self, c: char) -> bool {
+        self.auto_close.iter().any(|(_, close)| *close == c)
+    }
+
+    pub fn is_closing_bracket(&self, c: char) -> bool {
+        self.brackets.iter().any(|(_, close)| *close == c)
+    }
+
+    /// The bracket right after `caret`, or else right before it, together with
+    /// its partner. Both are byte offsets of the bracket characters.
+    pub fn bracket_pair_near(&self, text: &str, caret: usize) -> Option<(usize, usize)> {
+        let after = text[caret..].chars().next().map(|c| (caret, c));
+        let before = text[..caret].char_indices().next_back();
+        [after, before]
+            .into_iter()
+            .flatten()
+            .find_map(|(pos, c)| Some((pos, self.matching_bracket(text, pos, c)?)))
+    }
+
+    // Offset of the bracket matching the bracket `c` at `pos`, counting nesting
+    pub fn matching_bracket(&self, text: &str, pos: usize, c: char) -> Option<usize> {
+        if let Some(&(open, close)) = self.brackets.iter().find(|(open, _)| *open == c) {
+            let mut depth = 0;
+            for (i, ch) in text[pos..].char_indices().take_while(|(i, _)| *i < MAX_BRACKET_SCAN) {
+                if ch == open {
+                    depth += 1;
+                } else if ch == close {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(pos + i);
+                    }
+                }
+            }
+        } else if self.is_closing_bracket(c) {
+            return self.unclosed_bracket(text, pos, c);
+        }
+        None
+    }
+
+    /// The opening bracket left open before `pos` that `close` would close.
+    pub fn unclosed_bracket(&self, text: &str, pos: usize, close: char) -> Option<usize> {
+        let &(open, _) = self.brackets.iter().find(|(_, c)| *c == close)?;
+        let mut depth = 0;
+        for (i, ch) in text[..pos].char_indices().rev().take_while(|(i, _)| pos - *i < MAX_BRACKET_SCAN) {
+            if ch == close {
+                depth += 1;
+            } else if ch == open {
+                if depth == 0 {
+                    return Some(i);
+                }
+                depth -= 1;
+            }
+        }
+        None
+    }
+}
+
+// Leading spaces and tabs of the line starting at `line_start`
+pub fn line_indent(text: &str, line_start: usize) -> &str {
+    let line = &text[line_start..];
+    let end = line.find(|c: char| c != ' ' && c != '\t').unwrap_or(line.len());
+    &line[..end]
+}
+
+// One level of indentation, following what the line already uses
+pub fn indent_unit(indent: &str) -> &'static str {
+    if indent.starts_with('\t') {
+        "\t"
+    } else {
+        "    "
+    }
+}
@@ -0,0 +1,306 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use crate::commands::Command;
+use crate::cursors::{self, Selection, Splice};
+use crate::editor::Document;
+
+/// How Sort Lines compares two lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Lexicographic,
+    CaseInsensitive,
+    // by the number each line starts with; lines without one go last
+    Numeric,
+    // digit runs compare as numbers, so "file2" comes before "file10"
+    Natural,
+}
+
+/// Duplicate the selection after itself, or the caret's line below itself.
+pub fn duplicate(text: &str, sel: Selection) -> Splice {
+    if !sel.is_empty() {
+        let range = sel.range();
+        let copy = &text[range.clone()];
+        // 选中新复制出的那一份，再按一次继续复制
+        let selection = Selection::new(range.end, range.end + copy.len());
+        return Splice::new(range.end..range.end, copy.to_string(), selection);
+    }
+    let lines = cursors::selected_lines(text, sel);
+    let line = &text[lines.clone()];
+    let shift = line.len() + 1;
+    Splice::new(
+        lines.end..lines.end,
+        format!("\n{}", line),
+        Selection::new(sel.anchor + shift, sel.head + shift),
+    )
+}
+
+/// Swap the selected lines with the line above or below them. The
+/// selection, bookmarks and folds move along; None at the top or bottom of
+/// the document.
+pub fn move_lines(text: &str, sel: Selection, down: bool) -> Option<Splice> {
+    let lines = cursors::selected_lines(text, sel);
+    let block = &text[lines.clone()];
+    let count = block.matches('\n').count() + 1;
+    if down {
+        if lines.end == text.len() {
+            return None;
+        }
+        let next_end = cursors::line_end(text, lines.end + 1);
+        let next = &text[lines.end + 1..next_end];
+        let shift = next.len() + 1;
+        // 选中的行各下移一行，下面那一行移到最前
+        let line_map = (0..count).map(|i| Some(i + 1)).chain([Some(0)]).collect();
+        let splice = Splice::new(
+            lines.start..next_end,
+            format!("{}\n{}", next, block),
+            Selection::new(sel.anchor + shift, sel.head + shift),
+        );
+        Some(splice.with_line_map(line_map))
+    } else {
+        if lines.start == 0 {
+            return None;
+        }
+        let previous_start = cursors::line_start(text, lines.start - 1);
+        let previous = &text[previous_start..lines.start - 1];
+        let shift = previous.len() + 1;
+        let line_map = [Some(count)].into_iter().chain((0..count).map(Some)).collect();
+        let splice = Splice::new(
+            previous_start..lines.end,
+            format!("{}\n{}", block, previous),
+            Selection::new(sel.anchor - shift, sel.head - shift),
+        );
+        Some(splice.with_line_map(line_map))
+    }
+}
+
+/// Remove the selected lines, or the caret's line, with their line breaks.
+pub fn delete_lines(text: &str, sel: Selection) -> Option<Splice> {
+    if text.is_empty() {
+        return None;
+    }
+    let mut range = cursors::selected_lines(text, sel);
+    if range.end < text.len() {
+        range.end += 1;
+    } else if range.start > 0 {
+        // 最后一行没有换行符，删除上一行末尾的换行符
+        range.start -= 1;
+    }
+    // 光标落在下一行（或新的最后一行）的行首
+    let caret = cursors::line_start(text, range.start);
+    Some(Splice::new(range, String::new(), Selection::caret(caret)))
+}
+
+/// Join the selected lines into one, or the caret's line with the next.
+/// Leading whitespace of the joined lines is replaced by a single space.
+pub fn join_lines(text: &str, sel: Selection) -> Option<Splice> {
+    let mut range = cursors::selected_lines(text, sel);
+    if !text[range.clone()].contains('\n') {
+        if range.end == text.len() {
+            return None;
+        }
+        range.end = cursors::line_end(text, range.end + 1);
+    }
+
+    let mut joined = String::new();
+    let mut last_join = range.start;
+    for (i, line) in text[range.clone()].split('\n').enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if i == 0 {
+            joined.push_str(line.trim_end());
+            continue;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if !joined.is_empty() {
+            joined.push(' ');
+        }
+        last_join = range.start + joined.len();
+        joined.push_str(line);
+    }
+    if text[range.clone()].ends_with('\r') {
+        joined.push('\r');
+    }
+
+    let selection = if sel.is_empty() {
+        Selection::caret(last_join)
+    } else {
+        Selection::new(range.start, range.start + joined.len())
+    };
+    Some(Splice::new(range, joined, selection))
+}
+
+/// Sort the selected lines, or every line of the document.
+pub fn sort_lines(text: &str, sel: Selection, key: SortKey, descending: bool) -> Option<Splice> {
+    rewrite_lines(text, sel, |lines| {
+        // 稳定排序，相等的行保持原来的顺序
+        lines.sort_by(|(_, a), (_, b)| {
+            let (a, b) = (trim_cr(a), trim_cr(b));
+            let order = match key {
+                SortKey::Lexicographic => a.cmp(b),
+                SortKey::CaseInsensitive => a.to_lowercase().cmp(&b.to_lowercase()),
+                // 没有数字的行无论升序降序都排在最后
+                SortKey::Numeric => return numeric_cmp(a, b, descending),
+                SortKey::Natural => natural_cmp(a, b),
+            };
+            if descending { order.reverse() } else { order }
+        });
+    })
+}
+
+/// Keep only the first of identical lines.
+pub fn remove_duplicate_lines(text: &str, sel: Selection) -> Option<Splice> {
+    rewrite_lines(text, sel, |lines| {
+        let mut seen = HashSet::new();
+        lines.retain(|(_, line)| seen.insert(trim_cr(line).to_string()));
+    })
+}
+
+/// Remove lines that are empty or hold only whitespace.
+pub fn remove_empty_lines(text: &str, sel: Selection) -> Option<Splice> {
+    rewrite_lines(text, sel, |lines| lines.retain(|(_, line)| !line.trim().is_empty()))
+}
+
+pub fn reverse_lines(text: &str, sel: Selection) -> Option<Splice> {
+    rewrite_lines(text, sel, |lines| lines.reverse())
+}
+
+// Hand the selected lines (or all of them) to `rewrite`, each with its index,
+// and put the result back. Bookmarks and folds go wherever their line went.
+// The new lines stay selected; without a selection the caret keeps its line
+// number. None when nothing changed.
+fn rewrite_lines(text: &str, sel: Selection, rewrite: impl FnOnce(&mut Vec<(usize, &str)>)) -> Option<Splice> {
+    let range = if sel.is_empty() {
+        // 整个文档，末尾的换行符不算一个空行
+        0..text.strip_suffix('\n').unwrap_or(text).len()
+    } else {
+        cursors::selected_lines(text, sel)
+    };
+    let old = &text[range.clone()];
+    let mut lines: Vec<(usize, &str)> = old.split('\n').enumerate().collect();
+    let count = lines.len();
+    rewrite(&mut lines);
+    let new = lines.iter().map(|(_, line)| *line).collect::<Vec<_>>().join("\n");
+    if new == old {
+        return None;
+    }
+    let mut line_map = vec![None; count];
+    for (to, (from, _)) in lines.iter().enumerate() {
+        line_map[*from] = Some(to);
+    }
+
+    let selection = if sel.is_empty() {
+        let line = text[..sel.head].matches('\n').count();
+        let caret = cursors::line_starts(&new).nth(line).unwrap_or(new.len());
+        Selection::caret(caret)
+    } else {
+        Selection::new(range.start, range.start + new.len())
+    };
+    Some(Splice::new(range, new, selection).with_line_map(line_map))
+}
+
+fn trim_cr(line: &str) -> &str {
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+// The number a line starts with, after leading whitespace
+fn leading_number(line: &str) -> Option<f64> {
+    let line = line.trim_start();
+    let bytes = line.as_bytes();
+    let mut end = usize::from(matches!(bytes.first(), Some(b'-' | b'+')));
+    let digits_start = end;
+    let mut seen_dot = false;
+    while let Some(&b) = bytes.get(end) {
+        match b {
+            b'0'..=b'9' => end += 1,
+            b'.' if !seen_dot => {
+                seen_dot = true;
+                end += 1;
+            }
+            _ => break,
+        }
+    }
+    if !line[digits_start..end].bytes().any(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    line[..end].trim_end_matches('.').parse().ok()
+}
+
+fn numeric_cmp(a: &str, b: &str, descending: bool) -> Ordering {
+    let order = match (leading_number(a), leading_number(b)) {
+        (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal).then_with(|| a.cmp(b)),
+        (Some(_), None) => return Ordering::Less,
+        (None, Some(_)) => return Ordering::Greater,
+        (None, None) => a.cmp(b),
+    };
+    if descending { order.reverse() } else { order }
+}
+
+// Compare runs of digits by value and everything else ignoring case
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut x, mut y) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (x.peek().copied(), y.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(c), Some(d)) if c.is_ascii_digit() && d.is_ascii_digit() => {
+                let number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+                        digits.push(c);
+                    }
+                    digits
+                };
+                let (m, n) = (number(&mut x), number(&mut y));
+                let (m, n) = (m.trim_start_matches('0'), n.trim_start_matches('0'));
+                // 去掉前导零后，位数多的数更大
+                let order = m.len().cmp(&n.len()).then_with(|| m.cmp(n));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            (Some(c), Some(d)) => {
+                let order = c.to_lowercase().cmp(d.to_lowercase());
+                if order != Ordering::Equal {
+                    return order;
+                }
+                x.next();
+                y.next();
+            }
+        }
+    }
+}
+
+// Line commands, shared by the main window and extra windows. Each run is
+// a step of its own on the undo stack, so pressing Move Line Down three times
+// takes three Undos to get back.
+pub fn execute(doc: &mut Document, cmd: Command) {
+    let text = doc.content.as_str();
+    let sel = doc.primary_selection();
+    let splice = match cmd {
+        Command::DuplicateLine => Some(duplicate(text, sel)),
+        Command::MoveLineUp => move_lines(text, sel, false),
+        Command::MoveLineDown => move_lines(text, sel, true),
+        Command::DeleteLine => delete_lines(text, sel),
+        Command::JoinLines => join_lines(text, sel),
+        Command::SortLinesAscending => sort_lines(text, sel, SortKey::Lexicographic, false),
+        Command::SortLinesDescending => sort_lines(text, sel, SortKey::Lexicographic, true),
+        Command::SortLinesIgnoreCaseAscending => sort_lines(text, sel, SortKey::CaseInsensitive, false),
+        Command::SortLinesIgnoreCaseDescending => sort_lines(text, sel, SortKey::CaseInsensitive, true),
+        Command::SortLinesNumericAscending => sort_lines(text, sel, SortKey::Numeric, false),
+        Command::SortLinesNumericDescending => sort_lines(text, sel, SortKey::Numeric, true),
+        Command::SortLinesNaturalAscending => sort_lines(text, sel, SortKey::Natural, false),
+        Command::SortLinesNaturalDescending => sort_lines(text, sel, SortKey::Natural, true),
+        Command::RemoveDuplicateLines => remove_duplicate_lines(text, sel),
+        Command::RemoveEmptyLines => remove_empty_lines(text, sel),
+        Command::ReverseLines => reverse_lines(text, sel),
+        _ => return,
+    };
+    if let Some(splice) = splice {
+        doc.apply_splice(splice);
+    }
+    doc.request_focus();
+}
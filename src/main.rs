@@ -1,6 +1,5 @@
 use eframe::egui;
-// 移除未使用的导入
-// use std::path::PathBuf;
+use std::path::PathBuf;
 
 mod app;
 mod editor;
@@ -8,21 +7,92 @@ mod syntax;
 mod theme;
 mod ui;
 mod config;
+mod commands;
+mod keymap;
+mod fuzzy;
+mod picker;
+mod quick_open;
+mod file_tree;
+mod minimap;
+mod outline;
+mod panes;
+mod tabs;
+mod windows;
+mod session;
+mod parser_rules;
+mod clipboard;
+mod cursors;
+mod column_editor;
+mod folding;
+mod gutter;
+mod bookmarks;
+mod language;
+mod comments;
+mod line_ops;
+mod convert;
+mod history;
+mod analysis;
+#[cfg(unix)]
+mod instance;
+#[cfg(unix)]
+mod rpc;
 
 fn main() -> Result<(), eframe::Error> {
     // initialize logger
     env_logger::init();
-    
+
+    // files passed on the command line, made absolute so another instance can open them
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let files: Vec<PathBuf> = std::env::args_os()
+        .skip(1)
+        .map(|arg| {
+            let path = cwd.join(arg);
+            path.canonicalize().unwrap_or(path)
+        })
+        .collect();
+
+    #[cfg(unix)]
+    let listener = match instance::Instance::acquire(&files) {
+        instance::Instance::Primary(listener) => listener,
+        instance::Instance::Forwarded => {
+            log::info!("Handed {} file(s) over to the running instance", files.len());
+            return Ok(());
+        }
+    };
+
+    let config = config::Config::load().unwrap_or_default();
+    let layout = &config.window;
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_inner_size(layout.size)
+        .with_min_inner_size([400.0, 300.0])
+        .with_maximized(layout.maximized);
+    if let Some(position) = layout.position {
+        viewport = viewport.with_position(position);
+    }
+
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([1280.0, 720.0])
-            .with_min_inner_size([400.0, 300.0]),
+        viewport,
         ..Default::default()
     };
-    
+
     eframe::run_native(
         "Notion++",
         options,
-        Box::new(|cc| Box::new(app::NotionApp::new(cc)))
+        Box::new(move |cc| {
+            let mut app = app::NotionApp::new(cc, config);
+            app.restore_session();
+            #[cfg(unix)]
+            if let Some(listener) = listener {
+                app.set_instance_receiver(listener.spawn(cc.egui_ctx.clone()));
+            }
+            for file in &files {
+                if file.is_dir() {
+                    app.open_folder(&cc.egui_ctx, file);
+                } else if let Err(err) = app.open_path(file) {
+                    log::error!("Failed to open {}: {}", file.display(), err);
+                }
+            }
+            Box::new(app)
+        })
     )
-} 
\ No newline at end of file
+}
@@ -0,0 +1,186 @@
+use eframe::egui::{self, Color32, Rect, Sense};
+use std::sync::Arc;
+use syntect::highlighting::{HighlightIterator, HighlightState, Highlighter, ThemeSet};
+use syntect::parsing::ScopeStack;
+use syntect::util::LinesWithEndings;
+
+use crate::analysis::{Background, LineOps};
+use crate::editor::Document;
+
+// size of one line and one character on the map, in points
+const LINE_HEIGHT: f32 = 3.0;
+const CHAR_WIDTH: f32 = 1.5;
+// characters past this column are not drawn
+const MAX_COLUMNS: usize = 240;
+
+// A run of non-blank characters drawn as one bar; without a color it is
+// plain text, drawn in the text color of the current visuals
+struct MapSpan {
+    column: usize,
+    len: usize,
+    color: Option<Color32>,
+}
+
+// What the map lines were built from: the document's analysis and how it is drawn
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MapKey {
+    revision: u64,
+    syntax: Option<String>,
+    dark_mode: bool,
+    tab_width: usize,
+}
+
+/// Scaled-down picture of the whole document for the Document Map panel.
+/// Clicking jumps there, dragging the viewport box scrolls the editor.
+pub struct Minimap {
+    // lines colored in the background from the document's analysis
+    lines: Background<MapKey, Vec<Vec<MapSpan>>>,
+}
+
+impl Minimap {
+    pub fn new() -> Self {
+        Self { lines: Background::new() }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, doc: &mut Document) {
+        let dark_mode = ui.visuals().dark_mode;
+        let plain_color = ui.visuals().text_color();
+        let lines = self.lines(ui.ctx(), doc, dark_mode);
+
+        let (rect, response) = ui.allocate_exact_size(ui.available_size(), Sense::click_and_drag());
+        let painter = ui.painter_at(rect);
+
+        let map_height = lines.len() as f32 * LINE_HEIGHT;
+        let content_height = doc.content_height.max(1.0);
+        let max_scroll = (doc.content_height - doc.viewport_height).max(0.0);
+        let scroll = doc.scroll_offset.clamp(0.0, max_scroll);
+        let view_top = scroll / content_height;
+        let view_fraction = (doc.viewport_height / content_height).clamp(0.0, 1.0);
+
+        // a map taller than the panel scrolls along with the editor
+        let overflow = (map_height - rect.height()).max(0.0);
+        let map_offset = if max_scroll > 0.0 { overflow * scroll / max_scroll } else { 0.0 };
+
+        let first = (map_offset / LINE_HEIGHT) as usize;
+        let last = (((map_offset + rect.height()) / LINE_HEIGHT).ceil() as usize).min(lines.len());
+        for (i, spans) in lines.iter().enumerate().take(last).skip(first) {
+            let y = rect.top() + i as f32 * LINE_HEIGHT - map_offset;
+            for span in spans {
+                let x = rect.left() + span.column as f32 * CHAR_WIDTH;
+                if x > rect.right() {
+                    break;
+                }
+                let bar = Rect::from_min_size(
+                    egui::pos2(x, y),
+                    egui::vec2(span.len as f32 * CHAR_WIDTH, LINE_HEIGHT - 1.0),
+                );
+                painter.rect_filled(bar, 0.0, span.color.unwrap_or(plain_color).gamma_multiply(0.8));
+            }
+        }
+
+        // box over the lines that are visible in the editor
+        let slider_height = (view_fraction * map_height).max(LINE_HEIGHT * 2.0);
+        let slider_top = rect.top() + view_top * map_height - map_offset;
+        let slider = Rect::from_min_size(
+            egui::pos2(rect.left(), slider_top),
+            egui::vec2(rect.width(), slider_height),
+        );
+        let visuals = ui.visuals();
+        let fill = if response.dragged() || response.hovered() {
+            visuals.selection.bg_fill.gamma_multiply(0.35)
+        } else {
+            visuals.selection.bg_fill.gamma_multiply(0.2)
+        };
+        painter.rect(slider, 0.0, fill, visuals.widgets.noninteractive.bg_stroke);
+
+        let Some(pointer) = response.interact_pointer_pos() else {
+            return;
+        };
+        if response.drag_started() || response.clicked() {
+            if !slider.contains(pointer) {
+                // centre the viewport on the clicked line
+                let fraction = ((pointer.y - rect.top() + map_offset) / map_height.max(1.0)).clamp(0.0, 1.0);
+                doc.scroll_offset = (fraction * content_height - doc.viewport_height / 2.0).clamp(0.0, max_scroll);
+            }
+        } else if response.dragged() {
+            // the slider moves over the visible part of the map
+            let track = map_height.min(rect.height()) - slider_height;
+            if track > 0.0 {
+                doc.scroll_offset = (scroll + response.drag_delta().y * max_scroll / track).clamp(0.0, max_scroll);
+            }
+        }
+    }
+
+    // Lines of the latest map of this document, which may be from before
+    // the latest edits; a new one is made once the document is analysed again
+    fn lines(&mut self, ctx: &egui::Context, doc: &Document, dark_mode: bool) -> &[Vec<MapSpan>] {
+        self.lines.poll();
+        if let Some((key, analysis)) = doc.analysis() {
+            let map_key = MapKey { revision: key.revision, syntax: key.syntax.clone(), dark_mode, tab_width: doc.tab_width };
+            if self.lines.wants(&map_key) {
+                let text = Arc::clone(&analysis.text);
+                let ops = Arc::clone(&analysis.ops);
+                let theme_set = Arc::clone(doc.theme_set());
+                let highlight = map_key.syntax.is_some();
+                let tab_width = doc.tab_width.max(1);
+                self.lines.start(ctx, map_key, move || build_lines(&text, highlight.then_some(&*ops), &theme_set, dark_mode, tab_width));
+            }
+        }
+        match self.lines.get() {
+            Some((key, lines)) if doc.had_revision(key.revision) => lines,
+            _ => &[],
+        }
+    }
+}
+
+// 没有语法时整篇都是普通文字
+fn build_lines(text: &str, ops: Option<&LineOps>, theme_set: &ThemeSet, dark_mode: bool, tab_width: usize) -> Vec<Vec<MapSpan>> {
+    let Some(ops) = ops else {
+        return text.lines().map(|line| line_spans([(None, line)], tab_width)).collect();
+    };
+
+    let theme_name = if dark_mode { "base16-ocean.dark" } else { "InspiredGitHub" };
+    let highlighter = Highlighter::new(&theme_set.themes[theme_name]);
+    let mut state = HighlightState::new(&highlighter, ScopeStack::new());
+
+    // lines after the parser gave up stay plain
+    LinesWithEndings::from(text)
+        .enumerate()
+        .map(|(line_no, line)| match ops.get(line_no) {
+            Some(ops) => line_spans(
+                HighlightIterator::new(&mut state, ops, line, &highlighter).map(|(style, text)| {
+                    let c = style.foreground;
+                    (Some(Color32::from_rgba_unmultiplied(c.r, c.g, c.b, c.a)), text)
+                }),
+                tab_width,
+            ),
+            None => line_spans([(None, line)], tab_width),
+        })
+        .collect()
+}
+
+// Split colored text into bars, leaving gaps where the whitespace is
+fn line_spans<'a>(regions: impl IntoIterator<Item = (Option<Color32>, &'a str)>, tab_width: usize) -> Vec<MapSpan> {
+    let mut spans: Vec<MapSpan> = Vec::new();
+    let mut column = 0;
+    for (color, text) in regions {
+        for c in text.chars() {
+            if column >= MAX_COLUMNS {
+                return spans;
+            }
+            match c {
+                '\n' | '\r' => {}
+                '\t' => column += tab_width - column % tab_width,
+                c if c.is_whitespace() => column += 1,
+                _ => {
+                    match spans.last_mut() {
+                        Some(last) if last.color == color && last.column + last.len == column => last.len += 1,
+                        _ => spans.push(MapSpan { column, len: 1, color }),
+                    }
+                    column += 1;
+                }
+            }
+        }
+    }
+    spans
+}
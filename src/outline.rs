@@ -0,0 +1,434 @@
+use eframe::egui;
+use std::sync::Arc;
+use syntect::parsing::{Scope, ScopeStack};
+use syntect::util::LinesWithEndings;
+
+use crate::analysis::{Background, LineOps};
+use crate::editor::Document;
+use crate::fuzzy::fuzzy_match;
+use crate::parser_rules::ParserRules;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Method,
+    Class,
+    Struct,
+    Enum,
+    Trait,
+    Impl,
+    Module,
+    Type,
+    Macro,
+}
+
+impl SymbolKind {
+    // Map the last part of an `entity.name.*` scope to a kind
+    fn from_scope_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "function" => Self::Function,
+            "class" => Self::Class,
+            "struct" => Self::Struct,
+            "enum" => Self::Enum,
+            "trait" | "interface" => Self::Trait,
+            "impl" => Self::Impl,
+            "module" | "namespace" => Self::Module,
+            "type" => Self::Type,
+            "macro" => Self::Macro,
+            _ => return None,
+        })
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            Self::Function => "ƒ",
+            Self::Method => "m",
+            Self::Class => "C",
+            Self::Struct => "S",
+            Self::Enum => "E",
+            Self::Trait => "I",
+            Self::Impl => "⊞",
+            Self::Module => "{}",
+            Self::Type => "T",
+            Self::Macro => "!",
+        }
+    }
+
+    pub fn color(self) -> egui::Color32 {
+        match self {
+            Self::Function | Self::Method => egui::Color32::from_rgb(180, 120, 220),
+            Self::Class | Self::Struct | Self::Type => egui::Color32::from_rgb(230, 160, 60),
+            Self::Enum => egui::Color32::from_rgb(200, 170, 60),
+            Self::Trait | Self::Impl => egui::Color32::from_rgb(80, 150, 220),
+            Self::Module | Self::Macro => egui::Color32::from_rgb(110, 170, 110),
+        }
+    }
+
+    // Kinds whose body can contain other symbols
+    fn is_container(self) -> bool {
+        matches!(
+            self,
+            Self::Class | Self::Struct | Self::Enum | Self::Trait | Self::Impl | Self::Module
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    // zero-based line of the definition
+    pub line: usize,
+    pub children: Vec<Symbol>,
+}
+
+// A symbol before nesting, with the last line of its body
+struct FlatSymbol {
+    name: String,
+    kind: SymbolKind,
+    line: usize,
+    end_line: usize,
+}
+
+/// Definitions found through the `entity.name.*` scopes of the syntax, so names
+/// inside comments and strings are never picked up. `line_ops` comes from
+/// parsing `content`, see `analysis::parse_lines`.
+pub fn extract_symbols(content: &str, line_ops: &LineOps) -> Vec<Symbol> {
+    let entity_name = Scope::new("entity.name").unwrap();
+    let comment = Scope::new("comment").unwrap();
+    let string = Scope::new("string").unwrap();
+
+    let mut stack = ScopeStack::new();
+    let mut found: Vec<FoundName> = Vec::new();
+    // brace depth at the start of each line, plus one entry for the end of the text
+    let mut depths = vec![0i32];
+    let mut depth = 0i32;
+    // whether a line opens a brace outside comments and strings
+    let mut opens = Vec::new();
+
+    for ((line_no, line), ops) in LinesWithEndings::from(content).enumerate().zip(line_ops) {
+        let mut last = 0;
+        let mut opened = false;
+        let mut ops = ops.iter().peekable();
+        loop {
+            let next = ops.peek().map(|(pos, _)| *pos).unwrap_or(line.len());
+            if next > last {
+                let token = &line[last..next];
+                let scopes = stack.as_slice();
+                let in_text = scopes.iter().any(|s| comment.is_prefix_of(*s) || string.is_prefix_of(*s));
+
+                if let Some(scope) = scopes.iter().rev().find(|s| entity_name.is_prefix_of(**s)) {
+                    let kind = scope.build_string().split('.').nth(2).and_then(SymbolKind::from_scope_name);
+                    if let Some(kind) = kind {
+                        push_symbol(&mut found, token, last, kind, line_no, line);
+                    }
+                }
+
+                if !in_text {
+                    for c in token.chars() {
+                        match c {
+                            '{' => {
+                                depth += 1;
+                                opened = true;
+                            }
+                            '}' => depth -= 1,
+                            _ => {}
+                        }
+                    }
+                }
+                last = next;
+            }
+
+            match ops.next() {
+                Some((_, op)) => {
+                    if stack.apply(op).is_err() {
+                        break;
+                    }
+                }
+                None => break,
+            }
+        }
+
+        depths.push(depth);
+        opens.push(opened);
+    }
+
+    // only the lines that were actually parsed have a depth
+    let lines: Vec<&str> = content.lines().take(depths.len() - 1).collect();
+    let flat = found
+        .into_iter()
+        .map(|found| {
+            let end_line = if found.kind.is_container() {
+                body_end(&lines, &depths, &opens, found.line)
+            } else {
+                found.line
+            };
+            FlatSymbol { name: found.name, kind: found.kind, line: found.line, end_line }
+        })
+        .collect();
+
+    nest(flat)
+}
+
+// A name found in the text, before its body range is known
+struct FoundName {
+    name: String,
+    kind: SymbolKind,
+    line: usize,
+    // byte column right after the name, to glue split tokens back together
+    end: usize,
+}
+
+fn push_symbol(found: &mut Vec<FoundName>, token: &str, start: usize, kind: SymbolKind, line_no: usize, line: &str) {
+    if let Some(last) = found.last_mut() {
+        if last.line == line_no && last.kind == kind && (last.end == start || kind == SymbolKind::Impl) {
+            if kind != SymbolKind::Impl {
+                last.name.push_str(token);
+            }
+            last.end = start + token.len();
+            return;
+        }
+    }
+
+    let name = if kind == SymbolKind::Impl {
+        // `impl Trait for Type` reads better than just the type name
+        line.split('{').next().unwrap_or(line).trim().to_string()
+    } else {
+        token.trim().to_string()
+    };
+    if !name.is_empty() {
+        found.push(FoundName { name, kind, line: line_no, end: start + token.len() });
+    }
+}
+
+/// Last line of the body that starts at `line`: where the brace opened on that
+/// line (or alone on the next one) closes, or for brace-less languages the last
+/// line that is indented deeper than the definition.
+fn body_end(lines: &[&str], depths: &[i32], opens: &[bool], line: usize) -> usize {
+    let start_depth = depths[line];
+    let opens_on = if opens[line] {
+        Some(line)
+    } else if lines.get(line + 1).map(|l| l.trim_start().starts_with('{')).unwrap_or(false) {
+        Some(line + 1)
+    } else {
+        None
+    };
+
+    if let Some(open_line) = opens_on {
+        return (open_line..lines.len())
+            .find(|&l| depths[l + 1] <= start_depth)
+            .unwrap_or(lines.len().saturating_sub(1));
+    }
+
+    let indent = indentation(lines[line]);
+    let mut end = line;
+    for (l, text) in lines.iter().enumerate().skip(line + 1) {
+        if text.trim().is_empty() {
+            continue;
+        }
+        if indentation(text) <= indent {
+            break;
+        }
+        end = l;
+    }
+    end
+}
+
+pub fn indentation(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+fn nest(flat: Vec<FlatSymbol>) -> Vec<Symbol> {
+    let mut roots: Vec<Symbol> = Vec::new();
+    // containers we are inside: last line, kind and index path from the roots
+    let mut open: Vec<(usize, SymbolKind, Vec<usize>)> = Vec::new();
+
+    for sym in flat {
+        while open.last().map(|(end, _, _)| *end < sym.line).unwrap_or(false) {
+            open.pop();
+        }
+
+        let mut kind = sym.kind;
+        let siblings = match open.last() {
+            Some((_, parent_kind, path)) => {
+                let in_type = matches!(parent_kind, SymbolKind::Class | SymbolKind::Impl | SymbolKind::Trait);
+                if kind == SymbolKind::Function && in_type {
+                    kind = SymbolKind::Method;
+                }
+                &mut node_at(&mut roots, path).children
+            }
+            None => &mut roots,
+        };
+        siblings.push(Symbol {
+            name: sym.name,
+            kind,
+            line: sym.line,
+            children: Vec::new(),
+        });
+
+        if kind.is_container() {
+            let mut path = open.last().map(|(_, _, p)| p.clone()).unwrap_or_default();
+            path.push(siblings.len() - 1);
+            open.push((sym.end_line, kind, path));
+        }
+    }
+
+    roots
+}
+
+fn node_at<'a>(roots: &'a mut [Symbol], path: &[usize]) -> &'a mut Symbol {
+    let mut node = &mut roots[path[0]];
+    for &i in &path[1..] {
+        node = &mut node.children[i];
+    }
+    node
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutlineSort {
+    Position,
+    Name,
+}
+
+/// State of the Function List panel: filter, sort order and the symbols a
+/// user parser found, worked out in the background for a document revision
+/// and parser id. Symbols by the syntax come with the document's analysis.
+pub struct FunctionList {
+    filter: String,
+    sort: OutlineSort,
+    parsed: Background<(u64, String), Vec<Symbol>>,
+    rules: ParserRules,
+}
+
+impl FunctionList {
+    pub fn new() -> Self {
+        Self {
+            filter: String::new(),
+            sort: OutlineSort::Position,
+            parsed: Background::new(),
+            rules: ParserRules::default(),
+        }
+    }
+
+    pub fn set_rules(&mut self, rules: ParserRules) {
+        self.rules = rules;
+        // 规则变了，旧的结果作废
+        self.parsed = Background::new();
+    }
+
+    /// Draws the outline and returns the line of the symbol the user clicked.
+    pub fn show(&mut self, ui: &mut egui::Ui, doc: &Document) -> Option<usize> {
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.filter)
+                    .hint_text("Filter")
+                    .desired_width(ui.available_width() - 60.0),
+            );
+            let label = match self.sort {
+                OutlineSort::Position => "1→9",
+                OutlineSort::Name => "A→Z",
+            };
+            if ui.small_button(label).on_hover_text("Sort by position or name").clicked() {
+                self.sort = match self.sort {
+                    OutlineSort::Position => OutlineSort::Name,
+                    OutlineSort::Name => OutlineSort::Position,
+                };
+            }
+        });
+        ui.separator();
+
+        // a user parser for the extension wins over the syntax scopes
+        let extension = doc.path.as_ref()
+            .and_then(|path| path.extension())
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("");
+        let parser = self.rules.parser_for(extension);
+        if parser.is_none() && doc.syntax.is_none() {
+            ui.weak("No symbols for plain text");
+            return None;
+        }
+
+        // Until the latest text is parsed, symbols from an earlier revision of
+        // the same document stand in for it
+        let mut symbols = match parser {
+            Some(parser) => {
+                self.parsed.poll();
+                let key = (doc.revision(), parser.id.clone());
+                if self.parsed.wants(&key) {
+                    let parser = Arc::clone(parser);
+                    let content = doc.content.clone();
+                    self.parsed.start(ui.ctx(), key, move || parser.extract_symbols(&content));
+                }
+                match self.parsed.get() {
+                    Some(((revision, id), symbols)) if *id == parser.id && doc.had_revision(*revision) => symbols.clone(),
+                    _ => Vec::new(),
+                }
+            }
+            None => doc.analysis().map(|(_, analysis)| analysis.symbols.clone()).unwrap_or_default(),
+        };
+        if self.sort == OutlineSort::Name {
+            sort_by_name(&mut symbols);
+        }
+
+        let mut jump_to = None;
+        egui::ScrollArea::vertical()
+            .id_source("function_list_scroll")
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                let mut shown = 0;
+                for symbol in &symbols {
+                    shown += self.show_symbol(ui, symbol, &mut jump_to);
+                }
+                if shown == 0 {
+                    ui.weak("No symbols found");
+                }
+            });
+        jump_to
+    }
+
+    // Returns how many symbols were drawn, so filtered-out branches can be skipped
+    fn show_symbol(&self, ui: &mut egui::Ui, symbol: &Symbol, jump_to: &mut Option<usize>) -> usize {
+        if !self.matches_filter(symbol) {
+            return 0;
+        }
+
+        let label = |ui: &mut egui::Ui, jump_to: &mut Option<usize>| {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(symbol.kind.icon()).monospace().color(symbol.kind.color()));
+                if ui.selectable_label(false, &symbol.name).on_hover_text(format!("Line {}", symbol.line + 1)).clicked() {
+                    *jump_to = Some(symbol.line);
+                }
+            });
+        };
+
+        if symbol.children.is_empty() {
+            label(ui, jump_to);
+        } else {
+            let id = ui.make_persistent_id(("symbol", &symbol.name, symbol.line));
+            egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, true)
+                .show_header(ui, |ui| label(ui, jump_to))
+                .body(|ui| {
+                    for child in &symbol.children {
+                        self.show_symbol(ui, child, jump_to);
+                    }
+                });
+        }
+        1
+    }
+
+    fn matches_filter(&self, symbol: &Symbol) -> bool {
+        self.filter.is_empty()
+            || fuzzy_match(&self.filter, &symbol.name).is_some()
+            || symbol.children.iter().any(|child| self.matches_filter(child))
+    }
+}
+
+fn sort_by_name(symbols: &mut [Symbol]) {
+    symbols.sort_by_key(|s| s.name.to_lowercase());
+    for symbol in symbols {
+        sort_by_name(&mut symbol.children);
+    }
+}
@@ -0,0 +1,174 @@
+use eframe::egui::{self, Rect};
+
+use crate::editor::{DocumentCollection, ViewState};
+
+// gap between panes, in points
+const PANE_GAP: f32 = 6.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitDirection {
+    // panes side by side
+    Horizontal,
+    // panes stacked on top of each other
+    Vertical,
+}
+
+pub struct Pane {
+    pub document: Option<usize>,
+    pub view: ViewState,
+}
+
+/// The editor panes in the central area, all split along one direction.
+/// The active pane's document index lives in `NotionApp::active_document_index`
+/// and its view in the document itself, so everything that works on "the
+/// current document" keeps doing so. The other panes keep a snapshot here.
+pub struct EditorPanes {
+    panes: Vec<Pane>,
+    active: usize,
+    direction: SplitDirection,
+}
+
+impl EditorPanes {
+    pub fn new() -> Self {
+        Self {
+            panes: vec![Pane { document: None, view: ViewState::default() }],
+            active: 0,
+            direction: SplitDirection::Horizontal,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.panes.len()
+    }
+
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Pane> {
+        self.panes.get_mut(index)
+    }
+
+    // Open the active document in a new pane next to the active one. Splitting
+    // the other way turns the whole layout around.
+    pub fn split(&mut self, direction: SplitDirection, active_document: Option<usize>, documents: &DocumentCollection) {
+        self.store_active(active_document, documents);
+        let copy = Pane {
+            document: active_document,
+            view: self.panes[self.active].view.clone(),
+        };
+        self.panes.insert(self.active + 1, copy);
+        self.active += 1;
+        self.direction = direction;
+    }
+
+    pub fn close_active(&mut self, active_document: &mut Option<usize>, documents: &mut DocumentCollection) {
+        if self.panes.len() < 2 {
+            return;
+        }
+        self.panes.remove(self.active);
+        self.active = self.active.min(self.panes.len() - 1);
+        self.load_active(active_document, documents);
+    }
+
+    pub fn activate(&mut self, index: usize, active_document: &mut Option<usize>, documents: &mut DocumentCollection) {
+        if index == self.active || index >= self.panes.len() {
+            return;
+        }
+        self.store_active(*active_document, documents);
+        self.active = index;
+        self.load_active(active_document, documents);
+    }
+
+    pub fn focus_next(&mut self, active_document: &mut Option<usize>, documents: &mut DocumentCollection) {
+        let next = (self.active + 1) % self.panes.len();
+        self.activate(next, active_document, documents);
+    }
+
+    // Keep the other panes pointing at the right documents after one was closed
+    pub fn document_closed(&mut self, index: usize, remaining: usize) {
+        for (i, pane) in self.panes.iter_mut().enumerate() {
+            if i != self.active {
+                pane.document = index_after_close(pane.document, index, remaining);
+            }
+        }
+    }
+
+    pub fn document_inserted(&mut self, index: usize) {
+        for (i, pane) in self.panes.iter_mut().enumerate() {
+            if i != self.active {
+                pane.document = pane.document.map(|doc| if doc >= index { doc + 1 } else { doc });
+            }
+        }
+    }
+
+    pub fn document_moved(&mut self, from: usize, to: usize) {
+        for (i, pane) in self.panes.iter_mut().enumerate() {
+            if i != self.active {
+                pane.document = pane.document.map(|doc| index_after_move(doc, from, to));
+            }
+        }
+    }
+
+    // Screen areas of the panes inside `rect`
+    pub fn layout(&self, rect: Rect) -> Vec<Rect> {
+        let count = self.panes.len() as f32;
+        let total_gap = PANE_GAP * (count - 1.0);
+        (0..self.panes.len())
+            .map(|i| {
+                let i = i as f32;
+                match self.direction {
+                    SplitDirection::Horizontal => {
+                        let width = (rect.width() - total_gap) / count;
+                        let left = rect.left() + i * (width + PANE_GAP);
+                        Rect::from_min_size(egui::pos2(left, rect.top()), egui::vec2(width, rect.height()))
+                    }
+                    SplitDirection::Vertical => {
+                        let height = (rect.height() - total_gap) / count;
+                        let top = rect.top() + i * (height + PANE_GAP);
+                        Rect::from_min_size(egui::pos2(rect.left(), top), egui::vec2(rect.width(), height))
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn store_active(&mut self, active_document: Option<usize>, documents: &DocumentCollection) {
+        let pane = &mut self.panes[self.active];
+        pane.document = active_document;
+        if let Some(doc) = active_document.and_then(|i| documents.get(i)) {
+            pane.view = doc.view_state();
+        }
+    }
+
+    fn load_active(&self, active_document: &mut Option<usize>, documents: &mut DocumentCollection) {
+        let pane = &self.panes[self.active];
+        *active_document = pane.document;
+        if let Some(doc) = pane.document.and_then(|i| documents.get_mut(i)) {
+            doc.set_view_state(&pane.view);
+        }
+    }
+}
+
+// Which document a view should show once document `closed` is gone
+pub fn index_after_close(current: Option<usize>, closed: usize, remaining: usize) -> Option<usize> {
+    match current {
+        _ if remaining == 0 => None,
+        Some(i) if i > closed => Some(i - 1),
+        Some(i) if i == closed => Some(i.min(remaining - 1)),
+        other => other,
+    }
+}
+
+// Where index `current` ends up when the document at `from` is moved to `to`
+pub fn index_after_move(current: usize, from: usize, to: usize) -> usize {
+    if current == from {
+        to
+    } else if from < to && current > from && current <= to {
+        current - 1
+    } else if to < from && current >= to && current < from {
+        current + 1
+    } else {
+        current
+    }
+}
@@ -0,0 +1,289 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::fs;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::outline::{Symbol, SymbolKind};
+
+const RULES_FILE: &str = "function_list.json";
+
+// On-disk format, modelled on Notepad++'s functionList parsers
+#[derive(Deserialize)]
+struct RulesFile {
+    parsers: Vec<ParserSpec>,
+}
+
+#[derive(Deserialize)]
+struct ParserSpec {
+    id: String,
+    extensions: Vec<String>,
+    // anything matched here is ignored by the other expressions
+    #[serde(default)]
+    comment: Option<String>,
+    #[serde(default)]
+    class_range: Option<ClassRangeSpec>,
+    #[serde(default)]
+    function: Option<FunctionSpec>,
+}
+
+#[derive(Deserialize)]
+struct ClassRangeSpec {
+    main: String,
+    #[serde(default)]
+    name: Vec<String>,
+    // body delimiters; without them a class runs until the next class header
+    #[serde(default)]
+    open: Option<String>,
+    #[serde(default)]
+    close: Option<String>,
+    #[serde(default)]
+    function: Option<FunctionSpec>,
+}
+
+#[derive(Deserialize)]
+struct FunctionSpec {
+    main: String,
+    #[serde(default)]
+    name: Vec<String>,
+}
+
+struct NameRule {
+    main: Regex,
+    // applied one after another to narrow the main match down to the name
+    name: Vec<Regex>,
+}
+
+struct ClassRule {
+    header: NameRule,
+    delimiters: Option<(String, String)>,
+    method: Option<NameRule>,
+}
+
+pub struct Parser {
+    pub id: String,
+    extensions: Vec<String>,
+    comment: Option<Regex>,
+    class_range: Option<ClassRule>,
+    function: Option<NameRule>,
+}
+
+/// Function list parsers defined by the user in `function_list.json`.
+#[derive(Default)]
+pub struct ParserRules {
+    // shared with the outline's worker thread
+    parsers: Vec<Arc<Parser>>,
+}
+
+impl ParserRules {
+    pub fn load() -> Self {
+        let Some(path) = Config::config_dir().map(|dir| dir.join(RULES_FILE)) else {
+            return Self::default();
+        };
+        if !path.exists() {
+            return Self::default();
+        }
+
+        let spec = fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| serde_json::from_str::<RulesFile>(&text).map_err(anyhow::Error::from));
+
+        let spec = match spec {
+            Ok(spec) => spec,
+            Err(err) => {
+                log::error!("Failed to load function list rules {}: {}", path.display(), err);
+                return Self::default();
+            }
+        };
+
+        let parsers = spec
+            .parsers
+            .into_iter()
+            .filter_map(|parser| {
+                let id = parser.id.clone();
+                match Parser::compile(parser) {
+                    Ok(parser) => Some(Arc::new(parser)),
+                    Err(err) => {
+                        log::error!("Invalid function list parser \"{}\": {}", id, err);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Self { parsers }
+    }
+
+    pub fn parser_for(&self, extension: &str) -> Option<&Arc<Parser>> {
+        self.parsers
+            .iter()
+            .find(|p| p.extensions.iter().any(|e| e.eq_ignore_ascii_case(extension)))
+    }
+}
+
+impl Parser {
+    fn compile(spec: ParserSpec) -> anyhow::Result<Self> {
+        let comment = spec.comment.as_deref().map(Regex::new).transpose()?;
+        let class_range = spec
+            .class_range
+            .map(|range| -> anyhow::Result<ClassRule> {
+                let delimiters = match (range.open, range.close) {
+                    // 空的分隔符永远匹配，块的结尾就找不到了
+                    (Some(open), Some(close)) if open.is_empty() || close.is_empty() => {
+                        anyhow::bail!("class_range open and close must not be empty")
+                    }
+                    (Some(open), Some(close)) => Some((open, close)),
+                    _ => None,
+                };
+                Ok(ClassRule {
+                    header: NameRule::compile(&range.main, &range.name)?,
+                    delimiters,
+                    method: range.function.map(|f| NameRule::compile(&f.main, &f.name)).transpose()?,
+                })
+            })
+            .transpose()?;
+        let function = spec.function.map(|f| NameRule::compile(&f.main, &f.name)).transpose()?;
+
+        Ok(Self {
+            id: spec.id,
+            extensions: spec.extensions,
+            comment,
+            class_range,
+            function,
+        })
+    }
+
+    pub fn extract_symbols(&self, content: &str) -> Vec<Symbol> {
+        // blank out comments but keep every byte offset where it was
+        let text = match &self.comment {
+            Some(comment) => comment.replace_all(content, |caps: &regex::Captures| {
+                caps[0].chars().map(|c| if c == '\n' { "\n".to_string() } else { " ".repeat(c.len_utf8()) }).collect::<String>()
+            }).into_owned(),
+            None => content.to_string(),
+        };
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset) - 1;
+
+        let mut symbols = Vec::new();
+        let mut ranges = Vec::new();
+
+        if let Some(class_rule) = &self.class_range {
+            let headers: Vec<(usize, usize, String)> = class_rule
+                .header
+                .main
+                .find_iter(&text)
+                .filter_map(|m| Some((symbol_start(&m), m.end(), class_rule.header.name_of(m.as_str())?)))
+                .collect();
+
+            for (i, (start, header_end, name)) in headers.iter().enumerate() {
+                let end = match &class_rule.delimiters {
+                    Some((open, close)) => block_end(&text, *header_end, open, close),
+                    None => headers.get(i + 1).map(|h| h.0).unwrap_or(text.len()),
+                };
+                ranges.push((*start, end));
+
+                let children = class_rule
+                    .method
+                    .as_ref()
+                    .map(|rule| {
+                        rule.find_all(&text[*header_end..end])
+                            .into_iter()
+                            .map(|(offset, name)| Symbol {
+                                name,
+                                kind: SymbolKind::Method,
+                                line: line_of(header_end + offset),
+                                children: Vec::new(),
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                symbols.push(Symbol {
+                    name: name.clone(),
+                    kind: SymbolKind::Class,
+                    line: line_of(*start),
+                    children,
+                });
+            }
+        }
+
+        if let Some(function_rule) = &self.function {
+            for (offset, name) in function_rule.find_all(&text) {
+                if ranges.iter().any(|(start, end)| offset >= *start && offset < *end) {
+                    continue;
+                }
+                symbols.push(Symbol {
+                    name,
+                    kind: SymbolKind::Function,
+                    line: line_of(offset),
+                    children: Vec::new(),
+                });
+            }
+        }
+
+        symbols.sort_by_key(|s| s.line);
+        symbols
+    }
+}
+
+impl NameRule {
+    fn compile(main: &str, name: &[String]) -> Result<Self, regex::Error> {
+        Ok(Self {
+            main: Regex::new(main)?,
+            name: name.iter().map(|n| Regex::new(n)).collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn name_of(&self, matched: &str) -> Option<String> {
+        let mut name = matched;
+        for rule in &self.name {
+            name = rule.find(name)?.as_str();
+        }
+        let name = name.trim();
+        (!name.is_empty()).then(|| name.to_string())
+    }
+
+    // (byte offset, name) of every match
+    fn find_all(&self, text: &str) -> Vec<(usize, String)> {
+        self.main
+            .find_iter(text)
+            .filter_map(|m| Some((symbol_start(&m), self.name_of(m.as_str())?)))
+            .collect()
+    }
+}
+
+// Start of a match without the leading whitespace, which may reach back over
+// blank lines and blanked comments
+fn symbol_start(m: &regex::Match) -> usize {
+    let text = m.as_str();
+    m.start() + (text.len() - text.trim_start().len())
+}
+
+// Offset just past the close symbol matching the first open symbol after `from`
+fn block_end(text: &str, from: usize, open: &str, close: &str) -> usize {
+    let Some(first) = text[from..].find(open) else {
+        return text.len();
+    };
+
+    let mut depth = 0usize;
+    let mut pos = from + first;
+    while pos < text.len() {
+        let rest = &text[pos..];
+        if rest.starts_with(open) {
+            depth += 1;
+            pos += open.len();
+        } else if rest.starts_with(close) {
+            pos += close.len();
+            // depth is at least 1 here, the search starts at an open symbol
+            depth = depth.saturating_sub(1);
+            if depth == 0 {
+                return pos;
+            }
+        } else {
+            pos += rest.chars().next().map(char::len_utf8).unwrap_or(1);
+        }
+    }
+    text.len()
+}
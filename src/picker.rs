@@ -0,0 +1,164 @@
+use eframe::egui::{self, Key, Modifiers};
+
+use crate::ui::UiComponents;
+
+pub struct PickerRow {
+    pub text: String,
+    // char indices of `text` that matched the query
+    pub highlights: Vec<usize>,
+    // shown right-aligned, e.g. the key binding
+    pub detail: Option<String>,
+}
+
+/// A filter box with a list under it, shown over the top of the window.
+/// The caller ranks rows against `query()` and gets back the chosen index.
+pub struct FuzzyPicker {
+    id: &'static str,
+    hint: &'static str,
+    is_open: bool,
+    query: String,
+    selected: usize,
+    focus_requested: bool,
+    footer: Option<String>,
+}
+
+impl FuzzyPicker {
+    pub fn new(id: &'static str, hint: &'static str) -> Self {
+        Self {
+            id,
+            hint,
+            is_open: false,
+            query: String::new(),
+            selected: 0,
+            focus_requested: false,
+            footer: None,
+        }
+    }
+
+    pub fn open(&mut self) {
+        self.is_open = true;
+        self.query.clear();
+        self.selected = 0;
+        self.focus_requested = true;
+    }
+
+    pub fn close(&mut self) {
+        self.is_open = false;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    // weak text under the list, e.g. progress of a background scan
+    pub fn set_footer(&mut self, footer: Option<String>) {
+        self.footer = footer;
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, rows: &[PickerRow]) -> Option<usize> {
+        if !self.is_open {
+            return None;
+        }
+
+        let mut chosen = None;
+
+        // handle navigation keys before the text box sees them
+        let (up, down, enter, escape) = ctx.input_mut(|i| {
+            (
+                i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                i.consume_key(Modifiers::NONE, Key::ArrowDown),
+                i.consume_key(Modifiers::NONE, Key::Enter),
+                i.consume_key(Modifiers::NONE, Key::Escape),
+            )
+        });
+        if escape {
+            self.close();
+            return None;
+        }
+        if up {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if down {
+            self.selected += 1;
+        }
+        self.selected = self.selected.min(rows.len().saturating_sub(1));
+        if enter && !rows.is_empty() {
+            chosen = Some(self.selected);
+        }
+
+        let response = egui::Window::new(self.id)
+            .title_bar(false)
+            .resizable(false)
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 40.0))
+            .fixed_size(egui::vec2(520.0, 0.0))
+            .show(ctx, |ui| {
+                let query_before = self.query.clone();
+                let edit = ui.add(
+                    egui::TextEdit::singleline(&mut self.query)
+                        .hint_text(self.hint)
+                        .desired_width(f32::INFINITY),
+                );
+                if self.focus_requested {
+                    edit.request_focus();
+                    self.focus_requested = false;
+                }
+                if self.query != query_before {
+                    self.selected = 0;
+                }
+
+                ui.separator();
+
+                let row_height = ui.spacing().interact_size.y;
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .auto_shrink([false, true])
+                    .show_rows(ui, row_height, rows.len(), |ui, range| {
+                        for i in range {
+                            let row = &rows[i];
+                            let is_selected = i == self.selected;
+                            let response = ui.horizontal(|ui| {
+                                let job = UiComponents::highlighted_text(ui, &row.text, &row.highlights);
+                                let label = ui.selectable_label(is_selected, job);
+                                if let Some(detail) = &row.detail {
+                                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                        ui.weak(detail);
+                                    });
+                                }
+                                label
+                            }).inner;
+
+                            if response.clicked() {
+                                chosen = Some(i);
+                            }
+                            if is_selected && (up || down) {
+                                response.scroll_to_me(None);
+                            }
+                        }
+                    });
+
+                if rows.is_empty() {
+                    ui.weak("No matches");
+                }
+                if let Some(footer) = &self.footer {
+                    ui.separator();
+                    ui.weak(footer);
+                }
+            });
+
+        if let Some(response) = response {
+            if response.response.clicked_elsewhere() {
+                self.close();
+            }
+        }
+
+        if chosen.is_some() {
+            self.close();
+        }
+        chosen
+    }
+}
@@ -0,0 +1,182 @@
+use eframe::egui;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::analysis::Background;
+use crate::fuzzy::{fuzzy_match, FuzzyMatch};
+use crate::picker::{FuzzyPicker, PickerRow};
+
+const MAX_RESULTS: usize = 200;
+const BATCH_SIZE: usize = 500;
+
+/// Paths under a folder, collected on a background thread. Files and folders
+/// excluded by `.gitignore` (and hidden ones) are skipped, whether or not the
+/// folder is a git checkout.
+pub struct FileIndex {
+    root: PathBuf,
+    // relative paths with `/` separators
+    files: Arc<Mutex<Vec<String>>>,
+    finished: Arc<AtomicBool>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl FileIndex {
+    pub fn build(root: PathBuf, ctx: egui::Context) -> Self {
+        let files = Arc::new(Mutex::new(Vec::new()));
+        let finished = Arc::new(AtomicBool::new(false));
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let walk_root = root.clone();
+        let walk_files = files.clone();
+        let walk_finished = finished.clone();
+        let walk_cancelled = cancelled.clone();
+        thread::spawn(move || {
+            let mut batch = Vec::with_capacity(BATCH_SIZE);
+            let walker = ignore::WalkBuilder::new(&walk_root).require_git(false).build();
+
+            for entry in walker {
+                if walk_cancelled.load(Ordering::Relaxed) {
+                    return;
+                }
+                let Ok(entry) = entry else {
+                    continue;
+                };
+                if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                    continue;
+                }
+                if let Ok(relative) = entry.path().strip_prefix(&walk_root) {
+                    batch.push(relative.to_string_lossy().replace('\\', "/"));
+                }
+                if batch.len() >= BATCH_SIZE {
+                    walk_files.lock().unwrap().append(&mut batch);
+                    ctx.request_repaint();
+                }
+            }
+
+            walk_files.lock().unwrap().append(&mut batch);
+            walk_finished.store(true, Ordering::Relaxed);
+            ctx.request_repaint();
+        });
+
+        Self { root, files, finished, cancelled }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.lock().unwrap().len()
+    }
+}
+
+impl Drop for FileIndex {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Ctrl+P overlay that fuzzy-finds files in the project folder.
+pub struct QuickOpen {
+    picker: FuzzyPicker,
+    index: Option<FileIndex>,
+    // ranked on a worker thread for a query and index size; the rows shown
+    // until then are the previous ranking's
+    results: Background<(String, usize), Vec<(String, FuzzyMatch)>>,
+}
+
+impl QuickOpen {
+    pub fn new() -> Self {
+        Self {
+            picker: FuzzyPicker::new("quick_open", "Search files by name..."),
+            index: None,
+            results: Background::new(),
+        }
+    }
+
+    pub fn open(&mut self, root: PathBuf, ctx: &egui::Context) {
+        // re-scan every time so new and deleted files show up
+        self.index = Some(FileIndex::build(root, ctx.clone()));
+        self.results = Background::new();
+        self.picker.open();
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.picker.is_open()
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<PathBuf> {
+        self.refresh_results(ctx);
+        let index = self.index.as_ref()?;
+        let results = self.results.get().map(|(_, results)| results.as_slice()).unwrap_or_default();
+
+        let rows: Vec<PickerRow> = results
+            .iter()
+            .map(|(path, m)| PickerRow {
+                text: path.clone(),
+                highlights: m.positions.clone(),
+                detail: None,
+            })
+            .collect();
+
+        let footer = if index.is_finished() {
+            format!("{} files in {}", index.len(), index.root().display())
+        } else {
+            format!("Indexing... {} files", index.len())
+        };
+        self.picker.set_footer(Some(footer));
+
+        let chosen = self
+            .picker
+            .show(ctx, &rows)
+            .map(|i| index.root().join(&results[i].0));
+        if !self.picker.is_open() {
+            // stop a scan that is still running
+            self.index = None;
+        }
+        chosen
+    }
+
+    fn refresh_results(&mut self, ctx: &egui::Context) {
+        let Some(index) = &self.index else {
+            return;
+        };
+        self.results.poll();
+        let key = (self.picker.query().to_string(), index.len());
+        if !self.results.wants(&key) {
+            return;
+        }
+        let files = index.files.clone();
+        let (query, len) = key.clone();
+        self.results.start(ctx, key, move || {
+            // 复制一份再排序，免得扫描线程一直等着锁
+            let files = files.lock().unwrap()[..len].to_vec();
+            rank(&query, &files)
+        });
+    }
+}
+
+// The best matches for `query`, at most `MAX_RESULTS` of them
+fn rank(query: &str, files: &[String]) -> Vec<(String, FuzzyMatch)> {
+    let mut results: Vec<(String, FuzzyMatch)> = files
+        .iter()
+        .filter_map(|path| {
+            let mut m = fuzzy_match(query, path)?;
+            // matches inside the file name count for more than matches in folders
+            let name_start = path.rfind('/').map(|i| path[..=i].chars().count()).unwrap_or(0);
+            if m.positions.first().map(|&p| p >= name_start).unwrap_or(false) {
+                m.score += 10;
+            }
+            Some((path.clone(), m))
+        })
+        .collect();
+    results.sort_by(|a, b| b.1.score.cmp(&a.1.score).then(a.0.len().cmp(&b.0.len())));
+    results.truncate(MAX_RESULTS);
+    results
+}
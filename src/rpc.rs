@@ -0,0 +1,291 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+pub const SOCKET_NAME: &str = "notion-pp-rpc.sock";
+
+// Standard JSON-RPC 2.0 error codes
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const APP_ERROR: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    #[serde(default)]
+    pub jsonrpc: String,
+    // None for a notification, which gets no reply
+    #[serde(default)]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new<S: Into<String>>(code: i64, message: S) -> Self {
+        Self { code, message: message.into() }
+    }
+
+    pub fn invalid_params<S: Into<String>>(message: S) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::new(APP_ERROR, format!("{:#}", err))
+    }
+}
+
+/// A request waiting to be executed on the UI thread.
+pub struct RpcCall {
+    pub method: String,
+    pub params: Value,
+    reply: Sender<Result<Value, RpcError>>,
+}
+
+impl RpcCall {
+    pub fn respond(self, result: Result<Value, RpcError>) {
+        // The client may already have hung up
+        let _ = self.reply.send(result);
+    }
+}
+
+/// Newline-delimited JSON-RPC 2.0 over a Unix socket. Each connection is served on
+/// its own thread, but the calls themselves are handed to the app one at a time so
+/// they run against the same state as the UI.
+pub struct RpcServer {
+    listener: UnixListener,
+}
+
+impl RpcServer {
+    pub fn bind(path: &Path) -> io::Result<Self> {
+        // A socket that nobody answers on is left over from a previous run
+        if UnixStream::connect(path).is_err() {
+            let _ = std::fs::remove_file(path);
+        }
+        let listener = UnixListener::bind(path)?;
+        Ok(Self { listener })
+    }
+
+    pub fn spawn(self, ctx: Option<egui::Context>) -> Receiver<RpcCall> {
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in self.listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let tx = tx.clone();
+                        let ctx = ctx.clone();
+                        thread::spawn(move || {
+                            if let Err(err) = serve_connection(stream, tx, ctx) {
+                                log::warn!("RPC connection closed with error: {}", err);
+                            }
+                        });
+                    }
+                    Err(err) => log::warn!("RPC socket accept failed: {}", err),
+                }
+            }
+        });
+
+        rx
+    }
+}
+
+fn serve_connection(
+    stream: UnixStream,
+    tx: Sender<RpcCall>,
+    ctx: Option<egui::Context>,
+) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let reader = BufReader::new(stream);
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) if request.jsonrpc != "2.0" => request.id.map(|id| {
+                Response::error(id, RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""))
+            }),
+            Ok(request) => {
+                let (reply_tx, reply_rx) = mpsc::channel();
+                let call = RpcCall {
+                    method: request.method,
+                    params: request.params,
+                    reply: reply_tx,
+                };
+                if tx.send(call).is_err() {
+                    // The app has shut down
+                    return Ok(());
+                }
+                if let Some(ctx) = &ctx {
+                    ctx.request_repaint();
+                }
+
+                // a notification still waits, so calls run in the order they came
+                let reply = match reply_rx.recv() {
+                    Ok(reply) => reply,
+                    Err(_) => return Ok(()),
+                };
+                request.id.map(|id| match reply {
+                    Ok(result) => Response::result(id, result),
+                    Err(err) => Response::error(id, err),
+                })
+            }
+            Err(err) => Some(Response::error(Value::Null, RpcError::new(PARSE_ERROR, err.to_string()))),
+        };
+        let Some(response) = response else {
+            continue;
+        };
+
+        let json = serde_json::to_string(&response)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        writeln!(writer, "{}", json)?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+impl Response {
+    fn result(id: Value, result: Value) -> Self {
+        Self { jsonrpc: "2.0", id, result: Some(result), error: None }
+    }
+
+    fn error(id: Value, error: RpcError) -> Self {
+        Self { jsonrpc: "2.0", id, result: None, error: Some(error) }
+    }
+}
+
+// Helpers for pulling typed parameters out of a call
+pub fn param_str<'a>(params: &'a Value, name: &str) -> Result<&'a str, RpcError> {
+    params
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::invalid_params(format!("missing string parameter \"{}\"", name)))
+}
+
+pub fn param_usize(params: &Value, name: &str) -> Result<usize, RpcError> {
+    params
+        .get(name)
+        .and_then(Value::as_u64)
+        .map(|n| n as usize)
+        .ok_or_else(|| RpcError::invalid_params(format!("missing integer parameter \"{}\"", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::NotionApp;
+    use crate::config::Config;
+    use serde_json::json;
+    use std::path::PathBuf;
+    use std::sync::mpsc::RecvTimeoutError;
+    use std::time::{Duration, Instant};
+
+    // 应用一直不回答时测试失败，而不是一直挂着
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("notion-pp-test-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn drives_headless_app_over_socket() {
+        let socket_path = temp_path("rpc.sock");
+        let file_path = temp_path("doc.txt");
+        std::fs::write(&file_path, "first line\nsecond line\n").unwrap();
+
+        let mut app = NotionApp::with_config(Config::default());
+        let rx = RpcServer::bind(&socket_path).unwrap().spawn(None);
+
+        let client_file = file_path.clone();
+        let client_socket = socket_path.clone();
+        let (done_tx, done_rx) = mpsc::channel();
+        let client = thread::spawn(move || {
+            let stream = UnixStream::connect(&client_socket).unwrap();
+            stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut notifier = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut call = |id: u64, method: &str, params: Value| -> Value {
+                let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+                writeln!(writer, "{}", request).unwrap();
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let response: Value = serde_json::from_str(&line).unwrap();
+                assert_eq!(response["id"], json!(id));
+                response
+            };
+
+            let opened = call(1, "open", json!({ "path": client_file }));
+            assert_eq!(opened["result"]["index"], json!(0));
+
+            call(2, "goto_line", json!({ "line": 2 }));
+            call(3, "insert", json!({ "text": "inserted " }));
+
+            let found = call(4, "find", json!({ "text": "second" }));
+            assert_eq!(found["result"]["found"], json!(true));
+
+            let buffer = call(5, "get_active_buffer", Value::Null);
+            assert_eq!(buffer["result"]["content"], json!("first line\ninserted second line\n"));
+            assert_eq!(buffer["result"]["modified"], json!(true));
+
+            // a notification gets no reply, so the next reply is for call 6
+            writeln!(notifier, "{}", json!({ "jsonrpc": "2.0", "method": "list_tabs" })).unwrap();
+            let tabs = call(6, "list_tabs", Value::Null);
+            assert_eq!(tabs["result"].as_array().unwrap().len(), 1);
+            assert_eq!(tabs["result"][0]["window"], json!(0));
+
+            let saved = call(7, "save_all", Value::Null);
+            assert_eq!(saved["result"]["saved"], json!(1));
+
+            let unknown = call(8, "no_such_method", Value::Null);
+            assert_eq!(unknown["error"]["code"], json!(METHOD_NOT_FOUND));
+            done_tx.send(()).unwrap();
+        });
+
+        let started = Instant::now();
+        loop {
+            app.handle_rpc_calls(&rx);
+            match done_rx.recv_timeout(Duration::from_millis(5)) {
+                // the client finished, or panicked and join says why
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => assert!(started.elapsed() < TIMEOUT, "RPC client timed out"),
+            }
+        }
+        client.join().unwrap();
+
+        let saved = std::fs::read_to_string(&file_path).unwrap();
+        assert_eq!(saved, "first line\ninserted second line\n");
+
+        let _ = std::fs::remove_file(&file_path);
+        let _ = std::fs::remove_file(&socket_path);
+    }
+}
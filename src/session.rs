@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use anyhow::{Result, Context};
+
+use crate::config::Config;
+use crate::editor::DocumentCollection;
+
+const SESSION_FILE: &str = "session.json";
+
+/// Open documents of every window, written on exit and restored on launch.
+/// The first entry is the main window.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Session {
+    pub windows: Vec<WindowSession>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WindowSession {
+    // only documents that exist on disk; untitled ones are not kept
+    pub files: Vec<PathBuf>,
+    #[serde(default)]
+    pub pinned: Vec<PathBuf>,
+    pub active: Option<usize>,
+    // outer position and inner size in points, for extra windows
+    #[serde(default)]
+    pub position: Option<[f32; 2]>,
+    #[serde(default)]
+    pub size: Option<[f32; 2]>,
+    // start lines of the collapsed fold regions of each file
+    #[serde(default)]
+    pub folds: HashMap<PathBuf, Vec<usize>>,
+    #[serde(default)]
+    pub bookmarks: HashMap<PathBuf, Vec<usize>>,
+}
+
+impl WindowSession {
+    pub fn from_documents(documents: &DocumentCollection, active: Option<usize>) -> Self {
+        let mut session = Self::default();
+        for i in 0..documents.len() {
+            let Some(doc) = documents.get(i) else {
+                continue;
+            };
+            let Some(path) = &doc.path else {
+                continue;
+            };
+            if Some(i) == active {
+                session.active = Some(session.files.len());
+            }
+            if doc.pinned {
+                session.pinned.push(path.clone());
+            }
+            let folded = doc.folded_lines();
+            if !folded.is_empty() {
+                session.folds.insert(path.clone(), folded);
+            }
+            let bookmarked = doc.bookmarked_lines();
+            if !bookmarked.is_empty() {
+                session.bookmarks.insert(path.clone(), bookmarked);
+            }
+            session.files.push(path.clone());
+        }
+        session
+    }
+}
+
+impl Session {
+    fn file_path() -> Option<PathBuf> {
+        Some(Config::config_dir()?.join(SESSION_FILE))
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = Self::file_path()
+            .ok_or_else(|| anyhow::anyhow!("Could not determine config directory"))?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read session file: {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| "Failed to parse session file")
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::file_path()
+            .ok_or_else(|| anyhow::anyhow!("Could not determine config directory"))?;
+        let text = serde_json::to_string_pretty(self)
+            .with_context(|| "Failed to serialize session")?;
+        fs::write(&path, text)
+            .with_context(|| format!("Failed to write session file: {}", path.display()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+use eframe::egui;
+
+use crate::editor::DocumentCollection;
+use crate::panes::index_after_move;
+use crate::ui::UiComponents;
+
+/// Things the app has to react to after the tab bar was drawn.
+pub enum TabAction {
+    Activate(usize),
+    Close(usize),
+    // close every unpinned tab but this one
+    CloseOthers(usize),
+    CloseToRight(usize),
+    TogglePin(usize),
+    Move { from: usize, to: usize },
+    // released outside the tab bar, at this position in the window
+    DragOut { index: usize, pos: egui::Pos2 },
+    MoveToNewWindow(usize),
+}
+
+// Drag-and-drop payload of a tab being dragged
+struct DraggedTab(usize);
+
+/// Single-row, scrollable tab bar with an overflow list of every open document.
+pub struct TabBar {
+    // tab that was last scrolled into view
+    revealed: Option<usize>,
+    // last pointer position while a tab is dragged; the pointer may be gone on release
+    drag_pos: Option<egui::Pos2>,
+    bar_rect: egui::Rect,
+}
+
+impl TabBar {
+    pub fn new() -> Self {
+        Self {
+            revealed: None,
+            drag_pos: None,
+            bar_rect: egui::Rect::NOTHING,
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, documents: &DocumentCollection, active: Option<usize>) -> Vec<TabAction> {
+        let mut actions = Vec::new();
+        let overflow_width = 28.0;
+        let row_height = 26.0;
+        self.bar_rect = ui.max_rect();
+
+        ui.horizontal(|ui| {
+            let tabs_width = (ui.available_width() - overflow_width - ui.spacing().item_spacing.x).max(0.0);
+            ui.allocate_ui(egui::vec2(tabs_width, row_height), |ui| {
+                egui::ScrollArea::horizontal()
+                    .id_source("tabs_scroll")
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            for i in 0..documents.len() {
+                                self.show_tab(ui, documents, i, active, &mut actions);
+                            }
+                        });
+                    });
+            });
+
+            ui.menu_button("⏷", |ui| {
+                for i in 0..documents.len() {
+                    let Some(doc) = documents.get(i) else {
+                        continue;
+                    };
+                    let mut text = doc.filename.clone();
+                    if doc.is_modified {
+                        text.push('*');
+                    }
+                    if ui.selectable_label(Some(i) == active, text).clicked() {
+                        actions.push(TabAction::Activate(i));
+                        ui.close_menu();
+                    }
+                }
+            })
+            .response
+            .on_hover_text("All open documents");
+        });
+
+        self.revealed = active;
+        actions
+    }
+
+    fn show_tab(
+        &mut self,
+        ui: &mut egui::Ui,
+        documents: &DocumentCollection,
+        i: usize,
+        active: Option<usize>,
+        actions: &mut Vec<TabAction>,
+    ) {
+        let Some(doc) = documents.get(i) else {
+            return;
+        };
+        let is_active = Some(i) == active;
+
+        let response = UiComponents::file_tab(ui, &doc.filename, is_active, doc.is_modified);
+        let response = match &doc.path {
+            Some(path) => response.on_hover_text(path.display().to_string()),
+            None => response,
+        };
+        response.dnd_set_drag_payload(DraggedTab(i));
+
+        if response.clicked() {
+            actions.push(TabAction::Activate(i));
+        }
+        if response.dragged() {
+            self.drag_pos = ui.ctx().pointer_latest_pos().or(self.drag_pos);
+        }
+        if response.drag_released() {
+            if let Some(pos) = self.drag_pos.take() {
+                if !self.bar_rect.contains(pos) {
+                    actions.push(TabAction::DragOut { index: i, pos });
+                }
+            }
+        }
+        if response.middle_clicked() && !doc.pinned {
+            actions.push(TabAction::Close(i));
+        }
+        if is_active && self.revealed != active {
+            response.scroll_to_me(None);
+        }
+
+        // 拖到标签左半边插入到它之前，右半边插入到它之后
+        if let Some(dragged) = response.dnd_hover_payload::<DraggedTab>() {
+            if let Some(pointer) = ui.ctx().pointer_interact_pos() {
+                let before = pointer.x < response.rect.center().x;
+                let x = if before { response.rect.left() } else { response.rect.right() } - 1.0;
+                let stroke = egui::Stroke::new(2.0, ui.visuals().selection.stroke.color);
+                ui.painter().vline(x, response.rect.y_range(), stroke);
+
+                if response.dnd_release_payload::<DraggedTab>().is_some() {
+                    let insert_at = if before { i } else { i + 1 };
+                    if let Some(to) = drop_position(documents, dragged.0, insert_at) {
+                        actions.push(TabAction::Move { from: dragged.0, to });
+                    }
+                }
+            }
+        }
+
+        if doc.pinned {
+            if ui.small_button("📌").on_hover_text("Unpin").clicked() {
+                actions.push(TabAction::TogglePin(i));
+            }
+        } else if ui.small_button("×").clicked() {
+            actions.push(TabAction::Close(i));
+        }
+
+        response.context_menu(|ui| {
+            if ui.button("Close").clicked() {
+                actions.push(TabAction::Close(i));
+                ui.close_menu();
+            }
+            if ui.button("Close Others").clicked() {
+                actions.push(TabAction::CloseOthers(i));
+                ui.close_menu();
+            }
+            if ui.button("Close to the Right").clicked() {
+                actions.push(TabAction::CloseToRight(i));
+                ui.close_menu();
+            }
+
+            ui.separator();
+
+            if ui.button(if doc.pinned { "Unpin" } else { "Pin" }).clicked() {
+                actions.push(TabAction::TogglePin(i));
+                ui.close_menu();
+            }
+            if ui.button("Move to New Window").clicked() {
+                actions.push(TabAction::MoveToNewWindow(i));
+                ui.close_menu();
+            }
+            if let Some(path) = &doc.path {
+                if ui.button("Copy Path").clicked() {
+                    ui.ctx().output_mut(|o| o.copied_text = path.display().to_string());
+                    ui.close_menu();
+                }
+            }
+        });
+
+        ui.separator();
+    }
+}
+
+// Final index of a tab dropped at `insert_at`, kept inside its pinned or
+// unpinned group. None if it would not move.
+fn drop_position(documents: &DocumentCollection, from: usize, insert_at: usize) -> Option<usize> {
+    let pinned = documents.get(from)?.pinned;
+    let pinned_count = documents.pinned_count();
+    let to = if insert_at > from { insert_at - 1 } else { insert_at };
+    let to = if pinned {
+        to.min(pinned_count.saturating_sub(1))
+    } else {
+        to.clamp(pinned_count, documents.len().saturating_sub(1))
+    };
+    (to != from).then_some(to)
+}
+
+/// Documents in most-recently-used order for Ctrl+Tab. While the user holds
+/// Ctrl and keeps pressing Tab the order stays put; letting go of Ctrl puts
+/// the chosen document at the front.
+pub struct TabHistory {
+    recent: Vec<usize>,
+    // position in `order()` while cycling
+    cycling: Option<usize>,
+}
+
+impl TabHistory {
+    pub fn new() -> Self {
+        Self { recent: Vec::new(), cycling: None }
+    }
+
+    // Called every frame with the active document
+    pub fn update(&mut self, ctx: &egui::Context, active: Option<usize>) {
+        if self.cycling.is_some() {
+            if ctx.input(|i| i.modifiers.command) {
+                return;
+            }
+            self.cycling = None;
+        }
+        if let Some(idx) = active {
+            if self.recent.first() != Some(&idx) {
+                self.recent.retain(|&i| i != idx);
+                self.recent.insert(0, idx);
+            }
+        }
+    }
+
+    // Next document to show when cycling, given how many documents are open
+    pub fn cycle(&mut self, forward: bool, count: usize) -> Option<usize> {
+        let order = self.order(count);
+        if order.len() < 2 {
+            return None;
+        }
+        let pos = self.cycling.unwrap_or(0);
+        let next = if forward {
+            (pos + 1) % order.len()
+        } else {
+            (pos + order.len() - 1) % order.len()
+        };
+        self.cycling = Some(next);
+        Some(order[next])
+    }
+
+    pub fn document_closed(&mut self, index: usize) {
+        self.recent.retain(|&i| i != index);
+        for i in &mut self.recent {
+            if *i > index {
+                *i -= 1;
+            }
+        }
+        self.cycling = None;
+    }
+
+    pub fn document_inserted(&mut self, index: usize) {
+        for i in &mut self.recent {
+            if *i >= index {
+                *i += 1;
+            }
+        }
+    }
+
+    pub fn document_moved(&mut self, from: usize, to: usize) {
+        for i in &mut self.recent {
+            *i = index_after_move(*i, from, to);
+        }
+    }
+
+    // Recently used documents first, then the never visited ones by position
+    fn order(&self, count: usize) -> Vec<usize> {
+        let mut order: Vec<usize> = self.recent.iter().copied().filter(|&i| i < count).collect();
+        order.extend((0..count).filter(|i| !self.recent.contains(i)));
+        order
+    }
+}
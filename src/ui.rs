@@ -16,23 +16,33 @@ impl UiComponents {
         });
     }
     
-    pub fn file_tab(ui: &mut egui::Ui, name: &str, is_active: bool, is_modified: bool) -> bool {
-        let mut clicked = false;
-        
+    pub fn file_tab(ui: &mut egui::Ui, name: &str, is_active: bool, is_modified: bool) -> egui::Response {
         let padding = Vec2::new(10.0, 5.0);
         let rounding = Rounding::same(4.0);
         
+        let mut tab_text = name.to_string();
+        if is_modified {
+            tab_text.push('*');
+        }
+        
+        let text_color = if is_active {
+            ui.visuals().widgets.active.fg_stroke.color
+        } else {
+            ui.visuals().widgets.inactive.fg_stroke.color
+        };
+        
+        // 标签宽度随文件名变化，避免长文件名被截断
+        let galley = ui.painter().layout_no_wrap(tab_text, egui::FontId::default(), text_color);
+        let width = (galley.size().x + padding.x * 2.0).clamp(60.0, 240.0);
         let (rect, response) = ui.allocate_exact_size(
-            Vec2::new(ui.available_width().min(120.0), 24.0),
-            egui::Sense::click(),
+            Vec2::new(width, 24.0),
+            egui::Sense::click_and_drag(),
         );
         
-        if response.clicked() {
-            clicked = true;
-        }
-        
         let bg_color = if is_active {
             ui.visuals().widgets.active.bg_fill
+        } else if response.hovered() {
+            ui.visuals().widgets.hovered.bg_fill
         } else {
             ui.visuals().widgets.inactive.bg_fill
         };
@@ -43,31 +53,13 @@ impl UiComponents {
             Stroke::NONE
         };
         
-        let text_color = if is_active {
-            ui.visuals().widgets.active.fg_stroke.color
-        } else {
-            ui.visuals().widgets.inactive.fg_stroke.color
-        };
-        
-        let painter = ui.painter();
-        
+        let painter = ui.painter_at(rect);
         painter.rect(rect, rounding, bg_color, stroke);
         
-        let mut tab_text = name.to_string();
-        if is_modified {
-            tab_text.push('*');
-        }
+        let text_pos = egui::pos2(rect.left() + padding.x, rect.center().y - galley.size().y / 2.0);
+        painter.galley(text_pos, galley, text_color);
         
-        let text_rect = rect.shrink2(padding);
-        painter.text(
-            text_rect.center(),
-            egui::Align2::CENTER_CENTER,
-            &tab_text,
-            egui::FontId::default(),
-            text_color,
-        );
-        
-        clicked
+        response
     }
     
     pub fn line_info_panel(ui: &mut egui::Ui, line: usize, column: usize, selection: Option<(usize, usize)>) {
@@ -103,6 +95,42 @@ impl UiComponents {
         (find_changed, replace_clicked)
     }
     
+    // 高亮显示模糊匹配命中的字符
+    pub fn highlighted_text(ui: &egui::Ui, text: &str, highlights: &[usize]) -> egui::text::LayoutJob {
+        let mut job = egui::text::LayoutJob::default();
+        let font_id = egui::TextStyle::Button.resolve(ui.style());
+        let normal = egui::TextFormat {
+            font_id: font_id.clone(),
+            color: ui.visuals().text_color(),
+            ..Default::default()
+        };
+        let highlighted = egui::TextFormat {
+            font_id,
+            color: ui.visuals().hyperlink_color,
+            underline: Stroke::new(1.0, ui.visuals().hyperlink_color),
+            ..Default::default()
+        };
+
+        // 合并连续相同格式的字符
+        let mut run = String::new();
+        let mut run_highlighted = false;
+        for (i, c) in text.chars().enumerate() {
+            let is_highlighted = highlights.contains(&i);
+            if is_highlighted != run_highlighted && !run.is_empty() {
+                let format = if run_highlighted { &highlighted } else { &normal };
+                job.append(&run, 0.0, format.clone());
+                run.clear();
+            }
+            run_highlighted = is_highlighted;
+            run.push(c);
+        }
+        if !run.is_empty() {
+            let format = if run_highlighted { &highlighted } else { &normal };
+            job.append(&run, 0.0, format.clone());
+        }
+        job
+    }
+
     pub fn draw_tooltip(ui: &egui::Ui, text: &str, rect: Rect) {
         let layer_id = egui::LayerId::new(egui::Order::Tooltip, egui::Id::new("tooltip"));
         let tooltip_rect = Rect::from_min_size(
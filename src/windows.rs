@@ -0,0 +1,259 @@
+use eframe::egui::{self, Pos2, Rect};
+use std::path::PathBuf;
+
+use crate::app::{show_drop_overlay, text_file_dialog};
+use crate::commands::Command;
+use crate::editor::{self, Document, DocumentCollection};
+use crate::keymap::Keymap;
+use crate::panes::{index_after_close, index_after_move};
+use crate::tabs::{TabAction, TabBar};
+
+// 标题栏的大致高度，至少要留在屏幕上才能拖动窗口
+const TITLE_BAR_HEIGHT: f32 = 32.0;
+
+/// Where to move a window whose title bar is not fully on the monitor, e.g.
+/// a position saved while a monitor that is gone now was attached. egui only
+/// knows the size of the current monitor, taken to start at the origin.
+pub fn position_on_screen(outer: Rect, monitor: egui::Vec2) -> Option<Pos2> {
+    let x = outer.min.x.min(monitor.x - outer.width()).max(0.0);
+    let y = outer.min.y.min(monitor.y - TITLE_BAR_HEIGHT).max(0.0);
+    let pos = egui::pos2(x, y);
+    (pos != outer.min).then_some(pos)
+}
+
+/// Things the app has to react to after an extra window was drawn.
+pub enum WindowEvent {
+    // a tab was released at this screen position, outside the window
+    TabDraggedOut { index: usize, screen_pos: Pos2 },
+    MoveToNewWindow(usize),
+    // files or folders dropped onto this window
+    FilesDropped(Vec<PathBuf>),
+    Closed,
+}
+
+/// An extra top-level editor window with its own tabs. It is drawn as an
+/// immediate egui viewport, so it runs in the same frame as the main window.
+pub struct EditorWindow {
+    pub id: egui::ViewportId,
+    pub documents: DocumentCollection,
+    pub active_document_index: Option<usize>,
+    // inner rect in screen coordinates as of the last frame
+    pub screen_rect: Option<Rect>,
+    // outer position on screen, to restore the window in the next session
+    pub position: Option<Pos2>,
+    initial_position: Option<Pos2>,
+    initial_size: egui::Vec2,
+    // whether the restored position was checked against the monitor yet
+    position_checked: bool,
+    tab_bar: TabBar,
+}
+
+impl EditorWindow {
+    pub fn new(serial: u64, position: Option<Pos2>, size: Option<egui::Vec2>) -> Self {
+        Self {
+            id: egui::ViewportId::from_hash_of(("editor_window", serial)),
+            documents: DocumentCollection::new(),
+            active_document_index: None,
+            screen_rect: None,
+            position,
+            initial_position: position,
+            initial_size: size.unwrap_or(egui::vec2(900.0, 600.0)),
+            position_checked: false,
+            tab_bar: TabBar::new(),
+        }
+    }
+
+    pub fn add(&mut self, doc: Document) {
+        self.documents.add(doc);
+        self.active_document_index = Some(self.documents.len() - 1);
+    }
+
+    pub fn take(&mut self, index: usize) -> Option<Document> {
+        let doc = self.documents.take(index)?;
+        self.active_document_index = index_after_close(self.active_document_index, index, self.documents.len());
+        Some(doc)
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, keymap: &mut Keymap) -> Vec<WindowEvent> {
+        let title = match self.active_document_index.and_then(|idx| self.documents.get(idx)) {
+            Some(doc) => format!("{} - Notion++", doc.filename),
+            None => "Notion++".to_string(),
+        };
+        let mut builder = egui::ViewportBuilder::default()
+            .with_title(title)
+            .with_inner_size(self.initial_size)
+            .with_min_inner_size([300.0, 200.0]);
+        if let Some(pos) = self.initial_position {
+            builder = builder.with_position(pos);
+        }
+
+        ctx.show_viewport_immediate(self.id, builder, |ctx, _class| {
+            let mut events = Vec::new();
+            let info = ctx.input(|i| i.viewport().clone());
+            self.screen_rect = info.inner_rect;
+            if let Some(outer) = info.outer_rect {
+                self.position = Some(outer.min);
+                if let (false, Some(monitor)) = (self.position_checked, info.monitor_size) {
+                    self.position_checked = true;
+                    if let Some(pos) = position_on_screen(outer, monitor) {
+                        ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(pos));
+                    }
+                }
+            }
+            if info.close_requested() {
+                events.push(WindowEvent::Closed);
+            }
+
+            let (hovered, dropped) = ctx.input(|i| {
+                let dropped: Vec<PathBuf> = i.raw.dropped_files.iter().filter_map(|file| file.path.clone()).collect();
+                (i.raw.hovered_files.len(), dropped)
+            });
+            if hovered > 0 {
+                show_drop_overlay(ctx, hovered);
+            }
+            if !dropped.is_empty() {
+                events.push(WindowEvent::FilesDropped(dropped));
+            }
+
+            for cmd in keymap.process_input(ctx) {
+                self.execute_command(ctx, cmd);
+            }
+
+            if self.documents.len() > 0 {
+                let actions = egui::TopBottomPanel::top("window_tabs_bar")
+                    .show(ctx, |ui| self.tab_bar.show(ui, &self.documents, self.active_document_index))
+                    .inner;
+                for action in actions {
+                    self.handle_tab_action(action, &mut events);
+                }
+            }
+
+            egui::CentralPanel::default().show(ctx, |ui| {
+                match self.active_document_index.and_then(|idx| self.documents.get_mut(idx)) {
+                    Some(doc) => doc.ui(ui),
+                    None => {
+                        ui.vertical_centered(|ui| {
+                            ui.add_space(100.0);
+                            ui.weak("Drag a tab here, or close this window to return to the main one");
+                        });
+                    }
+                }
+            });
+
+            // tab positions are local to this window; the app needs them on screen
+            for event in &mut events {
+                if let WindowEvent::TabDraggedOut { screen_pos, .. } = event {
+                    if let Some(rect) = self.screen_rect {
+                        *screen_pos += rect.min.to_vec2();
+                    }
+                }
+            }
+            events
+        })
+    }
+
+    // Only the commands that make sense without the main window's panels
+    fn execute_command(&mut self, ctx: &egui::Context, cmd: Command) {
+        if let Some(doc) = self.active_document_index.and_then(|idx| self.documents.get_mut(idx)) {
+            if editor::execute(ctx, doc, cmd) {
+                return;
+            }
+        }
+        let count = self.documents.len();
+        match cmd {
+            Command::NewFile => self.add(Document::new()),
+            Command::Save | Command::SaveAs => {
+                let Some(doc) = self.active_document_index.and_then(|idx| self.documents.get_mut(idx)) else {
+                    return;
+                };
+                let result = if doc.path.is_none() || cmd == Command::SaveAs {
+                    match text_file_dialog().save_file() {
+                        Some(path) => doc.save_to_file(&path),
+                        None => Ok(()),
+                    }
+                } else {
+                    doc.save()
+                };
+                if let Err(err) = result {
+                    log::error!("Failed to save document: {}", err);
+                }
+            }
+            Command::CloseTab => {
+                if let Some(idx) = self.active_document_index {
+                    self.close(idx);
+                }
+            }
+            Command::NextTab | Command::NextRecentTab if count > 0 => {
+                self.active_document_index = Some(self.active_document_index.map(|i| (i + 1) % count).unwrap_or(0));
+            }
+            Command::PreviousTab | Command::PreviousRecentTab if count > 0 => {
+                self.active_document_index = Some(self.active_document_index.map(|i| (i + count - 1) % count).unwrap_or(0));
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_tab_action(&mut self, action: TabAction, events: &mut Vec<WindowEvent>) {
+        match action {
+            TabAction::Activate(idx) => self.active_document_index = Some(idx),
+            TabAction::Close(idx) => self.close(idx),
+            TabAction::CloseOthers(keep) => {
+                for idx in self.documents.close_other_tabs(keep) {
+                    self.document_closed(idx);
+                }
+            }
+            TabAction::CloseToRight(from) => {
+                for idx in self.documents.close_tabs_to_right(from) {
+                    self.document_closed(idx);
+                }
+            }
+            TabAction::TogglePin(idx) => {
+                if let Some(to) = self.documents.toggle_pin(idx) {
+                    self.move_tab(idx, to);
+                }
+            }
+            TabAction::Move { from, to } => self.move_tab(from, to),
+            TabAction::DragOut { index, pos } => {
+                // 仍在本窗口内松开时不做处理
+                let inside = self.screen_rect.map(|rect| Rect::from_min_size(Pos2::ZERO, rect.size()).contains(pos));
+                if inside != Some(true) {
+                    events.push(WindowEvent::TabDraggedOut { index, screen_pos: pos });
+                }
+            }
+            TabAction::MoveToNewWindow(idx) => events.push(WindowEvent::MoveToNewWindow(idx)),
+        }
+    }
+
+    fn close(&mut self, idx: usize) {
+        if self.documents.close(idx) {
+            self.document_closed(idx);
+        }
+    }
+
+    fn document_closed(&mut self, idx: usize) {
+        self.active_document_index = index_after_close(self.active_document_index, idx, self.documents.len());
+    }
+
+    fn move_tab(&mut self, from: usize, to: usize) {
+        if self.documents.move_tab(from, to) {
+            self.active_document_index = self.active_document_index.map(|idx| index_after_move(idx, from, to));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_bar_is_pulled_back_on_screen() {
+        let monitor = egui::vec2(1920.0, 1080.0);
+        let size = egui::vec2(800.0, 600.0);
+        let at = |x: f32, y: f32| Rect::from_min_size(egui::pos2(x, y), size);
+        assert_eq!(position_on_screen(at(100.0, 100.0), monitor), None);
+        // 标题栏在屏幕上方
+        assert_eq!(position_on_screen(at(100.0, -20.0), monitor), Some(egui::pos2(100.0, 0.0)));
+        // 在已经不存在的右侧显示器上
+        assert_eq!(position_on_screen(at(2500.0, 1200.0), monitor), Some(egui::pos2(1120.0, 1048.0)));
+    }
+}